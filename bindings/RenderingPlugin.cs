@@ -0,0 +1,234 @@
+// <auto-generated>
+// Generated by build.rs from the RenderingPlugin exports. Do not edit by hand.
+// </auto-generated>
+using System;
+using System.Runtime.InteropServices;
+
+namespace RenderingPlugin
+{
+    public enum RenderEventId : int
+    {
+        Default = 1,
+        FlushDebugDraw = 2,
+    }
+
+    public enum VideoFormat : int
+    {
+        Nv12 = 0,
+        I420 = 1,
+    }
+
+    public enum YuvColorSpace : int
+    {
+        Bt601 = 0,
+        Bt709 = 1,
+    }
+
+    public enum YuvRange : int
+    {
+        Limited = 0,
+        Full = 1,
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct VideoFrame
+    {
+        public int format;
+        public int width;
+        public int height;
+        public int color_space;
+        public int range;
+        public IntPtr y_plane;
+        public int y_stride;
+        public IntPtr u_plane;
+        public int u_stride;
+        public IntPtr v_plane;
+        public int v_stride;
+    }
+
+    [Flags]
+    public enum Capability : uint
+    {
+        Renderer = 0x00000001,
+        ColoredTriangle = 0x00000002,
+        TexturedQuad = 0x00000004,
+        ModifyTexture = 0x00000008,
+        ModifyVertexBuffer = 0x00000010,
+        ModifyIndexBuffer = 0x00000020,
+        DebugDraw = 0x00000040,
+        CameraMatrices = 0x00000080,
+        ShaderHotReload = 0x00000100,
+        FrameCapture = 0x00000200,
+        TextureFeed = 0x00000400,
+        VideoFrames = 0x00000800,
+        ImageLoading = 0x00001000,
+    }
+
+    public enum ValidationSeverity : int
+    {
+        Verbose = 0,
+        Info = 1,
+        Warning = 2,
+        Error = 3,
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct Vec3
+    {
+        public float x;
+        public float y;
+        public float z;
+    }
+
+    public enum IndexFormat : int
+    {
+        UInt16 = 0,
+        UInt32 = 1,
+    }
+
+    public static class NativeMethods
+    {
+#if (UNITY_IOS || UNITY_WEBGL) && !UNITY_EDITOR
+        const string DllName = "__Internal";
+#else
+        const string DllName = "RenderingPlugin";
+#endif
+
+        public const uint AbiVersion = 2;
+
+        [DllImport(DllName)]
+        public static extern void SetTimeFromUnity(float t);
+
+        [DllImport(DllName)]
+        public static extern void SetTimeFromUnityDouble(double t);
+
+        [DllImport(DllName)]
+        public static extern void SetTimePaused([MarshalAs(UnmanagedType.I1)] bool paused);
+
+        [DllImport(DllName)]
+        public static extern void SetTimeScale(double scale);
+
+        [DllImport(DllName)]
+        public static extern void SetFixedTimeStep(double step);
+
+        [DllImport(DllName)]
+        public static extern void ResetTime();
+
+        [DllImport(DllName)]
+        public static extern double GetTime();
+
+        [DllImport(DllName)]
+        public static extern double GetDeltaTime();
+
+        [DllImport(DllName)]
+        public static extern ulong GetFrameIndex();
+
+        [DllImport(DllName)]
+        public static extern void SetTextureFromUnity(IntPtr handle, int w, int h);
+
+        [DllImport(DllName)]
+        public static extern void SetMeshBuffersFromUnity(IntPtr handle, int vertex_count, float[] source_vertices, float[] source_normals, float[] source_uv);
+
+        [DllImport(DllName)]
+        public static extern void SetMeshIndexBufferFromUnity(IntPtr handle, int index_count, int index_format, IntPtr source_indices);
+
+        [DllImport(DllName)]
+        public static extern int GetActiveRenderer();
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool IsRenderApiAvailable();
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool GetUsesReverseZ();
+
+        [DllImport(DllName)]
+        public static extern void SetShaderDirectory([MarshalAs(UnmanagedType.LPStr)] string path);
+
+        [DllImport(DllName)]
+        public static extern void SetCameraMatrices(float[] view, float[] projection);
+
+        [DllImport(DllName)]
+        public static extern void SetOverlayTextureFromUnity(IntPtr handle);
+
+        [DllImport(DllName)]
+        public static extern IntPtr GetRenderEventFunc();
+
+        [DllImport(DllName)]
+        public static extern void DebugDrawLine(Vec3 from, Vec3 to, uint color);
+
+        [DllImport(DllName)]
+        public static extern void DebugDrawBox(Vec3 center, Vec3 half_extents, uint color);
+
+        [DllImport(DllName)]
+        public static extern void DebugDrawSphere(Vec3 center, float radius, uint color);
+
+        [DllImport(DllName)]
+        public static extern void DebugDrawArrow(Vec3 from, Vec3 to, uint color);
+
+        [DllImport(DllName)]
+        public static extern void DebugDrawAxes(float[] matrix, float size);
+
+        [DllImport(DllName)]
+        public static extern void DebugDrawQuad(Vec3 p0, Vec3 p1, Vec3 p2, Vec3 p3, uint color);
+
+        [DllImport(DllName)]
+        public static extern void DebugDrawClear();
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool StartRecording([MarshalAs(UnmanagedType.LPStr)] string path);
+
+        [DllImport(DllName)]
+        public static extern void StopRecording();
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool CaptureFrame([MarshalAs(UnmanagedType.LPStr)] string path);
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool DumpVulkanTrace([MarshalAs(UnmanagedType.LPStr)] string path);
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool SetTextureFeed([MarshalAs(UnmanagedType.LPStr)] string path);
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool LoadImageIntoTexture(IntPtr handle, [MarshalAs(UnmanagedType.LPStr)] string path);
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool SubmitVideoFrame(VideoFrame frame);
+
+        [DllImport(DllName)]
+        public static extern void SetVideoPlaneTexturesFromUnity(IntPtr luma, int luma_width, int luma_height, IntPtr chroma, int chroma_width, int chroma_height);
+
+        [DllImport(DllName)]
+        public static extern void StopVideo();
+
+        [DllImport(DllName)]
+        [return: MarshalAs(UnmanagedType.I1)]
+        public static extern bool GetYuvToRgbMatrix(int color_space, int range, [Out] float[] matrix);
+
+        [DllImport(DllName)]
+        public static extern IntPtr GetPluginVersion();
+
+        [DllImport(DllName)]
+        public static extern uint GetPluginAbiVersion();
+
+        [DllImport(DllName)]
+        public static extern uint QueryCapabilities(int renderer);
+
+        [DllImport(DllName)]
+        public static extern uint GetValidationMessageCount(int severity);
+
+        [DllImport(DllName)]
+        public static extern void ResetValidationMessageCounts();
+
+        [DllImport(DllName)]
+        public static extern void SetValidationLogSeverity(int severity);
+    }
+}
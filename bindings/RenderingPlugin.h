@@ -0,0 +1,137 @@
+/* Generated by build.rs from the RenderingPlugin exports. Do not edit by hand. */
+#ifndef RENDERING_PLUGIN_H
+#define RENDERING_PLUGIN_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#define RENDERING_PLUGIN_ABI_VERSION 2
+
+#if defined(_WIN32)
+#define RENDERING_PLUGIN_API __stdcall
+#else
+#define RENDERING_PLUGIN_API
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef void (RENDERING_PLUGIN_API *RenderingPluginRenderingEvent)(int32_t eventId);
+
+typedef enum RenderEventId {
+    RenderEventId_Default = 1,
+    RenderEventId_FlushDebugDraw = 2,
+} RenderEventId;
+
+typedef enum VideoFormat {
+    VideoFormat_Nv12 = 0,
+    VideoFormat_I420 = 1,
+} VideoFormat;
+
+typedef enum YuvColorSpace {
+    YuvColorSpace_Bt601 = 0,
+    YuvColorSpace_Bt709 = 1,
+} YuvColorSpace;
+
+typedef enum YuvRange {
+    YuvRange_Limited = 0,
+    YuvRange_Full = 1,
+} YuvRange;
+
+typedef struct VideoFrame {
+    int32_t format;
+    int32_t width;
+    int32_t height;
+    int32_t color_space;
+    int32_t range;
+    void* y_plane;
+    int32_t y_stride;
+    void* u_plane;
+    int32_t u_stride;
+    void* v_plane;
+    int32_t v_stride;
+} VideoFrame;
+
+typedef enum Capability {
+    Capability_Renderer = 0x00000001u,
+    Capability_ColoredTriangle = 0x00000002u,
+    Capability_TexturedQuad = 0x00000004u,
+    Capability_ModifyTexture = 0x00000008u,
+    Capability_ModifyVertexBuffer = 0x00000010u,
+    Capability_ModifyIndexBuffer = 0x00000020u,
+    Capability_DebugDraw = 0x00000040u,
+    Capability_CameraMatrices = 0x00000080u,
+    Capability_ShaderHotReload = 0x00000100u,
+    Capability_FrameCapture = 0x00000200u,
+    Capability_TextureFeed = 0x00000400u,
+    Capability_VideoFrames = 0x00000800u,
+    Capability_ImageLoading = 0x00001000u,
+} Capability;
+
+typedef enum ValidationSeverity {
+    ValidationSeverity_Verbose = 0,
+    ValidationSeverity_Info = 1,
+    ValidationSeverity_Warning = 2,
+    ValidationSeverity_Error = 3,
+} ValidationSeverity;
+
+typedef struct Vec3 {
+    float x;
+    float y;
+    float z;
+} Vec3;
+
+typedef enum IndexFormat {
+    IndexFormat_UInt16 = 0,
+    IndexFormat_UInt32 = 1,
+} IndexFormat;
+
+void RENDERING_PLUGIN_API SetTimeFromUnity(float t);
+void RENDERING_PLUGIN_API SetTimeFromUnityDouble(double t);
+void RENDERING_PLUGIN_API SetTimePaused(bool paused);
+void RENDERING_PLUGIN_API SetTimeScale(double scale);
+void RENDERING_PLUGIN_API SetFixedTimeStep(double step);
+void RENDERING_PLUGIN_API ResetTime(void);
+double RENDERING_PLUGIN_API GetTime(void);
+double RENDERING_PLUGIN_API GetDeltaTime(void);
+uint64_t RENDERING_PLUGIN_API GetFrameIndex(void);
+void RENDERING_PLUGIN_API SetTextureFromUnity(void* handle, int32_t w, int32_t h);
+void RENDERING_PLUGIN_API SetMeshBuffersFromUnity(void* handle, int32_t vertex_count, const float* source_vertices, const float* source_normals, const float* source_uv);
+void RENDERING_PLUGIN_API SetMeshIndexBufferFromUnity(void* handle, int32_t index_count, int32_t index_format, void* source_indices);
+int32_t RENDERING_PLUGIN_API GetActiveRenderer(void);
+bool RENDERING_PLUGIN_API IsRenderApiAvailable(void);
+bool RENDERING_PLUGIN_API GetUsesReverseZ(void);
+void RENDERING_PLUGIN_API SetShaderDirectory(const char* path);
+void RENDERING_PLUGIN_API SetCameraMatrices(const float* view, const float* projection);
+void RENDERING_PLUGIN_API SetOverlayTextureFromUnity(void* handle);
+RenderingPluginRenderingEvent RENDERING_PLUGIN_API GetRenderEventFunc(void);
+void RENDERING_PLUGIN_API DebugDrawLine(Vec3 from, Vec3 to, uint32_t color);
+void RENDERING_PLUGIN_API DebugDrawBox(Vec3 center, Vec3 half_extents, uint32_t color);
+void RENDERING_PLUGIN_API DebugDrawSphere(Vec3 center, float radius, uint32_t color);
+void RENDERING_PLUGIN_API DebugDrawArrow(Vec3 from, Vec3 to, uint32_t color);
+void RENDERING_PLUGIN_API DebugDrawAxes(const float* matrix, float size);
+void RENDERING_PLUGIN_API DebugDrawQuad(Vec3 p0, Vec3 p1, Vec3 p2, Vec3 p3, uint32_t color);
+void RENDERING_PLUGIN_API DebugDrawClear(void);
+bool RENDERING_PLUGIN_API StartRecording(const char* path);
+void RENDERING_PLUGIN_API StopRecording(void);
+bool RENDERING_PLUGIN_API CaptureFrame(const char* path);
+bool RENDERING_PLUGIN_API DumpVulkanTrace(const char* path);
+bool RENDERING_PLUGIN_API SetTextureFeed(const char* path);
+bool RENDERING_PLUGIN_API LoadImageIntoTexture(void* handle, const char* path);
+bool RENDERING_PLUGIN_API SubmitVideoFrame(VideoFrame frame);
+void RENDERING_PLUGIN_API SetVideoPlaneTexturesFromUnity(void* luma, int32_t luma_width, int32_t luma_height, void* chroma, int32_t chroma_width, int32_t chroma_height);
+void RENDERING_PLUGIN_API StopVideo(void);
+bool RENDERING_PLUGIN_API GetYuvToRgbMatrix(int32_t color_space, int32_t range, float* matrix);
+const char* RENDERING_PLUGIN_API GetPluginVersion(void);
+uint32_t RENDERING_PLUGIN_API GetPluginAbiVersion(void);
+uint32_t RENDERING_PLUGIN_API QueryCapabilities(int32_t renderer);
+uint32_t RENDERING_PLUGIN_API GetValidationMessageCount(int32_t severity);
+void RENDERING_PLUGIN_API ResetValidationMessageCounts(void);
+void RENDERING_PLUGIN_API SetValidationLogSeverity(int32_t severity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
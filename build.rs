@@ -0,0 +1,168 @@
+#[path = "build/bindings.rs"]
+mod bindings;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const SHADER_EXTENSIONS: [&str; 5] = ["dxbc", "dxil", "spv", "glsl", "hlsl"];
+
+const HLSL_STAGES: [(&str, &str, &str); 2] = [
+    ("VS", "vs", "vs_4_0_level_9_3"),
+    ("PS", "ps", "ps_4_0_level_9_3"),
+];
+
+fn find_fxc() -> Option<PathBuf> {
+    if let Some(fxc) = std::env::var_os("FXC") {
+        return Some(PathBuf::from(fxc));
+    }
+    if std::env::var("CARGO_CFG_TARGET_OS").ok()? != "windows" || !cfg!(windows) {
+        return None;
+    }
+    std::process::Command::new("fxc")
+        .arg("/?")
+        .output()
+        .ok()
+        .map(|_| PathBuf::from("fxc"))
+}
+
+fn compile_hlsl(
+    fxc: &Path,
+    source: &Path,
+    entry_point: &str,
+    profile: &str,
+    output: &Path,
+) -> bool {
+    std::process::Command::new(fxc)
+        .arg("/nologo")
+        .arg(format!("/T{}", profile))
+        .arg(format!("/E{}", entry_point))
+        .arg("/Qstrip_debug")
+        .arg("/Qstrip_priv")
+        .arg("/Fo")
+        .arg(output)
+        .arg(source)
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+const BINDING_SOURCES: [&str; 3] = ["src/lib.rs", "src/math.rs", "src/render_api.rs"];
+
+fn generate_bindings(manifest_dir: &Path, out_dir: &Path) {
+    println!("cargo:rerun-if-changed=build/bindings.rs");
+    println!("cargo:rerun-if-env-changed=RENDERING_PLUGIN_UPDATE_BINDINGS");
+    let sources = BINDING_SOURCES
+        .iter()
+        .map(|source| {
+            println!("cargo:rerun-if-changed={}", source);
+            std::fs::read_to_string(manifest_dir.join(source)).unwrap()
+        })
+        .collect::<Vec<_>>();
+    let sources = sources.iter().map(String::as_str).collect::<Vec<_>>();
+    let bindings = bindings::generate(&sources).unwrap_or_else(|e| panic!("bindings: {}", e));
+
+    let mut dirs = vec![out_dir.to_path_buf()];
+    if std::env::var_os("RENDERING_PLUGIN_UPDATE_BINDINGS").is_some() {
+        dirs.push(manifest_dir.join("bindings"));
+    }
+    for dir in dirs {
+        std::fs::write(dir.join("RenderingPlugin.cs"), &bindings.csharp).unwrap();
+        std::fs::write(dir.join("RenderingPlugin.h"), &bindings.c_header).unwrap();
+    }
+}
+
+//  `backend_d3d11` and `backend_vulkan` gate the backend modules: a backend is built when its feature
+//  is on and the target has the API (`platform-vulkan` only counts on Linux and Android).
+fn emit_backend_cfgs() {
+    let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let feature = |name: &str| std::env::var_os(format!("CARGO_FEATURE_{}", name)).is_some();
+    let backends = [
+        ("backend_d3d11", feature("D3D11") && target_os == "windows"),
+        (
+            "backend_vulkan",
+            feature("VULKAN")
+                || (feature("PLATFORM_VULKAN") && (target_os == "linux" || target_os == "android")),
+        ),
+    ];
+    for (cfg, enabled) in backends.iter() {
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+        if *enabled {
+            println!("cargo:rustc-cfg={}", cfg);
+        }
+    }
+}
+
+fn main() {
+    emit_backend_cfgs();
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let shader_dir = manifest_dir.join("shaders");
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    generate_bindings(&manifest_dir, &out_dir);
+    println!("cargo:rerun-if-changed={}", shader_dir.display());
+    println!("cargo:rerun-if-env-changed=FXC");
+
+    let mut files = std::fs::read_dir(&shader_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    files.sort();
+
+    let mut entries = Vec::new();
+    for path in &files {
+        let stem = path.file_stem().and_then(|s| s.to_str());
+        let ext = path.extension().and_then(|s| s.to_str());
+        if let (Some(stem), Some(ext)) = (stem, ext) {
+            if SHADER_EXTENSIONS.contains(&ext) {
+                entries.push((stem.to_string(), ext.to_string(), path.clone()));
+            }
+        }
+    }
+
+    if let Some(fxc) = find_fxc() {
+        let sources = entries
+            .iter()
+            .filter(|(_, ext, _)| ext == "hlsl")
+            .map(|(stem, _, path)| (stem.clone(), path.clone()))
+            .collect::<Vec<_>>();
+        for (stem, path) in sources {
+            let source = std::fs::read_to_string(&path).unwrap_or_default();
+            for (entry_point, suffix, profile) in HLSL_STAGES.iter() {
+                let name = format!("{}_{}", stem, suffix);
+                if !source.contains(&format!(" {}(", entry_point))
+                    || entries
+                        .iter()
+                        .any(|(n, ext, _)| *n == name && ext == "dxbc")
+                {
+                    continue;
+                }
+                let output = out_dir.join(format!("{}.dxbc", name));
+                if compile_hlsl(&fxc, &path, entry_point, profile, &output) {
+                    entries.push((name, "dxbc".to_string(), output));
+                } else {
+                    println!("cargo:warning=fxc failed to compile {} ({})", name, profile);
+                }
+            }
+        }
+    }
+
+    let mut out = std::fs::File::create(out_dir.join("embedded_shaders.rs")).unwrap();
+    writeln!(
+        out,
+        "pub static EMBEDDED_SHADERS: &[(&str, &str, &[u8])] = &["
+    )
+    .unwrap();
+    for (name, ext, path) in &entries {
+        writeln!(
+            out,
+            "    ({:?}, {:?}, include_bytes!({:?})),",
+            name,
+            ext,
+            path.display().to_string()
+        )
+        .unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
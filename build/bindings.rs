@@ -0,0 +1,481 @@
+//  Generates the C# and C bindings from the `pub extern "system" fn` exports and the
+//  `#[repr(C)]` / `#[repr(i32)]` / `#[repr(u32)]` types they use (`#[repr(u32)]` enums are bit
+//  flags). This only understands the subset of Rust the plugin's FFI surface is written in, and
+//  fails loudly on anything else.
+
+const CSHARP_KEYWORDS: [&str; 16] = [
+    "base", "byte", "char", "checked", "decimal", "event", "fixed", "in", "internal", "lock",
+    "object", "operator", "out", "params", "ref", "string",
+];
+
+#[derive(Clone, PartialEq, Debug)]
+enum FfiType {
+    Void,
+    Bool,
+    Float,
+    Double,
+    Int,
+    UInt,
+    ULong,
+    Handle,
+    ConstFloatArray,
+    MutFloatArray,
+    ConstUIntArray,
+    CString,
+    RenderingEvent,
+    Named(String),
+    Array(Box<FfiType>, usize),
+}
+
+struct Function {
+    name: String,
+    params: Vec<(String, FfiType)>,
+    ret: FfiType,
+}
+
+enum TypeDef {
+    Struct(String, Vec<(String, FfiType)>),
+    Enum(String, Vec<(String, i64)>, bool),
+}
+
+impl TypeDef {
+    fn name(&self) -> &str {
+        match self {
+            TypeDef::Struct(name, _) | TypeDef::Enum(name, _, _) => name,
+        }
+    }
+}
+
+pub struct Bindings {
+    pub csharp: String,
+    pub c_header: String,
+}
+
+fn parse_type(s: &str) -> Result<FfiType, String> {
+    let s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        let (element, len) = inner
+            .rsplit_once(';')
+            .ok_or_else(|| format!("unsupported array type `{}`", s))?;
+        let len = len
+            .trim()
+            .parse()
+            .map_err(|_| format!("unsupported array length in `{}`", s))?;
+        return Ok(FfiType::Array(Box::new(parse_type(element)?), len));
+    }
+    let (pointer, path) = if let Some(path) = s.strip_prefix("*const ") {
+        (Some(true), path)
+    } else if let Some(path) = s.strip_prefix("*mut ") {
+        (Some(false), path)
+    } else {
+        (None, s.as_str())
+    };
+    let name = path.rsplit("::").next().unwrap_or(path);
+    Ok(match (pointer, name) {
+        (None, "bool") => FfiType::Bool,
+        (None, "f32") => FfiType::Float,
+        (None, "f64") => FfiType::Double,
+        (None, "i32") | (None, "c_int") => FfiType::Int,
+        (None, "u32") => FfiType::UInt,
+        (None, "u64") => FfiType::ULong,
+        (None, "Handle") | (Some(_), "c_void") => FfiType::Handle,
+        (None, "RenderingEvent") => FfiType::RenderingEvent,
+        (Some(true), "f32") => FfiType::ConstFloatArray,
+        (Some(false), "f32") => FfiType::MutFloatArray,
+        (Some(true), "u32") => FfiType::ConstUIntArray,
+        (Some(true), "c_char") => FfiType::CString,
+        (None, name) if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            FfiType::Named(name.to_string())
+        }
+        _ => return Err(format!("unsupported FFI type `{}`", s)),
+    })
+}
+
+fn parse_fields(body: &str) -> Result<Vec<(String, FfiType)>, String> {
+    body.split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            let field = field.strip_prefix("pub ").unwrap_or(field);
+            let (name, ty) = field
+                .split_once(':')
+                .ok_or_else(|| format!("unsupported field `{}`", field))?;
+            Ok((name.trim().to_string(), parse_type(ty)?))
+        })
+        .collect()
+}
+
+fn parse_functions(source: &str) -> Result<Vec<Function>, String> {
+    const EXPORT: &str = "pub extern \"system\" fn ";
+    let mut functions = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find(EXPORT) {
+        let signature = &rest[start + EXPORT.len()..];
+        let end = signature.find('{').ok_or("unterminated export signature")?;
+        let signature = &signature[..end];
+        rest = &rest[start + EXPORT.len() + end..];
+
+        let open = signature.find('(').ok_or("missing parameter list")?;
+        let close = signature.rfind(')').ok_or("missing parameter list")?;
+        let params = parse_fields(&signature[open + 1..close])?;
+        let ret = match signature[close + 1..].trim().strip_prefix("->") {
+            Some(ret) => parse_type(ret)?,
+            None => FfiType::Void,
+        };
+        functions.push(Function {
+            name: signature[..open].trim().to_string(),
+            params,
+            ret,
+        });
+    }
+    Ok(functions)
+}
+
+fn parse_enum_value(value: &str) -> Option<i64> {
+    match value.split_once("<<") {
+        Some((base, shift)) => {
+            Some(base.trim().parse::<i64>().ok()? << shift.trim().parse::<u32>().ok()?)
+        }
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_abi_version(source: &str) -> Option<u32> {
+    source
+        .lines()
+        .find_map(|line| {
+            line.trim()
+                .strip_prefix("pub const PLUGIN_ABI_VERSION: u32 =")
+        })
+        .and_then(|value| value.trim().trim_end_matches(';').parse().ok())
+}
+
+//  Names re-exported with `pub use path::Name;`.
+fn parse_reexports(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .filter_map(|line| line.strip_prefix("pub use ")?.strip_suffix(';'))
+        .filter_map(|path| path.rsplit("::").next())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_types(source: &str) -> Result<Vec<TypeDef>, String> {
+    let mut types = Vec::new();
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < lines.len() {
+        let repr = lines[i].trim();
+        i += 1;
+        if repr != "#[repr(C)]" && repr != "#[repr(i32)]" && repr != "#[repr(u32)]" {
+            continue;
+        }
+        let flags = repr == "#[repr(u32)]";
+        while i < lines.len() && lines[i].trim().starts_with("#[") {
+            i += 1;
+        }
+        let header = match lines.get(i) {
+            Some(header) => header.trim(),
+            None => break,
+        };
+        let (is_struct, name) = if let Some(name) = header.strip_prefix("pub struct ") {
+            (true, name)
+        } else if let Some(name) = header.strip_prefix("pub enum ") {
+            (false, name)
+        } else {
+            continue;
+        };
+        let name = name.trim_end_matches('{').trim().to_string();
+        let mut body = String::new();
+        i += 1;
+        while i < lines.len() && lines[i].trim() != "}" {
+            body.push_str(lines[i].trim());
+            body.push(' ');
+            i += 1;
+        }
+        if is_struct {
+            types.push(TypeDef::Struct(name, parse_fields(&body)?));
+        } else {
+            let variants = body
+                .split(',')
+                .map(str::trim)
+                .filter(|variant| !variant.is_empty())
+                .map(|variant| {
+                    let (name, value) = variant
+                        .split_once('=')
+                        .ok_or_else(|| format!("enum variant `{}` needs a value", variant))?;
+                    let value = parse_enum_value(value.trim())
+                        .ok_or_else(|| format!("unsupported enum value `{}`", variant))?;
+                    Ok((name.trim().to_string(), value))
+                })
+                .collect::<Result<_, String>>()?;
+            types.push(TypeDef::Enum(name, variants, flags));
+        }
+    }
+    Ok(types)
+}
+
+fn collect_named(ty: &FfiType, names: &mut Vec<String>) {
+    match ty {
+        FfiType::Named(name) => names.push(name.clone()),
+        FfiType::Array(element, _) => collect_named(element, names),
+        _ => {}
+    }
+}
+
+fn csharp_type(ty: &FfiType) -> String {
+    match ty {
+        FfiType::Void => "void".to_string(),
+        FfiType::Bool => "bool".to_string(),
+        FfiType::Float => "float".to_string(),
+        FfiType::Double => "double".to_string(),
+        FfiType::Int => "int".to_string(),
+        FfiType::UInt => "uint".to_string(),
+        FfiType::ULong => "ulong".to_string(),
+        FfiType::Handle | FfiType::RenderingEvent => "IntPtr".to_string(),
+        FfiType::ConstFloatArray | FfiType::MutFloatArray => "float[]".to_string(),
+        FfiType::ConstUIntArray => "uint[]".to_string(),
+        FfiType::CString => "string".to_string(),
+        FfiType::Named(name) => name.clone(),
+        FfiType::Array(element, _) => format!("{}[]", csharp_type(element)),
+    }
+}
+
+fn csharp_name(name: &str) -> String {
+    if CSHARP_KEYWORDS.contains(&name) {
+        format!("@{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn c_type(ty: &FfiType) -> String {
+    match ty {
+        FfiType::Void => "void".to_string(),
+        FfiType::Bool => "bool".to_string(),
+        FfiType::Float => "float".to_string(),
+        FfiType::Double => "double".to_string(),
+        FfiType::Int => "int32_t".to_string(),
+        FfiType::UInt => "uint32_t".to_string(),
+        FfiType::ULong => "uint64_t".to_string(),
+        FfiType::Handle => "void*".to_string(),
+        FfiType::ConstFloatArray => "const float*".to_string(),
+        FfiType::MutFloatArray => "float*".to_string(),
+        FfiType::ConstUIntArray => "const uint32_t*".to_string(),
+        FfiType::CString => "const char*".to_string(),
+        FfiType::RenderingEvent => "RenderingPluginRenderingEvent".to_string(),
+        FfiType::Named(name) => name.clone(),
+        FfiType::Array(element, _) => c_type(element),
+    }
+}
+
+fn generate_csharp(functions: &[Function], types: &[&TypeDef], abi_version: Option<u32>) -> String {
+    let mut out = String::new();
+    out.push_str("// <auto-generated>\n");
+    out.push_str(
+        "// Generated by build.rs from the RenderingPlugin exports. Do not edit by hand.\n",
+    );
+    out.push_str("// </auto-generated>\n");
+    out.push_str("using System;\nusing System.Runtime.InteropServices;\n\n");
+    out.push_str("namespace RenderingPlugin\n{\n");
+    for ty in types {
+        match ty {
+            TypeDef::Struct(name, fields) => {
+                out.push_str("    [StructLayout(LayoutKind.Sequential)]\n");
+                out.push_str(&format!("    public struct {}\n    {{\n", name));
+                for (field, ty) in fields {
+                    if let FfiType::Array(_, len) = ty {
+                        out.push_str(&format!(
+                            "        [MarshalAs(UnmanagedType.ByValArray, SizeConst = {})]\n",
+                            len
+                        ));
+                    }
+                    out.push_str(&format!(
+                        "        public {} {};\n",
+                        csharp_type(ty),
+                        csharp_name(field)
+                    ));
+                }
+                out.push_str("    }\n\n");
+            }
+            TypeDef::Enum(name, variants, flags) => {
+                if *flags {
+                    out.push_str("    [Flags]\n");
+                }
+                out.push_str(&format!(
+                    "    public enum {} : {}\n    {{\n",
+                    name,
+                    if *flags { "uint" } else { "int" }
+                ));
+                for (variant, value) in variants {
+                    let value = if *flags {
+                        format!("0x{:08X}", value)
+                    } else {
+                        value.to_string()
+                    };
+                    out.push_str(&format!("        {} = {},\n", variant, value));
+                }
+                out.push_str("    }\n\n");
+            }
+        }
+    }
+    out.push_str("    public static class NativeMethods\n    {\n");
+    out.push_str("#if (UNITY_IOS || UNITY_WEBGL) && !UNITY_EDITOR\n");
+    out.push_str("        const string DllName = \"__Internal\";\n");
+    out.push_str("#else\n");
+    out.push_str("        const string DllName = \"RenderingPlugin\";\n");
+    out.push_str("#endif\n");
+    if let Some(abi_version) = abi_version {
+        out.push_str(&format!(
+            "\n        public const uint AbiVersion = {};\n",
+            abi_version
+        ));
+    }
+    for function in functions {
+        out.push_str("\n        [DllImport(DllName)]\n");
+        if function.ret == FfiType::Bool {
+            out.push_str("        [return: MarshalAs(UnmanagedType.I1)]\n");
+        }
+        let params = function
+            .params
+            .iter()
+            .map(|(name, ty)| {
+                let marshal = match ty {
+                    FfiType::Bool => "[MarshalAs(UnmanagedType.I1)] ",
+                    FfiType::CString => "[MarshalAs(UnmanagedType.LPStr)] ",
+                    FfiType::MutFloatArray => "[Out] ",
+                    _ => "",
+                };
+                format!("{}{} {}", marshal, csharp_type(ty), csharp_name(name))
+            })
+            .collect::<Vec<_>>();
+        //  The marshaller would free a returned string, so hand the pointer to the caller.
+        let ret = match function.ret {
+            FfiType::CString => "IntPtr".to_string(),
+            _ => csharp_type(&function.ret),
+        };
+        out.push_str(&format!(
+            "        public static extern {} {}({});\n",
+            ret,
+            function.name,
+            params.join(", ")
+        ));
+    }
+    out.push_str("    }\n}\n");
+    out
+}
+
+fn generate_c_header(
+    functions: &[Function],
+    types: &[&TypeDef],
+    abi_version: Option<u32>,
+) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "/* Generated by build.rs from the RenderingPlugin exports. Do not edit by hand. */\n",
+    );
+    out.push_str("#ifndef RENDERING_PLUGIN_H\n#define RENDERING_PLUGIN_H\n\n");
+    out.push_str("#include <stdbool.h>\n#include <stdint.h>\n\n");
+    if let Some(abi_version) = abi_version {
+        out.push_str(&format!(
+            "#define RENDERING_PLUGIN_ABI_VERSION {}\n\n",
+            abi_version
+        ));
+    }
+    out.push_str("#if defined(_WIN32)\n#define RENDERING_PLUGIN_API __stdcall\n");
+    out.push_str("#else\n#define RENDERING_PLUGIN_API\n#endif\n\n");
+    out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    out.push_str(
+        "typedef void (RENDERING_PLUGIN_API *RenderingPluginRenderingEvent)(int32_t eventId);\n\n",
+    );
+    for ty in types {
+        match ty {
+            TypeDef::Struct(name, fields) => {
+                out.push_str(&format!("typedef struct {} {{\n", name));
+                for (field, ty) in fields {
+                    match ty {
+                        FfiType::Array(_, len) => {
+                            out.push_str(&format!("    {} {}[{}];\n", c_type(ty), field, len))
+                        }
+                        _ => out.push_str(&format!("    {} {};\n", c_type(ty), field)),
+                    }
+                }
+                out.push_str(&format!("}} {};\n\n", name));
+            }
+            TypeDef::Enum(name, variants, flags) => {
+                out.push_str(&format!("typedef enum {} {{\n", name));
+                for (variant, value) in variants {
+                    let value = if *flags {
+                        format!("0x{:08X}u", value)
+                    } else {
+                        value.to_string()
+                    };
+                    out.push_str(&format!("    {}_{} = {},\n", name, variant, value));
+                }
+                out.push_str(&format!("}} {};\n\n", name));
+            }
+        }
+    }
+    for function in functions {
+        let params = if function.params.is_empty() {
+            "void".to_string()
+        } else {
+            function
+                .params
+                .iter()
+                .map(|(name, ty)| format!("{} {}", c_type(ty), name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        out.push_str(&format!(
+            "{} RENDERING_PLUGIN_API {}({});\n",
+            c_type(&function.ret),
+            function.name,
+            params
+        ));
+    }
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+    out
+}
+
+//  `sources[0]` is the crate root: its exports are bound, and every `#[repr]` type it declares or
+//  re-exports is emitted (e.g. render event IDs). Types from the other sources are emitted only when
+//  an export uses them or the root re-exports them.
+pub fn generate(sources: &[&str]) -> Result<Bindings, String> {
+    let functions = parse_functions(sources[0])?;
+    let root_types = parse_types(sources[0])?;
+    let mut all_types = Vec::new();
+    for source in &sources[1..] {
+        all_types.extend(parse_types(source)?);
+    }
+
+    let mut used = root_types
+        .iter()
+        .map(|ty| ty.name().to_string())
+        .chain(parse_reexports(sources[0]))
+        .collect::<Vec<_>>();
+    for function in &functions {
+        for (_, ty) in &function.params {
+            collect_named(ty, &mut used);
+        }
+        collect_named(&function.ret, &mut used);
+    }
+    let mut types = root_types.iter().collect::<Vec<_>>();
+    for ty in &all_types {
+        if used.iter().any(|name| name == ty.name()) && !types.iter().any(|t| t.name() == ty.name())
+        {
+            types.push(ty);
+        }
+    }
+    for name in &used {
+        if !types.iter().any(|ty| ty.name() == name) {
+            return Err(format!("no #[repr] definition found for `{}`", name));
+        }
+    }
+
+    let abi_version = parse_abi_version(sources[0]);
+    Ok(Bindings {
+        csharp: generate_csharp(&functions, &types, abi_version),
+        c_header: generate_c_header(&functions, &types, abi_version),
+    })
+}
@@ -0,0 +1,19 @@
+// Compiled into simple_vs.dxbc / simple_ps.dxbc with:
+// fxc /Tvs_4_0_level_9_3 /EVS simple.hlsl /Fo simple_vs.dxbc /Qstrip_reflect /Qstrip_debug /Qstrip_priv
+// fxc /Tps_4_0_level_9_3 /EPS simple.hlsl /Fo simple_ps.dxbc /Qstrip_reflect /Qstrip_debug /Qstrip_priv
+
+cbuffer MyCB : register(b0)
+{
+    float4x4 worldMatrix;
+}
+
+void VS(float3 pos : POSITION, float4 color : COLOR, out float4 ocolor : COLOR, out float4 opos : SV_Position)
+{
+    opos = mul(worldMatrix, float4(pos, 1));
+    ocolor = color;
+}
+
+float4 PS(float4 color : COLOR) : SV_TARGET
+{
+    return color;
+}
@@ -0,0 +1,15 @@
+cbuffer MyCB : register(b0)
+{
+    float4x4 worldMatrix;
+}
+Texture2D myTexture : register(t0);
+SamplerState mySampler : register(s0);
+void VS(float3 pos : POSITION, float2 uv : TEXCOORD0, out float2 ouv : TEXCOORD0, out float4 opos : SV_Position)
+{
+    opos = mul(worldMatrix, float4(pos, 1));
+    ouv = uv;
+}
+float4 PS(float2 uv : TEXCOORD0) : SV_Target
+{
+    return myTexture.Sample(mySampler, uv);
+}
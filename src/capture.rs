@@ -0,0 +1,152 @@
+use crate::image::{Image, ImageFormat};
+use crate::logger;
+use crate::render_api::{Handle, RenderAPI};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::Mutex;
+
+pub const CAPTURE_PATH_ENV: &str = "RENDERING_PLUGIN_CAPTURE";
+
+//  Replaced by the frame index. A path containing it keeps capturing every frame.
+const FRAME_PLACEHOLDER: &str = "{frame}";
+
+//  Frames read back but not written yet. The render thread only waits when the writer falls this
+//  far behind, so a `{frame}` capture slows rendering down instead of dropping frames.
+const MAX_QUEUED_FRAMES: usize = 4;
+
+static PENDING: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+struct Writer {
+    sender: SyncSender<(PathBuf, Image, u64)>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+static WRITER: Mutex<Option<Writer>> = Mutex::new(None);
+
+//  Captures are read back at the end of the next frame on the render thread and written by the
+//  writer thread, so the request only checks the path up front.
+pub fn request(path: &Path) -> Result<(), String> {
+    if ImageFormat::from_path(path).is_none() {
+        return Err(format!(
+            "unsupported capture file extension (expected .png, .ppm or .exr): {}",
+            path.display()
+        ));
+    }
+    *PENDING.lock().unwrap() = Some(path.to_path_buf());
+    Ok(())
+}
+
+pub fn cancel() {
+    *PENDING.lock().unwrap() = None;
+}
+
+fn frame_path(path: &Path, frame_index: u64) -> Option<PathBuf> {
+    let path = path.to_str()?;
+    if path.contains(FRAME_PLACEHOLDER) {
+        Some(PathBuf::from(
+            path.replace(FRAME_PLACEHOLDER, &format!("{:06}", frame_index)),
+        ))
+    } else {
+        None
+    }
+}
+
+//  Writes the render target when the backend can read it back, and otherwise the texture set with
+//  `SetTextureFromUnity`.
+pub fn end_frame(api: &dyn RenderAPI, texture: (Handle, i32, i32), frame_index: u64) {
+    let path = {
+        let mut pending = PENDING.lock().unwrap();
+        match pending.as_ref() {
+            Some(path) => match frame_path(path, frame_index) {
+                Some(frame_path) => frame_path,
+                None => pending.take().unwrap(),
+            },
+            None => return,
+        }
+    };
+
+    let (texture_handle, texture_width, texture_height) = texture;
+    let image = api
+        .read_back_frame()
+        .or_else(|| api.read_back_texture(texture_handle, texture_width, texture_height));
+    match image {
+        Some(image) => queue(path, image, frame_index),
+        None => logger::error(&format!(
+            "failed to capture {}: nothing to read back",
+            path.display()
+        )),
+    }
+}
+
+//  Encoding and writing happen on a writer thread that is started with the first capture.
+fn queue(path: PathBuf, image: Image, frame_index: u64) {
+    let mut writer = WRITER.lock().unwrap();
+    let writer = writer.get_or_insert_with(|| {
+        let (sender, receiver) = sync_channel::<(PathBuf, Image, u64)>(MAX_QUEUED_FRAMES);
+        let thread = std::thread::spawn(move || {
+            for (path, image, frame_index) in receiver {
+                match image.save(&path) {
+                    Ok(_) => logger::info(&format!(
+                        "captured frame {} to {}",
+                        frame_index,
+                        path.display()
+                    )),
+                    Err(e) => {
+                        logger::error(&format!("failed to capture {}: {}", path.display(), e))
+                    }
+                }
+            }
+        });
+        Writer { sender, thread }
+    });
+    if writer.sender.send((path, image, frame_index)).is_err() {
+        logger::error("capture writer thread stopped");
+    }
+}
+
+//  Waits until every queued capture is written and stops the writer thread.
+pub fn finish() {
+    let writer = WRITER.lock().unwrap().take();
+    if let Some(Writer { sender, thread }) = writer {
+        drop(sender);
+        if thread.join().is_err() {
+            logger::error("capture writer thread panicked");
+        }
+    }
+}
+
+#[test]
+fn test_frame_path() {
+    assert_eq!(
+        frame_path(Path::new("out/frame_{frame}.png"), 42),
+        Some(PathBuf::from("out/frame_000042.png"))
+    );
+    assert_eq!(frame_path(Path::new("out/frame.png"), 42), None);
+}
+
+#[test]
+fn test_end_frame() {
+    let _replaying = crate::replay::REPLAYING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let api = crate::render_api_software::RenderAPISoftware::new(8, 4);
+    api.framebuffer().borrow_mut().fill([10, 20, 30, 255]);
+    let path = std::env::temp_dir().join(format!(
+        "rendering_plugin_capture_{}_{{frame}}.ppm",
+        std::process::id()
+    ));
+    request(&path).unwrap();
+    for frame_index in 0..3 {
+        end_frame(api.as_ref(), (std::ptr::null_mut(), 0, 0), frame_index);
+    }
+    cancel();
+    finish();
+    let mut expected = Image::new(8, 4);
+    expected.fill([10, 20, 30, 255]);
+    for frame_index in 0..3 {
+        let frame_path = frame_path(&path, frame_index).unwrap();
+        let data = std::fs::read(&frame_path);
+        let _ = std::fs::remove_file(&frame_path);
+        assert_eq!(data.unwrap(), expected.encode(ImageFormat::Ppm));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::math::Vec3;
+use crate::render_api;
+use std::sync::Mutex;
+
+const MAX_VERTICES: usize = 1 << 20;
+const SPHERE_SEGMENTS: usize = 24;
+
+pub const COLOR_RED: u32 = 0xFF0000FF;
+pub const COLOR_GREEN: u32 = 0xFF00FF00;
+pub const COLOR_BLUE: u32 = 0xFFFF0000;
+
+//  Quads share their corners between the two triangles, so they are drawn indexed.
+struct DebugDrawBatch {
+    lines: Vec<render_api::MyVertex>,
+    triangles: Vec<render_api::MyVertex>,
+    triangle_indices: Vec<u32>,
+}
+
+static BATCH: Mutex<DebugDrawBatch> = Mutex::new(DebugDrawBatch {
+    lines: Vec::new(),
+    triangles: Vec::new(),
+    triangle_indices: Vec::new(),
+});
+
+fn vertex(p: Vec3, color: u32) -> render_api::MyVertex {
+    render_api::MyVertex {
+        x: p.x,
+        y: p.y,
+        z: p.z,
+        color,
+    }
+}
+
+fn push_lines(points: &[Vec3], color: u32) {
+    let mut batch = BATCH.lock().unwrap_or_else(|e| e.into_inner());
+    if batch.lines.len() + points.len() > MAX_VERTICES {
+        return;
+    }
+    batch.lines.extend(points.iter().map(|&p| vertex(p, color)));
+}
+
+fn push_triangles(points: &[Vec3], indices: &[u32], color: u32) {
+    let mut batch = BATCH.lock().unwrap_or_else(|e| e.into_inner());
+    if batch.triangles.len() + points.len() > MAX_VERTICES {
+        return;
+    }
+    let base = batch.triangles.len() as u32;
+    batch
+        .triangles
+        .extend(points.iter().map(|&p| vertex(p, color)));
+    batch
+        .triangle_indices
+        .extend(indices.iter().map(|&i| base + i));
+}
+
+fn perpendicular(dir: Vec3) -> Vec3 {
+    let up = if dir.y.abs() < 0.99 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    dir.cross(up).normalize()
+}
+
+pub fn line(from: Vec3, to: Vec3, color: u32) {
+    push_lines(&[from, to], color);
+}
+
+fn box_lines(center: Vec3, half_extents: Vec3) -> Vec<Vec3> {
+    let corner = |i: usize| {
+        let sign = |bit: usize| if i & bit != 0 { 1.0 } else { -1.0 };
+        Vec3::new(
+            center.x + half_extents.x * sign(1),
+            center.y + half_extents.y * sign(2),
+            center.z + half_extents.z * sign(4),
+        )
+    };
+    let mut points = Vec::with_capacity(24);
+    for i in 0..8 {
+        for &bit in &[1, 2, 4] {
+            if i & bit == 0 {
+                points.push(corner(i));
+                points.push(corner(i | bit));
+            }
+        }
+    }
+    points
+}
+
+pub fn wire_box(center: Vec3, half_extents: Vec3, color: u32) {
+    push_lines(&box_lines(center, half_extents), color);
+}
+
+fn sphere_lines(center: Vec3, radius: f32) -> Vec<Vec3> {
+    let mut points = Vec::with_capacity(SPHERE_SEGMENTS * 6);
+    for axis in 0..3 {
+        let point = |i: usize| {
+            let phi = i as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+            let (s, c) = (phi.sin() * radius, phi.cos() * radius);
+            let offset = match axis {
+                0 => Vec3::new(0.0, c, s),
+                1 => Vec3::new(c, 0.0, s),
+                _ => Vec3::new(c, s, 0.0),
+            };
+            center.add(offset)
+        };
+        for i in 0..SPHERE_SEGMENTS {
+            points.push(point(i));
+            points.push(point(i + 1));
+        }
+    }
+    points
+}
+
+pub fn sphere(center: Vec3, radius: f32, color: u32) {
+    push_lines(&sphere_lines(center, radius), color);
+}
+
+fn arrow_lines(from: Vec3, to: Vec3) -> Vec<Vec3> {
+    let dir = to.sub(from);
+    let len = dir.length();
+    if len <= 0.0 {
+        return Vec::new();
+    }
+    let dir = dir.scale(1.0 / len);
+    let side = perpendicular(dir);
+    let up = dir.cross(side);
+    let head = len * 0.2;
+    let base = to.sub(dir.scale(head));
+    let mut points = vec![from, to];
+    for &offset in &[side, side.scale(-1.0), up, up.scale(-1.0)] {
+        points.push(to);
+        points.push(base.add(offset.scale(head * 0.5)));
+    }
+    points
+}
+
+pub fn arrow(from: Vec3, to: Vec3, color: u32) {
+    push_lines(&arrow_lines(from, to), color);
+}
+
+//  One (from, to, color) arrow per column of the matrix's rotation part.
+fn axis_arrows(matrix: &[f32; 16], size: f32) -> [(Vec3, Vec3, u32); 3] {
+    let origin = Vec3::new(matrix[12], matrix[13], matrix[14]);
+    let column = |i: usize| Vec3::new(matrix[i * 4], matrix[i * 4 + 1], matrix[i * 4 + 2]);
+    let arrow =
+        |i: usize, color: u32| (origin, origin.add(column(i).normalize().scale(size)), color);
+    [
+        arrow(0, COLOR_RED),
+        arrow(1, COLOR_GREEN),
+        arrow(2, COLOR_BLUE),
+    ]
+}
+
+pub fn axes(matrix: &[f32; 16], size: f32) {
+    for &(from, to, color) in &axis_arrows(matrix, size) {
+        arrow(from, to, color);
+    }
+}
+
+pub fn quad(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, color: u32) {
+    push_triangles(&[p0, p1, p2, p3], &[0, 1, 2, 0, 2, 3], color);
+}
+
+pub fn clear() {
+    let mut batch = BATCH.lock().unwrap_or_else(|e| e.into_inner());
+    batch.lines.clear();
+    batch.triangles.clear();
+    batch.triangle_indices.clear();
+}
+
+pub fn flush(api: &dyn render_api::RenderAPI, world_matrix: [f32; 16]) {
+    let (lines, triangles, triangle_indices) = {
+        let mut batch = BATCH.lock().unwrap_or_else(|e| e.into_inner());
+        (
+            std::mem::take(&mut batch.lines),
+            std::mem::take(&mut batch.triangles),
+            std::mem::take(&mut batch.triangle_indices),
+        )
+    };
+    if !triangle_indices.is_empty() {
+        api.draw_simple_indexed_triangles(
+            world_matrix,
+            (triangle_indices.len() / 3) as i32,
+            &triangles,
+            &triangle_indices,
+        );
+    }
+    if !lines.is_empty() {
+        api.draw_simple_lines(world_matrix, (lines.len() / 2) as i32, &lines);
+    }
+}
+
+#[cfg(test)]
+fn assert_near(a: Vec3, b: Vec3) {
+    assert!(a.sub(b).length() < 1e-4, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn test_box_lines() {
+    let center = Vec3::new(1.0, 2.0, 3.0);
+    let half_extents = Vec3::new(0.5, 1.0, 2.0);
+    let points = box_lines(center, half_extents);
+    assert_eq!(points.len(), 24);
+    for edge in points.chunks_exact(2) {
+        let d = edge[1].sub(edge[0]);
+        let lengths = [d.x, d.y, d.z];
+        //  Every edge runs along one axis, from the negative to the positive side.
+        assert_eq!(lengths.iter().filter(|&&l| l != 0.0).count(), 1);
+        assert!(
+            lengths == [1.0, 0.0, 0.0] || lengths == [0.0, 2.0, 0.0] || lengths == [0.0, 0.0, 4.0]
+        );
+        for p in edge {
+            let offset = p.sub(center);
+            assert_eq!(offset.x.abs(), 0.5);
+            assert_eq!(offset.y.abs(), 1.0);
+            assert_eq!(offset.z.abs(), 2.0);
+        }
+    }
+}
+
+#[test]
+fn test_sphere_lines() {
+    let center = Vec3::new(-1.0, 0.0, 4.0);
+    let points = sphere_lines(center, 2.0);
+    assert_eq!(points.len(), SPHERE_SEGMENTS * 6);
+    for p in &points {
+        assert!((p.sub(center).length() - 2.0).abs() < 1e-4);
+    }
+    //  Each circle is closed.
+    for circle in points.chunks_exact(SPHERE_SEGMENTS * 2) {
+        assert_near(circle[circle.len() - 1], circle[0]);
+    }
+}
+
+#[test]
+fn test_arrow_lines() {
+    let from = Vec3::new(0.0, 0.0, 0.0);
+    let to = Vec3::new(0.0, 0.0, 10.0);
+    let points = arrow_lines(from, to);
+    assert_eq!(points.len(), 10);
+    assert_near(points[0], from);
+    assert_near(points[1], to);
+    for head in points[2..].chunks_exact(2) {
+        assert_near(head[0], to);
+        //  The head is 20% of the length, and half as wide.
+        assert!((head[1].z - 8.0).abs() < 1e-4);
+        assert!((Vec3::new(head[1].x, head[1].y, 0.0).length() - 1.0).abs() < 1e-4);
+    }
+    assert!(arrow_lines(to, to).is_empty());
+}
+
+#[test]
+fn test_axis_arrows() {
+    let mut matrix = [0.0; 16];
+    matrix[0] = 2.0;
+    matrix[5] = 2.0;
+    matrix[10] = 2.0;
+    matrix[12] = 1.0;
+    matrix[13] = 2.0;
+    matrix[14] = 3.0;
+    matrix[15] = 1.0;
+    let arrows = axis_arrows(&matrix, 0.5);
+    let origin = Vec3::new(1.0, 2.0, 3.0);
+    let expected = [
+        (Vec3::new(1.5, 2.0, 3.0), COLOR_RED),
+        (Vec3::new(1.0, 2.5, 3.0), COLOR_GREEN),
+        (Vec3::new(1.0, 2.0, 3.5), COLOR_BLUE),
+    ];
+    for (&(from, to, color), &(expected_to, expected_color)) in arrows.iter().zip(&expected) {
+        assert_near(from, origin);
+        assert_near(to, expected_to);
+        assert_eq!(color, expected_color);
+    }
+}
+
+#[test]
+fn test_flush_quads() {
+    let _replaying = crate::replay::REPLAYING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let api = crate::render_api_software::RenderAPISoftware::new(8, 8);
+    let corner = |x: f32, y: f32| Vec3::new(x, y, 0.5);
+    clear();
+    quad(
+        corner(-1.0, -1.0),
+        corner(0.0, -1.0),
+        corner(0.0, 1.0),
+        corner(-1.0, 1.0),
+        COLOR_RED,
+    );
+    quad(
+        corner(0.0, -1.0),
+        corner(1.0, -1.0),
+        corner(1.0, 1.0),
+        corner(0.0, 1.0),
+        COLOR_BLUE,
+    );
+    flush(api.as_ref(), crate::math::Mat4::IDENTITY.to_cols_array());
+    let framebuffer = api.framebuffer();
+    let framebuffer = framebuffer.borrow();
+    for y in 0..8 {
+        assert_eq!(framebuffer.pixel(0, y), COLOR_RED.to_le_bytes());
+        assert_eq!(framebuffer.pixel(7, y), COLOR_BLUE.to_le_bytes());
+    }
+}
@@ -0,0 +1,395 @@
+//  In-process stand-in for the Unity side of the native plugin interface, so the plugin can be
+//  loaded, sent device events and render events, and unloaded in tests on any platform. It serves
+//  IUnityGraphics (with a selectable renderer), IUnityLog (messages are collected), an
+//  IUnityProfiler that reports itself as unavailable and IUnityGraphicsVulkan, which only remembers
+//  the initialization callback and can be withdrawn. Other interfaces are not found, like in a
+//  player that lacks them.
+
+#![allow(non_snake_case)]
+
+use std::os::raw::{c_char, c_int, c_ulonglong, c_void};
+use std::sync::{Mutex, MutexGuard};
+use unity_native_plugin::graphics::{GfxDeviceEventType, GfxRenderer};
+
+type DeviceEventCallback = unsafe extern "system" fn(c_int);
+//  UnityVulkanInitCallback, with the Vulkan function pointers left opaque.
+type VulkanInitCallback = unsafe extern "system" fn(*const c_void, *mut c_void) -> *const c_void;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UnityInterfaceGUID {
+    high: c_ulonglong,
+    low: c_ulonglong,
+}
+
+const GRAPHICS_GUID: UnityInterfaceGUID = UnityInterfaceGUID {
+    high: 0x7CBA0A9CA4DDB544,
+    low: 0x8C5AD4926EB17B11,
+};
+const LOG_GUID: UnityInterfaceGUID = UnityInterfaceGUID {
+    high: 0x9E7507FA5B444D5D,
+    low: 0x92FB979515EA83FC,
+};
+const PROFILER_GUID: UnityInterfaceGUID = UnityInterfaceGUID {
+    high: 0x2CE79ED8316A4833,
+    low: 0x87076B2013E1571F,
+};
+const GRAPHICS_VULKAN_GUID: UnityInterfaceGUID = UnityInterfaceGUID {
+    high: 0x95355348D4EF4E11,
+    low: 0x9789313DFCFFCC87,
+};
+
+#[repr(C)]
+struct IUnityInterfaces {
+    GetInterface: unsafe extern "system" fn(UnityInterfaceGUID) -> *mut c_void,
+    RegisterInterface: unsafe extern "system" fn(UnityInterfaceGUID, *mut c_void),
+    GetInterfaceSplit: unsafe extern "system" fn(c_ulonglong, c_ulonglong) -> *mut c_void,
+    RegisterInterfaceSplit: unsafe extern "system" fn(c_ulonglong, c_ulonglong, *mut c_void),
+}
+
+#[repr(C)]
+struct IUnityGraphics {
+    GetRenderer: unsafe extern "system" fn() -> c_int,
+    RegisterDeviceEventCallback: unsafe extern "system" fn(Option<DeviceEventCallback>),
+    UnregisterDeviceEventCallback: unsafe extern "system" fn(Option<DeviceEventCallback>),
+    ReserveEventIDRange: unsafe extern "system" fn(c_int) -> c_int,
+}
+
+#[repr(C)]
+struct IUnityLog {
+    Log: unsafe extern "system" fn(c_int, *const c_char, *const c_char, c_int),
+}
+
+#[repr(C)]
+struct IUnityProfiler {
+    EmitEvent: unsafe extern "system" fn(*const c_void, u16, u16, *const c_void),
+    IsEnabled: unsafe extern "system" fn() -> c_int,
+    IsAvailable: unsafe extern "system" fn() -> c_int,
+    CreateMarker:
+        unsafe extern "system" fn(*mut *const c_void, *const c_char, u16, u16, c_int) -> c_int,
+    SetMarkerMetadataName:
+        unsafe extern "system" fn(*const c_void, c_int, *const c_char, u8, u8) -> c_int,
+    RegisterThread: unsafe extern "system" fn(*mut u64, *const c_char, *const c_char) -> c_int,
+    UnregisterThread: unsafe extern "system" fn(u64) -> c_int,
+}
+
+//  Only InterceptInitialization, the first entry, is filled in; the plugin uses nothing else.
+#[repr(C)]
+struct IUnityGraphicsVulkan {
+    InterceptInitialization:
+        unsafe extern "system" fn(Option<VulkanInitCallback>, *mut c_void) -> bool,
+}
+
+//  UnityLogType value of warnings.
+pub const LOG_WARNING: i32 = 2;
+
+//  The first event ID handed out by ReserveEventIDRange, above the plugin's own IDs.
+const FIRST_RESERVED_EVENT_ID: c_int = 1000;
+
+struct HostState {
+    renderer: c_int,
+    callbacks: Vec<DeviceEventCallback>,
+    messages: Vec<(i32, String)>,
+    next_event_id: c_int,
+    vulkan_available: bool,
+    vulkan_init_callback: Option<VulkanInitCallback>,
+}
+
+static STATE: Mutex<HostState> = Mutex::new(HostState {
+    renderer: GfxRenderer::Null as c_int,
+    callbacks: Vec::new(),
+    messages: Vec::new(),
+    next_event_id: FIRST_RESERVED_EVENT_ID,
+    vulkan_available: true,
+    vulkan_init_callback: None,
+});
+
+fn state() -> MutexGuard<'static, HostState> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+unsafe extern "system" fn get_interface(guid: UnityInterfaceGUID) -> *mut c_void {
+    get_interface_split(guid.high, guid.low)
+}
+
+unsafe extern "system" fn register_interface(_guid: UnityInterfaceGUID, _ptr: *mut c_void) {}
+
+unsafe extern "system" fn get_interface_split(high: c_ulonglong, low: c_ulonglong) -> *mut c_void {
+    let matches = |guid: UnityInterfaceGUID| guid.high == high && guid.low == low;
+    if matches(GRAPHICS_GUID) {
+        &GRAPHICS as *const IUnityGraphics as _
+    } else if matches(LOG_GUID) {
+        &LOG as *const IUnityLog as _
+    } else if matches(PROFILER_GUID) {
+        &PROFILER as *const IUnityProfiler as _
+    } else if matches(GRAPHICS_VULKAN_GUID) && state().vulkan_available {
+        &GRAPHICS_VULKAN as *const IUnityGraphicsVulkan as _
+    } else {
+        std::ptr::null_mut()
+    }
+}
+
+unsafe extern "system" fn register_interface_split(
+    _high: c_ulonglong,
+    _low: c_ulonglong,
+    _ptr: *mut c_void,
+) {
+}
+
+unsafe extern "system" fn get_renderer() -> c_int {
+    state().renderer
+}
+
+unsafe extern "system" fn register_device_event_callback(callback: Option<DeviceEventCallback>) {
+    if let Some(callback) = callback {
+        state().callbacks.push(callback);
+    }
+}
+
+unsafe extern "system" fn unregister_device_event_callback(callback: Option<DeviceEventCallback>) {
+    if let Some(callback) = callback {
+        state()
+            .callbacks
+            .retain(|&registered| registered as usize != callback as usize);
+    }
+}
+
+unsafe extern "system" fn reserve_event_id_range(count: c_int) -> c_int {
+    let mut state = state();
+    let first = state.next_event_id;
+    state.next_event_id += count.max(0);
+    first
+}
+
+unsafe extern "system" fn log(
+    log_type: c_int,
+    message: *const c_char,
+    _file_name: *const c_char,
+    _file_line: c_int,
+) {
+    if message.is_null() {
+        return;
+    }
+    let message = std::ffi::CStr::from_ptr(message)
+        .to_string_lossy()
+        .into_owned();
+    state().messages.push((log_type, message));
+}
+
+unsafe extern "system" fn intercept_initialization(
+    callback: Option<VulkanInitCallback>,
+    _userdata: *mut c_void,
+) -> bool {
+    state().vulkan_init_callback = callback;
+    true
+}
+
+unsafe extern "system" fn emit_event(_: *const c_void, _: u16, _: u16, _: *const c_void) {}
+
+unsafe extern "system" fn profiler_disabled() -> c_int {
+    0
+}
+
+unsafe extern "system" fn create_marker(
+    desc: *mut *const c_void,
+    _: *const c_char,
+    _: u16,
+    _: u16,
+    _: c_int,
+) -> c_int {
+    if !desc.is_null() {
+        *desc = std::ptr::null();
+    }
+    -1
+}
+
+unsafe extern "system" fn set_marker_metadata_name(
+    _: *const c_void,
+    _: c_int,
+    _: *const c_char,
+    _: u8,
+    _: u8,
+) -> c_int {
+    -1
+}
+
+unsafe extern "system" fn register_thread(
+    thread_id: *mut u64,
+    _: *const c_char,
+    _: *const c_char,
+) -> c_int {
+    if !thread_id.is_null() {
+        *thread_id = 0;
+    }
+    -1
+}
+
+unsafe extern "system" fn unregister_thread(_: u64) -> c_int {
+    -1
+}
+
+static INTERFACES: IUnityInterfaces = IUnityInterfaces {
+    GetInterface: get_interface,
+    RegisterInterface: register_interface,
+    GetInterfaceSplit: get_interface_split,
+    RegisterInterfaceSplit: register_interface_split,
+};
+
+static GRAPHICS: IUnityGraphics = IUnityGraphics {
+    GetRenderer: get_renderer,
+    RegisterDeviceEventCallback: register_device_event_callback,
+    UnregisterDeviceEventCallback: unregister_device_event_callback,
+    ReserveEventIDRange: reserve_event_id_range,
+};
+
+static LOG: IUnityLog = IUnityLog { Log: log };
+
+static GRAPHICS_VULKAN: IUnityGraphicsVulkan = IUnityGraphicsVulkan {
+    InterceptInitialization: intercept_initialization,
+};
+
+static PROFILER: IUnityProfiler = IUnityProfiler {
+    EmitEvent: emit_event,
+    IsEnabled: profiler_disabled,
+    IsAvailable: profiler_disabled,
+    CreateMarker: create_marker,
+    SetMarkerMetadataName: set_marker_metadata_name,
+    RegisterThread: register_thread,
+    UnregisterThread: unregister_thread,
+};
+
+//  The plugin keeps its state in globals, so only one host exists at a time; it holds the replay
+//  lock, which also keeps replays (and the golden-image tests) from running while it lives.
+pub struct FakeUnity {
+    _exclusive: MutexGuard<'static, ()>,
+    loaded: bool,
+}
+
+impl FakeUnity {
+    pub fn new(renderer: GfxRenderer) -> FakeUnity {
+        let exclusive = crate::replay::REPLAYING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut state = state();
+        state.renderer = renderer as c_int;
+        state.callbacks.clear();
+        state.messages.clear();
+        state.next_event_id = FIRST_RESERVED_EVENT_ID;
+        state.vulkan_available = true;
+        state.vulkan_init_callback = None;
+        FakeUnity {
+            _exclusive: exclusive,
+            loaded: false,
+        }
+    }
+
+    pub fn load(&mut self) {
+        if !self.loaded {
+            self.loaded = true;
+            crate::UnityPluginLoad(&INTERFACES as *const IUnityInterfaces as *mut _);
+        }
+    }
+
+    pub fn unload(&mut self) {
+        if self.loaded {
+            self.loaded = false;
+            crate::UnityPluginUnload();
+        }
+    }
+
+    //  Takes effect on the next Initialize event, as when Unity recreates the device.
+    pub fn set_renderer(&self, renderer: GfxRenderer) {
+        state().renderer = renderer as c_int;
+    }
+
+    //  Whether IUnityGraphicsVulkan is found, as it is not in players that lack Vulkan support.
+    pub fn set_vulkan_available(&self, available: bool) {
+        state().vulkan_available = available;
+    }
+
+    //  The callback the plugin passed to IUnityGraphicsVulkan::InterceptInitialization.
+    pub fn vulkan_init_callback(&self) -> Option<VulkanInitCallback> {
+        state().vulkan_init_callback
+    }
+
+    pub fn device_event(&self, event_type: GfxDeviceEventType) {
+        //  Copied so callbacks can call back into the host.
+        let callbacks = state().callbacks.clone();
+        for callback in callbacks {
+            unsafe { callback(event_type as c_int) };
+        }
+    }
+
+    //  What `GL.IssuePluginEvent(GetRenderEventFunc(), event_id)` ends up calling.
+    pub fn render_event(&self, event_id: i32) {
+        crate::on_render_event(event_id);
+    }
+
+    pub fn device_event_callback_count(&self) -> usize {
+        state().callbacks.len()
+    }
+
+    pub fn messages(&self) -> Vec<(i32, String)> {
+        state().messages.clone()
+    }
+}
+
+impl Drop for FakeUnity {
+    fn drop(&mut self) {
+        self.unload();
+    }
+}
+
+#[test]
+fn test_plugin_lifecycle() {
+    let mut unity = FakeUnity::new(GfxRenderer::Metal);
+    unity.load();
+    assert_eq!(unity.device_event_callback_count(), 1);
+    assert_eq!(crate::GetActiveRenderer(), GfxRenderer::Metal as i32);
+    assert!(!crate::IsRenderApiAvailable());
+    assert!(unity
+        .messages()
+        .iter()
+        .any(|(log_type, message)| *log_type == LOG_WARNING && message.contains("no render API")));
+
+    assert!(crate::GetRenderEventFunc().is_some());
+    unity.render_event(crate::RenderEventId::Default as i32);
+    unity.device_event(GfxDeviceEventType::Shutdown);
+    unity.set_renderer(GfxRenderer::Vulkan);
+    unity.device_event(GfxDeviceEventType::Initialize);
+    assert_eq!(crate::GetActiveRenderer(), GfxRenderer::Vulkan as i32);
+    unity.device_event(GfxDeviceEventType::Shutdown);
+    assert_eq!(crate::GetActiveRenderer(), GfxRenderer::Null as i32);
+
+    unity.unload();
+    assert_eq!(unity.device_event_callback_count(), 0);
+}
+
+//  Loading under Vulkan hands the interception to Unity, and a missing IUnityGraphicsVulkan is
+//  reported instead of taking the plugin down.
+#[cfg(backend_vulkan)]
+#[test]
+fn test_plugin_load_with_vulkan() {
+    let mut unity = FakeUnity::new(GfxRenderer::Vulkan);
+    unity.load();
+    let callback = unity
+        .vulkan_init_callback()
+        .expect("no Vulkan init callback");
+    let intercept: unsafe extern "system" fn(_, _) -> _ =
+        crate::vulkan_api::vulkan_functions::intercept_vulkan_initialization;
+    assert_eq!(callback as usize, intercept as usize);
+    unity.device_event(GfxDeviceEventType::Initialize);
+    assert_eq!(crate::GetActiveRenderer(), GfxRenderer::Vulkan as i32);
+    assert!(!crate::IsRenderApiAvailable());
+    unity.unload();
+    drop(unity);
+
+    let mut unity = FakeUnity::new(GfxRenderer::Vulkan);
+    unity.set_vulkan_available(false);
+    unity.load();
+    assert!(unity.vulkan_init_callback().is_none());
+    assert!(unity.messages().iter().any(|(log_type, message)| {
+        *log_type == LOG_WARNING && message.contains("IUnityGraphicsVulkan")
+    }));
+    unity.unload();
+}
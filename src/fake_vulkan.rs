@@ -0,0 +1,471 @@
+//  In-process stand-in for the Vulkan loader, so the interception in vulkan_api.rs can be tested
+//  without a driver. `get_instance_proc_addr` plays the loader's vkGetInstanceProcAddr: it hands
+//  out stub entry points for a handful of functions, offers a configurable set of layers and
+//  extensions, and records every lookup and call along with the layers and extensions that
+//  instances and devices were created with.
+
+use crate::vulkan_api::hook;
+use ash::vk::Handle;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::{Mutex, MutexGuard};
+
+pub const INSTANCE: u64 = 0x1000;
+pub const PHYSICAL_DEVICE: u64 = 0x2000;
+pub const DEVICE: u64 = 0x3000;
+pub const DEBUG_MESSENGER: u64 = 0x4000;
+
+//  Reported through the debug messenger chained into vkCreateInstance, like a validation layer
+//  complaining about the create info.
+pub const INSTANCE_MESSAGE: &[u8] = b"fake validation error\0";
+
+#[derive(Default)]
+struct LoaderState {
+    layers: Vec<String>,
+    instance_extensions: Vec<String>,
+    device_extensions: Vec<String>,
+    lookups: Vec<String>,
+    calls: Vec<String>,
+    instance_layers: Vec<String>,
+    enabled_instance_extensions: Vec<String>,
+    enabled_device_extensions: Vec<String>,
+    chained_messenger: bool,
+}
+
+static STATE: Mutex<Option<LoaderState>> = Mutex::new(None);
+
+//  Serializes tests, as the interception keeps its state in globals.
+static EXCLUSIVE: Mutex<()> = Mutex::new(());
+
+fn with_state<R>(f: impl FnOnce(&mut LoaderState) -> R) -> R {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    f(state.get_or_insert_with(LoaderState::default))
+}
+
+fn call(name: &str) {
+    with_state(|state| state.calls.push(name.to_string()));
+}
+
+unsafe fn strings(count: u32, names: *const *const c_char) -> Vec<String> {
+    if names.is_null() {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(names, count as usize)
+        .iter()
+        .map(|&name| CStr::from_ptr(name).to_string_lossy().into_owned())
+        .collect()
+}
+
+fn fill_name(dst: &mut [c_char], name: &str) {
+    for (d, &s) in dst.iter_mut().zip(name.as_bytes()) {
+        *d = s as c_char;
+    }
+    dst[name.len().min(dst.len() - 1)] = 0;
+}
+
+//  The two-call enumeration protocol, including VK_INCOMPLETE for short arrays.
+unsafe fn enumerate<T: Default>(
+    names: &[String],
+    count: *mut u32,
+    properties: *mut T,
+    fill: impl Fn(&mut T, &str),
+) -> ash::vk::Result {
+    if properties.is_null() {
+        *count = names.len() as u32;
+        return ash::vk::Result::SUCCESS;
+    }
+    let written = names.len().min(*count as usize);
+    for (i, name) in names.iter().take(written).enumerate() {
+        let mut p = T::default();
+        fill(&mut p, name);
+        *properties.add(i) = p;
+    }
+    *count = written as u32;
+    if written < names.len() {
+        ash::vk::Result::INCOMPLETE
+    } else {
+        ash::vk::Result::SUCCESS
+    }
+}
+
+unsafe extern "system" fn enumerate_instance_layer_properties(
+    count: *mut u32,
+    properties: *mut ash::vk::LayerProperties,
+) -> ash::vk::Result {
+    call("vkEnumerateInstanceLayerProperties");
+    let layers = with_state(|state| state.layers.clone());
+    enumerate(&layers, count, properties, |p, name| {
+        fill_name(&mut p.layer_name, name)
+    })
+}
+
+//  Layers provide no extensions of their own.
+unsafe extern "system" fn enumerate_instance_extension_properties(
+    layer_name: *const c_char,
+    count: *mut u32,
+    properties: *mut ash::vk::ExtensionProperties,
+) -> ash::vk::Result {
+    call("vkEnumerateInstanceExtensionProperties");
+    let extensions = if layer_name.is_null() {
+        with_state(|state| state.instance_extensions.clone())
+    } else {
+        Vec::new()
+    };
+    enumerate(&extensions, count, properties, |p, name| {
+        fill_name(&mut p.extension_name, name)
+    })
+}
+
+unsafe extern "system" fn enumerate_device_extension_properties(
+    _: ash::vk::PhysicalDevice,
+    _: *const c_char,
+    count: *mut u32,
+    properties: *mut ash::vk::ExtensionProperties,
+) -> ash::vk::Result {
+    call("vkEnumerateDeviceExtensionProperties");
+    let extensions = with_state(|state| state.device_extensions.clone());
+    enumerate(&extensions, count, properties, |p, name| {
+        fill_name(&mut p.extension_name, name)
+    })
+}
+
+unsafe extern "system" fn create_instance(
+    p_create_info: *const ash::vk::InstanceCreateInfo,
+    _: *const ash::vk::AllocationCallbacks,
+    p_instance: *mut ash::vk::Instance,
+) -> ash::vk::Result {
+    call("vkCreateInstance");
+    let create_info = &*p_create_info;
+    let layers = strings(
+        create_info.enabled_layer_count,
+        create_info.pp_enabled_layer_names,
+    );
+    let extensions = strings(
+        create_info.enabled_extension_count,
+        create_info.pp_enabled_extension_names,
+    );
+
+    let mut next = create_info.p_next as *const ash::vk::BaseInStructure;
+    let mut chained_messenger = false;
+    while !next.is_null() {
+        if (*next).s_type == ash::vk::StructureType::DEBUG_UTILS_MESSENGER_CREATE_INFO_EXT {
+            let messenger = &*(next as *const ash::vk::DebugUtilsMessengerCreateInfoEXT);
+            let data = ash::vk::DebugUtilsMessengerCallbackDataEXT {
+                p_message: INSTANCE_MESSAGE.as_ptr() as *const c_char,
+                ..Default::default()
+            };
+            if let Some(callback) = messenger.pfn_user_callback {
+                callback(
+                    ash::vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+                    ash::vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+                    &data,
+                    messenger.p_user_data,
+                );
+            }
+            chained_messenger = true;
+        }
+        next = (*next).p_next;
+    }
+
+    with_state(|state| {
+        state.instance_layers = layers;
+        state.enabled_instance_extensions = extensions;
+        state.chained_messenger = chained_messenger;
+    });
+    *p_instance = ash::vk::Instance::from_raw(INSTANCE);
+    ash::vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_instance(
+    _: ash::vk::Instance,
+    _: *const ash::vk::AllocationCallbacks,
+) {
+    call("vkDestroyInstance");
+}
+
+unsafe extern "system" fn create_device(
+    _: ash::vk::PhysicalDevice,
+    p_create_info: *const ash::vk::DeviceCreateInfo,
+    _: *const ash::vk::AllocationCallbacks,
+    p_device: *mut ash::vk::Device,
+) -> ash::vk::Result {
+    call("vkCreateDevice");
+    let extensions = strings(
+        (*p_create_info).enabled_extension_count,
+        (*p_create_info).pp_enabled_extension_names,
+    );
+    with_state(|state| state.enabled_device_extensions = extensions);
+    *p_device = ash::vk::Device::from_raw(DEVICE);
+    ash::vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn create_debug_utils_messenger_ext(
+    _: ash::vk::Instance,
+    _: *const ash::vk::DebugUtilsMessengerCreateInfoEXT,
+    _: *const ash::vk::AllocationCallbacks,
+    p_messenger: *mut ash::vk::DebugUtilsMessengerEXT,
+) -> ash::vk::Result {
+    call("vkCreateDebugUtilsMessengerEXT");
+    *p_messenger = ash::vk::DebugUtilsMessengerEXT::from_raw(DEBUG_MESSENGER);
+    ash::vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_debug_utils_messenger_ext(
+    _: ash::vk::Instance,
+    _: ash::vk::DebugUtilsMessengerEXT,
+    _: *const ash::vk::AllocationCallbacks,
+) {
+    call("vkDestroyDebugUtilsMessengerEXT");
+}
+
+pub unsafe extern "system" fn get_physical_device_properties(
+    _: ash::vk::PhysicalDevice,
+    _: *mut ash::vk::PhysicalDeviceProperties,
+) {
+    call("vkGetPhysicalDeviceProperties");
+}
+
+pub unsafe extern "system" fn cmd_draw(_: ash::vk::CommandBuffer, _: u32, _: u32, _: u32, _: u32) {
+    call("vkCmdDraw");
+}
+
+unsafe extern "system" fn get_device_proc_addr(
+    _: ash::vk::Device,
+    name: *const c_char,
+) -> ash::vk::PFN_vkVoidFunction {
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    with_state(|state| state.lookups.push(name.clone()));
+    match name.as_str() {
+        "vkCmdDraw" => hook(cmd_draw as unsafe extern "system" fn(_, _, _, _, _)),
+        _ => None,
+    }
+}
+
+//  The loader's vkGetInstanceProcAddr. Unknown names return null, as for an unsupported function.
+pub extern "system" fn get_instance_proc_addr(
+    _: ash::vk::Instance,
+    name: *const c_char,
+) -> ash::vk::PFN_vkVoidFunction {
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    with_state(|state| state.lookups.push(name.clone()));
+    unsafe {
+        match name.as_str() {
+            "vkGetInstanceProcAddr" => {
+                hook(get_instance_proc_addr as extern "system" fn(_, _) -> _)
+            }
+            "vkEnumerateInstanceLayerProperties" => {
+                hook(enumerate_instance_layer_properties as unsafe extern "system" fn(_, _) -> _)
+            }
+            "vkEnumerateInstanceExtensionProperties" => hook(
+                enumerate_instance_extension_properties as unsafe extern "system" fn(_, _, _) -> _,
+            ),
+            "vkEnumerateDeviceExtensionProperties" => hook(
+                enumerate_device_extension_properties as unsafe extern "system" fn(_, _, _, _) -> _,
+            ),
+            "vkCreateInstance" => hook(create_instance as unsafe extern "system" fn(_, _, _) -> _),
+            "vkDestroyInstance" => hook(destroy_instance as unsafe extern "system" fn(_, _)),
+            "vkCreateDevice" => hook(create_device as unsafe extern "system" fn(_, _, _, _) -> _),
+            "vkGetDeviceProcAddr" => {
+                hook(get_device_proc_addr as unsafe extern "system" fn(_, _) -> _)
+            }
+            "vkCreateDebugUtilsMessengerEXT" => {
+                hook(create_debug_utils_messenger_ext as unsafe extern "system" fn(_, _, _, _) -> _)
+            }
+            "vkDestroyDebugUtilsMessengerEXT" => {
+                hook(destroy_debug_utils_messenger_ext as unsafe extern "system" fn(_, _, _))
+            }
+            "vkGetPhysicalDeviceProperties" => {
+                hook(get_physical_device_properties as unsafe extern "system" fn(_, _))
+            }
+            "vkCmdDraw" => hook(cmd_draw as unsafe extern "system" fn(_, _, _, _, _)),
+            _ => None,
+        }
+    }
+}
+
+pub struct FakeVulkan {
+    _exclusive: MutexGuard<'static, ()>,
+}
+
+impl FakeVulkan {
+    pub fn new(layers: &[&str], instance_extensions: &[&str], device_extensions: &[&str]) -> Self {
+        let exclusive = EXCLUSIVE.lock().unwrap_or_else(|e| e.into_inner());
+        let to_strings = |names: &[&str]| names.iter().map(|s| s.to_string()).collect();
+        *STATE.lock().unwrap_or_else(|e| e.into_inner()) = Some(LoaderState {
+            layers: to_strings(layers),
+            instance_extensions: to_strings(instance_extensions),
+            device_extensions: to_strings(device_extensions),
+            ..Default::default()
+        });
+        FakeVulkan {
+            _exclusive: exclusive,
+        }
+    }
+
+    pub fn lookups(&self) -> Vec<String> {
+        with_state(|state| state.lookups.clone())
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        with_state(|state| state.calls.clone())
+    }
+
+    pub fn instance_layers(&self) -> Vec<String> {
+        with_state(|state| state.instance_layers.clone())
+    }
+
+    pub fn instance_extensions(&self) -> Vec<String> {
+        with_state(|state| state.enabled_instance_extensions.clone())
+    }
+
+    pub fn device_extensions(&self) -> Vec<String> {
+        with_state(|state| state.enabled_device_extensions.clone())
+    }
+
+    pub fn chained_messenger(&self) -> bool {
+        with_state(|state| state.chained_messenger)
+    }
+}
+
+fn c_names(names: &[&'static [u8]]) -> Vec<*const c_char> {
+    names
+        .iter()
+        .map(|name| name.as_ptr() as *const c_char)
+        .collect()
+}
+
+unsafe fn lookup(
+    gipa: ash::vk::PFN_vkGetInstanceProcAddr,
+    instance: ash::vk::Instance,
+    name: &[u8],
+) -> Option<usize> {
+    gipa(instance, name.as_ptr() as *const c_char).map(|f| f as usize)
+}
+
+#[test]
+fn test_vulkan_interception() {
+    use crate::vulkan_api::{self, vulkan_functions};
+    use crate::ValidationSeverity;
+
+    let vulkan = FakeVulkan::new(
+        &["VK_LAYER_KHRONOS_validation"],
+        &["VK_KHR_surface", "VK_EXT_debug_utils"],
+        &["VK_KHR_swapchain", "VK_KHR_external_memory"],
+    );
+    let requested = |name: &'static [u8]| CStr::from_bytes_with_nul(name).unwrap();
+    vulkan_api::request_instance_extension(requested(b"VK_KHR_not_available\0"));
+    vulkan_api::request_device_extension(requested(b"VK_KHR_external_memory\0"));
+    vulkan_api::enable_validation();
+    crate::vulkan_trace::enable(16, None);
+
+    unsafe {
+        let gipa = vulkan_functions::intercept_vulkan_initialization(
+            get_instance_proc_addr,
+            std::ptr::null_mut(),
+        );
+        let null = ash::vk::Instance::null();
+        //  Hooked functions are replaced, everything else comes straight from the loader.
+        assert_eq!(
+            lookup(gipa, null, b"vkGetInstanceProcAddr\0"),
+            Some(gipa as usize)
+        );
+        let create_instance_fn = lookup(gipa, null, b"vkCreateInstance\0").unwrap();
+        assert_ne!(
+            create_instance_fn,
+            (create_instance as unsafe extern "system" fn(_, _, _) -> _) as usize
+        );
+        assert_eq!(
+            lookup(gipa, null, b"vkGetPhysicalDeviceProperties\0"),
+            Some((get_physical_device_properties as unsafe extern "system" fn(_, _)) as usize)
+        );
+        assert_eq!(lookup(gipa, null, b"vkNotAFunction\0"), None);
+        //  Instance and device functions need an instance.
+        assert_eq!(lookup(gipa, null, b"vkCreateDevice\0"), None);
+        assert_eq!(lookup(gipa, null, b"vkDestroyInstance\0"), None);
+        assert_eq!(lookup(gipa, null, b"vkGetDeviceProcAddr\0"), None);
+        assert!(vulkan.lookups().iter().any(|name| name == "vkNotAFunction"));
+
+        //  Unity's names are kept, requested ones appended once, unavailable ones dropped.
+        let errors = crate::validation::count(ValidationSeverity::Error);
+        let extensions = c_names(&[b"VK_KHR_surface\0", b"VK_EXT_debug_utils\0"]);
+        let create_info = ash::vk::InstanceCreateInfo {
+            enabled_extension_count: extensions.len() as u32,
+            pp_enabled_extension_names: extensions.as_ptr(),
+            ..Default::default()
+        };
+        let create_instance_fn: ash::vk::PFN_vkCreateInstance =
+            std::mem::transmute(create_instance_fn);
+        let mut instance = ash::vk::Instance::null();
+        assert_eq!(
+            create_instance_fn(&create_info, std::ptr::null(), &mut instance),
+            ash::vk::Result::SUCCESS
+        );
+        assert_eq!(instance.as_raw(), INSTANCE);
+        assert_eq!(vulkan.instance_layers(), ["VK_LAYER_KHRONOS_validation"]);
+        assert_eq!(
+            vulkan.instance_extensions(),
+            ["VK_KHR_surface", "VK_EXT_debug_utils"]
+        );
+        assert!(vulkan.chained_messenger());
+        assert!(crate::validation::count(ValidationSeverity::Error) > errors);
+        assert!(vulkan
+            .calls()
+            .iter()
+            .any(|name| name == "vkCreateDebugUtilsMessengerEXT"));
+
+        let create_device_fn: ash::vk::PFN_vkCreateDevice =
+            std::mem::transmute(lookup(gipa, instance, b"vkCreateDevice\0").unwrap());
+        let extensions = c_names(&[b"VK_KHR_swapchain\0"]);
+        let create_info = ash::vk::DeviceCreateInfo {
+            enabled_extension_count: extensions.len() as u32,
+            pp_enabled_extension_names: extensions.as_ptr(),
+            ..Default::default()
+        };
+        let mut device = ash::vk::Device::null();
+        assert_eq!(
+            create_device_fn(
+                ash::vk::PhysicalDevice::from_raw(PHYSICAL_DEVICE),
+                &create_info,
+                std::ptr::null(),
+                &mut device
+            ),
+            ash::vk::Result::SUCCESS
+        );
+        assert_eq!(
+            vulkan.device_extensions(),
+            ["VK_KHR_swapchain", "VK_KHR_external_memory"]
+        );
+
+        //  With tracing on, device functions are wrapped and still reach the driver.
+        let gdpa: ash::vk::PFN_vkGetDeviceProcAddr =
+            std::mem::transmute(lookup(gipa, instance, b"vkGetDeviceProcAddr\0").unwrap());
+        let cmd_draw_fn = gdpa(device, b"vkCmdDraw\0".as_ptr() as *const c_char).unwrap();
+        assert_ne!(
+            cmd_draw_fn as usize,
+            (cmd_draw as unsafe extern "system" fn(_, _, _, _, _)) as usize
+        );
+        let cmd_draw_fn: ash::vk::PFN_vkCmdDraw = std::mem::transmute(cmd_draw_fn);
+        cmd_draw_fn(ash::vk::CommandBuffer::null(), 3, 1, 0, 0);
+        assert!(vulkan.calls().iter().any(|name| name == "vkCmdDraw"));
+        assert_eq!(
+            crate::vulkan_trace::recorded_calls(),
+            [(
+                "vkCmdDraw",
+                "command_buffer=0x0, vertex_count=3, instance_count=1, first_vertex=0, first_instance=0"
+                    .to_string()
+            )]
+        );
+
+        //  The messenger goes away before the instance.
+        let destroy_instance_fn: ash::vk::PFN_vkDestroyInstance =
+            std::mem::transmute(lookup(gipa, instance, b"vkDestroyInstance\0").unwrap());
+        destroy_instance_fn(instance, std::ptr::null());
+        let calls = vulkan.calls();
+        assert_eq!(
+            &calls[calls.len() - 2..],
+            ["vkDestroyDebugUtilsMessengerEXT", "vkDestroyInstance"]
+        );
+    }
+}
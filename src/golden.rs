@@ -0,0 +1,345 @@
+//  Golden-image tests: every effect is rendered at fixed timestamps by replaying a short call stream
+//  on the software backend, and its output is compared with the references in tests/golden.
+//  Set RENDERING_PLUGIN_UPDATE_GOLDEN=1 to rewrite the references instead. On a mismatch the actual
+//  output (and, for images, an amplified difference image) is written to target/golden.
+
+use crate::image::Image;
+use crate::recorder::Call;
+use crate::replay;
+use std::path::{Path, PathBuf};
+
+const REFERENCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const FAILURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/golden");
+const UPDATE_ENV: &str = "RENDERING_PLUGIN_UPDATE_GOLDEN";
+
+const FRAMEBUFFER_SIZE: (u32, u32) = (128, 128);
+const TEXTURE_SIZE: i32 = 64;
+const TIMESTAMPS: [f64; 3] = [0.0, 0.8, 2.5];
+
+const TEXTURE: u64 = 1;
+const VERTEX_BUFFER: u64 = 2;
+const INDEX_BUFFER: u64 = 3;
+
+#[derive(Clone, Copy, Debug)]
+enum Output {
+    Framebuffer,
+    Texture(u64),
+    F32Buffer(u64),
+    U32Buffer(u64),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Tolerance {
+    min_psnr: f64,
+    max_abs_diff: f64,
+    //  Leaves pixels on an edge of the reference (next to a pixel of another color) out of the
+    //  maximum difference, so rasterization differences only count towards the PSNR there.
+    mask_edges: bool,
+}
+
+const EXACT: Tolerance = Tolerance {
+    min_psnr: f64::INFINITY,
+    max_abs_diff: 0.0,
+    mask_edges: false,
+};
+
+struct Effect {
+    name: &'static str,
+    setup: fn() -> Vec<Call>,
+    output: Output,
+    tolerance: Tolerance,
+}
+
+//  A new effect only needs an entry here and `RENDERING_PLUGIN_UPDATE_GOLDEN=1 cargo test` to
+//  create its references.
+const EFFECTS: [Effect; 4] = [
+    //  Rasterization differences along the edges are allowed to flip whole pixels.
+    Effect {
+        name: "triangle",
+        setup: Vec::new,
+        output: Output::Framebuffer,
+        tolerance: Tolerance {
+            min_psnr: 30.0,
+            max_abs_diff: 2.0,
+            mask_edges: true,
+        },
+    },
+    Effect {
+        name: "plasma",
+        setup: plasma_texture,
+        output: Output::Texture(TEXTURE),
+        tolerance: Tolerance {
+            min_psnr: 45.0,
+            max_abs_diff: 2.0,
+            mask_edges: false,
+        },
+    },
+    Effect {
+        name: "vertex_wave",
+        setup: grid_mesh,
+        output: Output::F32Buffer(VERTEX_BUFFER),
+        tolerance: Tolerance {
+            min_psnr: 80.0,
+            max_abs_diff: 1e-4,
+            mask_edges: false,
+        },
+    },
+    Effect {
+        name: "index_reveal",
+        setup: grid_mesh,
+        output: Output::U32Buffer(INDEX_BUFFER),
+        tolerance: EXACT,
+    },
+];
+
+fn plasma_texture() -> Vec<Call> {
+    vec![Call::SetTexture {
+        handle: TEXTURE,
+        width: TEXTURE_SIZE,
+        height: TEXTURE_SIZE,
+    }]
+}
+
+//  A 5x5 vertex grid on the XZ plane, like a subdivided Unity plane.
+fn grid_mesh() -> Vec<Call> {
+    const SIZE: u32 = 5;
+    let (mut vertices, mut normals, mut uv, mut indices) = (vec![], vec![], vec![], vec![]);
+    for z in 0..SIZE {
+        for x in 0..SIZE {
+            vertices.extend_from_slice(&[x as f32 - 2.0, 0.0, z as f32 - 2.0]);
+            normals.extend_from_slice(&[0.0, 1.0, 0.0]);
+            uv.extend_from_slice(&[x as f32 / 4.0, z as f32 / 4.0]);
+        }
+    }
+    for z in 0..SIZE - 1 {
+        for x in 0..SIZE - 1 {
+            let i = z * SIZE + x;
+            indices.extend_from_slice(&[i, i + SIZE, i + 1, i + 1, i + SIZE, i + SIZE + 1]);
+        }
+    }
+    vec![
+        Call::SetMeshBuffers {
+            handle: VERTEX_BUFFER,
+            vertex_count: (SIZE * SIZE) as i32,
+            vertices,
+            normals,
+            uv,
+        },
+        Call::SetMeshIndexBuffer {
+            handle: INDEX_BUFFER,
+            index_count: indices.len() as i32,
+            index_format: crate::render_api::IndexFormat::UInt32 as i32,
+            indices,
+        },
+    ]
+}
+
+enum Actual {
+    Image(Image),
+    Buffer(Vec<u8>),
+}
+
+fn render(effect: &Effect, time: f64) -> Option<Actual> {
+    let mut calls = vec![Call::SetTimeFromUnityDouble(time)];
+    calls.extend((effect.setup)());
+    calls.push(Call::RenderEvent(crate::RenderEventId::Default as i32));
+
+    let mut actual = None;
+    replay::replay(&calls, FRAMEBUFFER_SIZE, |_, framebuffer, resources| {
+        actual = match effect.output {
+            Output::Framebuffer => Some(Actual::Image(framebuffer.clone())),
+            Output::Texture(handle) => resources.get_texture(handle).cloned().map(Actual::Image),
+            Output::F32Buffer(handle) | Output::U32Buffer(handle) => resources
+                .get_buffer(handle)
+                .map(|buffer| Actual::Buffer(buffer.to_vec())),
+        };
+    });
+    actual
+}
+
+fn samples(output: Output, bytes: &[u8]) -> Vec<f64> {
+    match output {
+        Output::Framebuffer | Output::Texture(_) => bytes.iter().map(|&b| b as f64).collect(),
+        Output::F32Buffer(_) => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect(),
+        Output::U32Buffer(_) => bytes
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect(),
+    }
+}
+
+//  Pixels of `image` that differ from one of their 8 neighbors.
+fn edge_mask(image: &Image) -> Vec<bool> {
+    let (width, height) = (image.width as i64, image.height as i64);
+    let mut mask = vec![false; (width * height) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.pixel(x as u32, y as u32);
+            mask[(y * width + x) as usize] = (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+                .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < width && ny < height)
+                .any(|(nx, ny)| image.pixel(nx as u32, ny as u32) != pixel);
+        }
+    }
+    mask
+}
+
+//  PSNR is relative to 255 for images and to the largest reference magnitude for buffers. Samples
+//  of pixels set in `mask` (4 samples per pixel) are left out of the maximum difference.
+fn compare(output: Output, actual: &[u8], expected: &[u8], mask: Option<&[bool]>) -> (f64, f64) {
+    let (actual, expected) = (samples(output, actual), samples(output, expected));
+    if actual.len() != expected.len() {
+        return (0.0, f64::INFINITY);
+    }
+    let peak = match output {
+        Output::Framebuffer | Output::Texture(_) => 255.0,
+        _ => expected.iter().fold(1.0f64, |peak, v| peak.max(v.abs())),
+    };
+    let mut squared_error = 0.0;
+    let mut max_abs_diff = 0.0f64;
+    for (i, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+        let diff = (a - e).abs();
+        squared_error += diff * diff;
+        if !matches!(mask, Some(mask) if mask[i / 4]) {
+            max_abs_diff = max_abs_diff.max(diff);
+        }
+    }
+    let mse = squared_error / expected.len().max(1) as f64;
+    let psnr = if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (peak * peak / mse).log10()
+    };
+    (psnr, max_abs_diff)
+}
+
+fn diff_image(actual: &Image, expected: &Image) -> Image {
+    let mut diff = Image::new(actual.width, actual.height);
+    for (dst, (a, e)) in diff.pixels.chunks_exact_mut(4).zip(
+        actual
+            .pixels
+            .chunks_exact(4)
+            .zip(expected.pixels.chunks_exact(4)),
+    ) {
+        for i in 0..3 {
+            let d = (a[i] as i32 - e[i] as i32)
+                .abs()
+                .max((a[3] as i32 - e[3] as i32).abs());
+            dst[i] = (d * 4).min(255) as u8;
+        }
+        dst[3] = 255;
+    }
+    diff
+}
+
+fn check(effect: &Effect, time: f64, update: bool) -> Result<(), String> {
+    let name = format!("{}_{:.2}s", effect.name, time);
+    let actual = render(effect, time).ok_or_else(|| format!("{}: no output", name))?;
+    let extension = match actual {
+        Actual::Image(_) => "png",
+        Actual::Buffer(_) => "bin",
+    };
+    let reference_path = Path::new(REFERENCE_DIR).join(format!("{}.{}", name, extension));
+
+    let write = |path: &Path, actual: &Actual| -> Result<(), String> {
+        std::fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+        match actual {
+            Actual::Image(image) => image.save(path),
+            Actual::Buffer(bytes) => std::fs::write(path, bytes),
+        }
+        .map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    if update {
+        return write(&reference_path, &actual);
+    }
+
+    let (psnr, max_abs_diff, expected_image) = match &actual {
+        Actual::Image(image) => {
+            let expected = Image::load(&reference_path)?;
+            if (expected.width, expected.height) != (image.width, image.height) {
+                return Err(format!("{}: size differs from the reference", name));
+            }
+            let mask = Some(edge_mask(&expected)).filter(|_| effect.tolerance.mask_edges);
+            let (psnr, max_abs_diff) = compare(
+                effect.output,
+                &image.pixels,
+                &expected.pixels,
+                mask.as_deref(),
+            );
+            (psnr, max_abs_diff, Some(expected))
+        }
+        Actual::Buffer(bytes) => {
+            let expected = std::fs::read(&reference_path)
+                .map_err(|e| format!("{}: {}", reference_path.display(), e))?;
+            let (psnr, max_abs_diff) = compare(effect.output, bytes, &expected, None);
+            (psnr, max_abs_diff, None)
+        }
+    };
+    if psnr >= effect.tolerance.min_psnr && max_abs_diff <= effect.tolerance.max_abs_diff {
+        return Ok(());
+    }
+
+    let failure_path = |suffix: &str| -> PathBuf {
+        Path::new(FAILURE_DIR).join(format!("{}.{}.{}", name, suffix, extension))
+    };
+    write(&failure_path("actual"), &actual)?;
+    if let (Actual::Image(image), Some(expected)) = (&actual, &expected_image) {
+        write(
+            &failure_path("diff"),
+            &Actual::Image(diff_image(image, expected)),
+        )?;
+    }
+    Err(format!(
+        "{}: PSNR {:.2} dB (min {:.2}), max abs diff {} (max {}), output written to {}",
+        name,
+        psnr,
+        effect.tolerance.min_psnr,
+        max_abs_diff,
+        effect.tolerance.max_abs_diff,
+        FAILURE_DIR
+    ))
+}
+
+#[test]
+fn test_golden_images() {
+    let update = std::env::var_os(UPDATE_ENV).is_some();
+    let failures = EFFECTS
+        .iter()
+        .flat_map(|effect| TIMESTAMPS.iter().map(move |&time| (effect, time)))
+        .filter_map(|(effect, time)| check(effect, time, update).err())
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_edge_masked_compare() {
+    let mut expected = Image::new(8, 8);
+    expected.fill([0, 0, 0, 255]);
+    for y in 0..8 {
+        for x in 4..8 {
+            expected.set_pixel(x, y, [255, 0, 0, 255]);
+        }
+    }
+    let mask = edge_mask(&expected);
+    assert_eq!(mask.iter().filter(|&&edge| edge).count(), 16);
+
+    let compare_with = |x: u32, y: u32| {
+        let mut actual = expected.clone();
+        actual.set_pixel(x, y, [0, 255, 0, 255]);
+        compare(
+            Output::Framebuffer,
+            &actual.pixels,
+            &expected.pixels,
+            Some(&mask),
+        )
+    };
+    //  A flipped pixel on the edge only lowers the PSNR, one inside a shape is a difference.
+    let (psnr, max_abs_diff) = compare_with(4, 3);
+    assert!(psnr.is_finite());
+    assert_eq!(max_abs_diff, 0.0);
+    assert_eq!(compare_with(6, 3).1, 255.0);
+    assert_eq!(compare_with(1, 3).1, 255.0);
+}
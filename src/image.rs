@@ -0,0 +1,733 @@
+use std::path::Path;
+
+//  Tightly packed RGBA8 image, used as the software backend's framebuffer and textures.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn row_pitch(&self) -> usize {
+        self.width as usize * 4
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = y as usize * self.row_pitch() + x as usize * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        let i = y as usize * self.row_pitch() + x as usize * 4;
+        self.pixels[i..i + 4].copy_from_slice(&color);
+    }
+
+    pub fn fill(&mut self, color: [u8; 4]) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    //  2x2 box filter, rounding odd sizes down.
+    fn halved(&self) -> Image {
+        let mut image = Image::new((self.width / 2).max(1), (self.height / 2).max(1));
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let mut sum = [0u32; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let pixel = self.pixel(
+                        (x * 2 + dx).min(self.width - 1),
+                        (y * 2 + dy).min(self.height - 1),
+                    );
+                    for (sum, &value) in sum.iter_mut().zip(&pixel) {
+                        *sum += value as u32;
+                    }
+                }
+                image.set_pixel(x, y, sum.map(|sum| ((sum + 2) / 4) as u8));
+            }
+        }
+        image
+    }
+
+    //  Bilinear resampling with aligned pixel centers. Large reductions go through 2x2 box filtered
+    //  halvings first, so they average the source instead of skipping most of it.
+    pub fn resized(&self, width: u32, height: u32) -> Image {
+        let mut source = std::borrow::Cow::Borrowed(self);
+        while source.width >= width * 2 && source.height >= height * 2 {
+            source = std::borrow::Cow::Owned(source.halved());
+        }
+        if (source.width, source.height) == (width, height) {
+            return source.into_owned();
+        }
+        let sample = |dst: u32, dst_size: u32, src_size: u32| {
+            let position = ((dst as f32 + 0.5) * src_size as f32 / dst_size as f32 - 0.5).max(0.0);
+            let index = (position as u32).min(src_size - 1);
+            (
+                index,
+                (index + 1).min(src_size - 1),
+                position - index as f32,
+            )
+        };
+        let mut image = Image::new(width, height);
+        for y in 0..height {
+            let (y0, y1, fy) = sample(y, height, source.height);
+            for x in 0..width {
+                let (x0, x1, fx) = sample(x, width, source.width);
+                let (a, b) = (source.pixel(x0, y0), source.pixel(x1, y0));
+                let (c, d) = (source.pixel(x0, y1), source.pixel(x1, y1));
+                let mut color = [0; 4];
+                for i in 0..4 {
+                    let top = a[i] as f32 + (b[i] as f32 - a[i] as f32) * fx;
+                    let bottom = c[i] as f32 + (d[i] as f32 - c[i] as f32) * fx;
+                    color[i] = (top + (bottom - top) * fy).round() as u8;
+                }
+                image.set_pixel(x, y, color);
+            }
+        }
+        image
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+}
+
+impl Image {
+    pub fn encode(&self, format: ImageFormat) -> Vec<u8> {
+        match format {
+            ImageFormat::Png => encode_png(self),
+            ImageFormat::Ppm => encode_ppm(self),
+            ImageFormat::Exr => encode_exr(self),
+        }
+    }
+
+    //  PNG, JPEG and Radiance HDR files are recognized by their contents, TGA files (which have no
+    //  signature) by the extension. HDR values are clamped to 0..1 and stored with the sRGB curve.
+    pub fn load(path: &Path) -> Result<Image, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        let result = if bytes.starts_with(PNG_SIGNATURE) {
+            decode_png(&bytes)
+        } else if bytes.starts_with(&[0xFF, 0xD8]) {
+            crate::jpeg::decode(&bytes)
+        } else if bytes.starts_with(b"#?") {
+            decode_hdr(&bytes)
+        } else if extension.as_deref() == Some("tga") {
+            decode_tga(&bytes)
+        } else {
+            return Err(format!("unsupported image file: {}", path.display()));
+        };
+        result.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    //  The format is chosen from the file extension.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let format = ImageFormat::from_path(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unsupported image file extension: {}", path.display()),
+            )
+        })?;
+        std::fs::write(path, self.encode(format))
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn png_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(chunk_type);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+//  Larger TGA and HDR images are rejected before their pixels are allocated.
+const MAX_DECODED_SIZE: usize = 16384;
+
+fn check_decoded_size(format: &str, width: usize, height: usize) -> Result<(), String> {
+    if width == 0 || height == 0 || width > MAX_DECODED_SIZE || height > MAX_DECODED_SIZE {
+        return Err(format!(
+            "unsupported {} image size {}x{}",
+            format, width, height
+        ));
+    }
+    Ok(())
+}
+
+//  RGBA8 without compression (stored deflate blocks), which keeps the encoder small and the
+//  output exact. Captures are evidence, not assets, so the size is not a concern.
+fn encode_png(image: &Image) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((image.row_pitch() + 1) * image.height as usize);
+    for row in image.pixels.chunks_exact(image.row_pitch().max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    png_chunk(&mut out, b"IHDR", &header);
+    png_chunk(&mut out, b"IDAT", &zlib);
+    png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+//  Non-interlaced PNGs of any color type with 8 or 16 bits per sample (16-bit samples are
+//  truncated to 8 bits).
+fn decode_png(bytes: &[u8]) -> Result<Image, String> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err("not a PNG file".to_string());
+    }
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut transparency = Vec::new();
+    let mut data = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 12 <= bytes.len() {
+        let length =
+            u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+                as usize;
+        let chunk = bytes
+            .get(pos + 8..pos + 8 + length)
+            .ok_or_else(|| "truncated PNG chunk".to_string())?;
+        match &bytes[pos + 4..pos + 8] {
+            b"IHDR" if length >= 13 => header = Some(chunk),
+            b"PLTE" => palette = chunk.to_vec(),
+            b"tRNS" => transparency = chunk.to_vec(),
+            b"IDAT" => data.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        pos += 12 + length;
+    }
+
+    let header = header.ok_or_else(|| "missing PNG header".to_string())?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(format!("invalid PNG color type {}", color_type)),
+    };
+    if !(depth == 8 || depth == 16 && color_type != 3) || interlace != 0 {
+        return Err(format!(
+            "unsupported PNG (bit depth {}, interlace {})",
+            depth, interlace
+        ));
+    }
+
+    let bytes_per_pixel = channels * depth as usize / 8;
+    let stride = width as usize * bytes_per_pixel;
+    let raw = crate::inflate::zlib_decompress(&data)?;
+    if raw.len() < (stride + 1) * height as usize {
+        return Err("truncated PNG image data".to_string());
+    }
+
+    let mut image = Image::new(width, height);
+    let mut previous = vec![0u8; stride];
+    let mut row = vec![0u8; stride];
+    for y in 0..height as usize {
+        let filtered = &raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+        for i in 0..stride {
+            let a = if i >= bytes_per_pixel {
+                row[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let b = previous[i];
+            let c = if i >= bytes_per_pixel {
+                previous[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let predictor = match filtered[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                filter => return Err(format!("invalid PNG filter {}", filter)),
+            };
+            row[i] = filtered[i + 1].wrapping_add(predictor);
+        }
+
+        for x in 0..width as usize {
+            let sample = |c: usize| row[(x * channels + c) * depth as usize / 8];
+            let color = match color_type {
+                0 => [sample(0), sample(0), sample(0), 255],
+                2 => [sample(0), sample(1), sample(2), 255],
+                3 => {
+                    let index = sample(0) as usize;
+                    let rgb = palette
+                        .get(index * 3..index * 3 + 3)
+                        .ok_or_else(|| "PNG palette index out of range".to_string())?;
+                    let alpha = transparency.get(index).copied().unwrap_or(255);
+                    [rgb[0], rgb[1], rgb[2], alpha]
+                }
+                4 => [sample(0), sample(0), sample(0), sample(1)],
+                _ => [sample(0), sample(1), sample(2), sample(3)],
+            };
+            image.set_pixel(x as u32, y as u32, color);
+        }
+        std::mem::swap(&mut previous, &mut row);
+    }
+    Ok(image)
+}
+
+fn tga_color(pixel: &[u8]) -> [u8; 4] {
+    match *pixel {
+        [low, high] => {
+            let value = u16::from_le_bytes([low, high]);
+            let channel = |shift: u16| (((value >> shift) & 31) * 255 / 31) as u8;
+            [channel(10), channel(5), channel(0), 255]
+        }
+        [b, g, r] => [r, g, b, 255],
+        [b, g, r, a] => [r, g, b, a],
+        _ => [pixel[0], pixel[0], pixel[0], 255],
+    }
+}
+
+//  Uncompressed and RLE TGA files: true color with 16, 24 or 32 bits per pixel, 8-bit grayscale,
+//  and 8-bit indices into a 16, 24 or 32-bit color map.
+fn decode_tga(bytes: &[u8]) -> Result<Image, String> {
+    if bytes.len() < 18 {
+        return Err("truncated TGA header".to_string());
+    }
+    let u16_at = |pos: usize| u16::from_le_bytes([bytes[pos], bytes[pos + 1]]) as usize;
+    let (map_type, image_type, map_bits) = (bytes[1], bytes[2], bytes[7]);
+    let (map_first, map_length) = (u16_at(3), u16_at(5));
+    let (width, height, bits, descriptor) = (u16_at(12), u16_at(14), bytes[16], bytes[17]);
+    match (image_type & !8, bits, map_bits) {
+        (1, 8, 15 | 16 | 24 | 32) if map_type == 1 => {}
+        (2, 15 | 16 | 24 | 32, _) | (3, 8, _) => {}
+        _ => {
+            return Err(format!(
+                "unsupported TGA image type {} with {} bits per pixel",
+                image_type, bits
+            ))
+        }
+    }
+    let mut pos = 18 + bytes[0] as usize;
+    let mut palette = Vec::new();
+    if map_type == 1 {
+        let entry_size = (map_bits as usize).div_ceil(8);
+        let map = bytes
+            .get(pos..pos + map_length * entry_size)
+            .ok_or_else(|| "truncated TGA color map".to_string())?;
+        palette = map.chunks_exact(entry_size).map(tga_color).collect();
+        pos += map.len();
+    }
+
+    let pixel_size = (bits as usize).div_ceil(8);
+    check_decoded_size("TGA", width, height)?;
+    let count = width * height;
+    //  Every pixel is stored, or every run of up to 128 pixels takes a packet header and a pixel.
+    let min_data = if image_type & 8 == 0 {
+        count * pixel_size
+    } else {
+        count.div_ceil(128) * (1 + pixel_size)
+    };
+    if bytes.len().saturating_sub(pos) < min_data {
+        return Err("truncated TGA image data".to_string());
+    }
+    let read_pixel = |pos: &mut usize| -> Result<[u8; 4], String> {
+        let pixel = bytes
+            .get(*pos..*pos + pixel_size)
+            .ok_or_else(|| "truncated TGA image data".to_string())?;
+        *pos += pixel_size;
+        match image_type & !8 {
+            1 => (pixel[0] as usize)
+                .checked_sub(map_first)
+                .and_then(|index| palette.get(index))
+                .copied()
+                .ok_or_else(|| "TGA color map index out of range".to_string()),
+            _ => Ok(tga_color(pixel)),
+        }
+    };
+    let mut pixels = Vec::with_capacity(count);
+    while pixels.len() < count {
+        if image_type & 8 == 0 {
+            pixels.push(read_pixel(&mut pos)?);
+            continue;
+        }
+        let header = *bytes
+            .get(pos)
+            .ok_or_else(|| "truncated TGA image data".to_string())?;
+        pos += 1;
+        let run = (header & 127) as usize + 1;
+        if header & 128 != 0 {
+            let color = read_pixel(&mut pos)?;
+            pixels.resize(pixels.len() + run, color);
+        } else {
+            for _ in 0..run {
+                pixels.push(read_pixel(&mut pos)?);
+            }
+        }
+    }
+
+    //  Rows are stored bottom to top unless bit 5 of the descriptor is set.
+    let mut image = Image::new(width as u32, height as u32);
+    for (i, &color) in pixels[..count].iter().enumerate() {
+        let (mut x, mut y) = (i % width, i / width);
+        if descriptor & 16 != 0 {
+            x = width - 1 - x;
+        }
+        if descriptor & 32 == 0 {
+            y = height - 1 - y;
+        }
+        image.set_pixel(x as u32, y as u32, color);
+    }
+    Ok(image)
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+//  Radiance RGBE files in the standard -Y +X orientation, flat or with run-length encoded
+//  scanlines.
+fn decode_hdr(bytes: &[u8]) -> Result<Image, String> {
+    let mut pos = 0;
+    let next_line = |pos: &mut usize| -> Result<&[u8], String> {
+        let length = bytes[*pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| "truncated HDR header".to_string())?;
+        let line = &bytes[*pos..*pos + length];
+        *pos += length + 1;
+        Ok(line)
+    };
+    loop {
+        let line = next_line(&mut pos)?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+            return Err("only RGBE HDR files are supported".to_string());
+        }
+    }
+    let resolution = String::from_utf8_lossy(next_line(&mut pos)?).into_owned();
+    let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (
+            width.parse::<usize>().map_err(|e| e.to_string())?,
+            height.parse::<usize>().map_err(|e| e.to_string())?,
+        ),
+        _ => return Err(format!("unsupported HDR orientation `{}`", resolution)),
+    };
+
+    check_decoded_size("HDR", width, height)?;
+    let truncated = || "truncated HDR image data".to_string();
+    //  A flat scanline takes 4 bytes per pixel, a run-length encoded one at least a header and a
+    //  two-byte run per 127 pixels of each channel.
+    let min_scanline = if (8..0x8000).contains(&width) {
+        4 + width.div_ceil(127) * 8
+    } else {
+        width * 4
+    };
+    if bytes.len() - pos < min_scanline * height {
+        return Err(truncated());
+    }
+    let mut image = Image::new(width as u32, height as u32);
+    let mut scanline = vec![0u8; width * 4];
+    for y in 0..height {
+        let rle_header = [2, 2, (width >> 8) as u8, width as u8];
+        if (8..0x8000).contains(&width) && bytes.get(pos..pos + 4) == Some(&rle_header[..]) {
+            //  Each channel of the scanline is stored separately, as runs and literal spans.
+            pos += 4;
+            for channel in scanline.chunks_exact_mut(width) {
+                let mut x = 0;
+                while x < width {
+                    let count = *bytes.get(pos).ok_or_else(truncated)? as usize;
+                    pos += 1;
+                    if count > 128 {
+                        let value = *bytes.get(pos).ok_or_else(truncated)?;
+                        pos += 1;
+                        channel
+                            .get_mut(x..x + count - 128)
+                            .ok_or_else(|| "invalid HDR run".to_string())?
+                            .fill(value);
+                        x += count - 128;
+                    } else {
+                        let span = bytes.get(pos..pos + count).ok_or_else(truncated)?;
+                        channel
+                            .get_mut(x..x + count)
+                            .filter(|_| count > 0)
+                            .ok_or_else(|| "invalid HDR run".to_string())?
+                            .copy_from_slice(span);
+                        pos += count;
+                        x += count;
+                    }
+                }
+            }
+        } else {
+            let flat = bytes.get(pos..pos + width * 4).ok_or_else(truncated)?;
+            pos += width * 4;
+            for (x, rgbe) in flat.chunks_exact(4).enumerate() {
+                for (channel, &value) in rgbe.iter().enumerate() {
+                    scanline[channel * width + x] = value;
+                }
+            }
+        }
+        for x in 0..width {
+            let exponent = scanline[3 * width + x];
+            let scale = if exponent == 0 {
+                0.0
+            } else {
+                2f32.powi(exponent as i32 - 136)
+            };
+            let channel = |c: usize| linear_to_srgb(scanline[c * width + x] as f32 * scale);
+            image.set_pixel(
+                x as u32,
+                y as u32,
+                [channel(0), channel(1), channel(2), 255],
+            );
+        }
+    }
+    Ok(image)
+}
+
+//  Binary RGB; alpha is dropped.
+fn encode_ppm(image: &Image) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+    for pixel in image.pixels.chunks_exact(4) {
+        out.extend_from_slice(&pixel[..3]);
+    }
+    out
+}
+
+fn exr_attribute(out: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(attribute_type.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+//  Uncompressed scanline OpenEXR with 32-bit float channels. Values are the stored 8-bit values
+//  scaled to 0..1, with no transfer function applied.
+fn encode_exr(image: &Image) -> Vec<u8> {
+    const CHANNELS: [(&str, usize); 4] = [("A", 3), ("B", 2), ("G", 1), ("R", 0)];
+    let (width, height) = (image.width as usize, image.height as usize);
+
+    let mut out = vec![0x76, 0x2F, 0x31, 0x01, 2, 0, 0, 0];
+    let mut channels = Vec::new();
+    for (name, _) in CHANNELS.iter() {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&2i32.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    let mut window = Vec::with_capacity(16);
+    for v in [0, 0, width as i32 - 1, height as i32 - 1].iter() {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    exr_attribute(&mut out, "channels", "chlist", &channels);
+    exr_attribute(&mut out, "compression", "compression", &[0]);
+    exr_attribute(&mut out, "dataWindow", "box2i", &window);
+    exr_attribute(&mut out, "displayWindow", "box2i", &window);
+    exr_attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    exr_attribute(&mut out, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    exr_attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(
+        &mut out,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    out.push(0);
+
+    let line_size = width * CHANNELS.len() * 4;
+    let first_line = (out.len() + height * 8) as u64;
+    for y in 0..height as u64 {
+        out.extend_from_slice(&(first_line + y * (8 + line_size as u64)).to_le_bytes());
+    }
+    for y in 0..height {
+        out.extend_from_slice(&(y as i32).to_le_bytes());
+        out.extend_from_slice(&(line_size as i32).to_le_bytes());
+        let row = &image.pixels[y * image.row_pitch()..(y + 1) * image.row_pitch()];
+        for (_, component) in CHANNELS.iter() {
+            for pixel in row.chunks_exact(4) {
+                let value = pixel[*component] as f32 / 255.0;
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn test_encode() {
+    let mut image = Image::new(3, 2);
+    image.fill([10, 20, 30, 255]);
+    image.set_pixel(2, 1, [255, 0, 0, 128]);
+
+    let ppm = image.encode(ImageFormat::Ppm);
+    assert!(ppm.starts_with(b"P6\n3 2\n255\n"));
+    assert_eq!(ppm[ppm.len() - 3..], [255, 0, 0]);
+
+    let png = image.encode(ImageFormat::Png);
+    assert_eq!(png[..8], *PNG_SIGNATURE);
+    assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    assert_eq!(png[png.len() - 12..png.len() - 8], [0, 0, 0, 0]);
+
+    assert_eq!(decode_png(&png), Ok(image.clone()));
+
+    let exr = image.encode(ImageFormat::Exr);
+    assert_eq!(exr[..4], [0x76, 0x2F, 0x31, 0x01]);
+    assert_eq!(
+        ImageFormat::from_path(Path::new("frame.PNG")),
+        Some(ImageFormat::Png)
+    );
+    assert_eq!(ImageFormat::from_path(Path::new("frame.bmp")), None);
+}
+
+#[test]
+fn test_decode() {
+    //  3x2 RLE TGA, stored bottom row first: a run of red, then blue, green and white.
+    let mut tga = vec![0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 2, 0, 24, 0];
+    tga.extend_from_slice(&[0x82, 0, 0, 255, 0x02, 255, 0, 0, 0, 255, 0, 255, 255, 255]);
+    let image = decode_tga(&tga).unwrap();
+    assert_eq!(image.pixel(2, 1), [255, 0, 0, 255]);
+    assert_eq!(image.pixel(0, 0), [0, 0, 255, 255]);
+    assert_eq!(image.pixel(2, 0), [255, 255, 255, 255]);
+
+    //  8x1 RLE HDR: red as a run at 1.0, green as literals from 0 to 0.875, blue as a run of 0.
+    let mut hdr = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+    hdr.extend_from_slice(&[2, 2, 0, 8, 136, 128, 8, 0, 16, 32, 48, 64, 80, 96, 112]);
+    hdr.extend_from_slice(&[136, 0, 136, 129]);
+    let image = decode_hdr(&hdr).unwrap();
+    assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+    assert_eq!(image.pixel(7, 0), [255, linear_to_srgb(0.875), 0, 255]);
+
+    //  Sizes beyond the limit, or beyond what the remaining data can hold, fail before allocating.
+    tga[12..16].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+    assert!(decode_tga(&tga).unwrap_err().contains("size"));
+    tga[12..16].copy_from_slice(&[0, 0x40, 0, 0x40]);
+    assert!(decode_tga(&tga).unwrap_err().contains("truncated"));
+    assert!(decode_hdr(b"#?RADIANCE\n\n-Y 100000 +X 100000\n")
+        .unwrap_err()
+        .contains("size"));
+    assert!(decode_hdr(&hdr[..hdr.len() - 1])
+        .unwrap_err()
+        .contains("truncated"));
+    assert!(decode_hdr(b"#?RADIANCE\n\n-Y 16384 +X 16384\n")
+        .unwrap_err()
+        .contains("truncated"));
+
+    let mut image = Image::new(2, 1);
+    image.set_pixel(1, 0, [255, 255, 255, 255]);
+    let resized = image.resized(4, 1);
+    assert_eq!(
+        (0..4).map(|x| resized.pixel(x, 0)[0]).collect::<Vec<_>>(),
+        [0, 64, 191, 255]
+    );
+    assert_eq!(resized.resized(1, 1).pixel(0, 0), [128, 128, 128, 128]);
+}
@@ -0,0 +1,515 @@
+//  Baseline JPEG decoder (8-bit sequential Huffman, grayscale or YCbCr with any chroma
+//  subsampling, restart intervals), enough to load photos and textures without a dependency.
+//  Progressive, lossless, arithmetic-coded and CMYK files are rejected.
+
+use crate::image::Image;
+
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+//  Canonical Huffman table, decoded one bit at a time (JPEG F.2.2.3).
+#[derive(Clone, Default)]
+struct Huffman {
+    max_code: [i32; 17],
+    value_offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], values: &[u8]) -> Huffman {
+        let mut table = Huffman {
+            max_code: [-1; 17],
+            value_offset: [0; 17],
+            values: values.to_vec(),
+        };
+        let (mut code, mut index) = (0, 0);
+        for length in 1..=16 {
+            let count = counts[length - 1] as i32;
+            table.value_offset[length] = index - code;
+            if count > 0 {
+                code += count;
+                index += count;
+                table.max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        table
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u8, String> {
+        let mut code = 0;
+        for length in 1..=16 {
+            code = (code << 1) | reader.bit() as i32;
+            if code <= self.max_code[length] {
+                return self
+                    .values
+                    .get((code + self.value_offset[length]) as usize)
+                    .copied()
+                    .ok_or_else(|| "invalid Huffman table".to_string());
+            }
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+//  Reads entropy-coded data, removing stuffed zero bytes. At a marker it returns zero bits without
+//  advancing, so a truncated scan decodes as gray instead of failing.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    byte: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn next_byte(&mut self) -> u32 {
+        match self.data.get(self.pos..self.pos + 2) {
+            Some([0xFF, 0x00]) => {
+                self.pos += 2;
+                0xFF
+            }
+            Some([0xFF, _]) => 0,
+            _ => match self.data.get(self.pos) {
+                Some(&byte) => {
+                    self.pos += 1;
+                    byte as u32
+                }
+                None => 0,
+            },
+        }
+    }
+
+    fn bit(&mut self) -> u32 {
+        if self.count == 0 {
+            self.byte = self.next_byte();
+            self.count = 8;
+        }
+        self.count -= 1;
+        (self.byte >> self.count) & 1
+    }
+
+    fn bits(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |value, _| (value << 1) | self.bit())
+    }
+
+    //  A `size`-bit value in JPEG's sign representation (F.2.2.1).
+    fn receive_extend(&mut self, size: u8) -> i32 {
+        if size == 0 {
+            return 0;
+        }
+        let value = self.bits(size as u32) as i32;
+        if value < 1 << (size - 1) {
+            value - (1 << size) + 1
+        } else {
+            value
+        }
+    }
+
+    fn restart(&mut self) -> Result<(), String> {
+        self.count = 0;
+        match self.data.get(self.pos..self.pos + 2) {
+            Some([0xFF, 0xD0..=0xD7]) => {
+                self.pos += 2;
+                Ok(())
+            }
+            _ => Err("missing restart marker".to_string()),
+        }
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quantization: usize,
+    dc_table: usize,
+    ac_table: usize,
+    prediction: i32,
+    stride: usize,
+    pixels: Vec<u8>,
+}
+
+struct Tables {
+    quantization: [[u16; 64]; 4],
+    dc_tables: [Huffman; 4],
+    ac_tables: [Huffman; 4],
+    cosines: [[f32; 8]; 8],
+}
+
+struct Decoder {
+    tables: Tables,
+    components: Vec<Component>,
+    width: usize,
+    height: usize,
+    mcus_x: usize,
+    mcus_y: usize,
+    restart_interval: usize,
+}
+
+fn be16(bytes: &[u8], pos: usize) -> Result<usize, String> {
+    bytes
+        .get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+        .ok_or_else(|| "truncated JPEG file".to_string())
+}
+
+impl Tables {
+    //  Inverse DCT of a dequantized block in natural order into 8x8 pixels at `(x, y)`.
+    fn idct(&self, coefficients: &[f32; 64], component: &mut Component, x: usize, y: usize) {
+        let mut rows = [0.0; 64];
+        for v in 0..8 {
+            for px in 0..8 {
+                rows[v * 8 + px] = (0..8)
+                    .map(|u| self.cosines[px][u] * coefficients[v * 8 + u])
+                    .sum();
+            }
+        }
+        for py in 0..8 {
+            let row = (y + py) * component.stride + x;
+            for px in 0..8 {
+                let value: f32 = (0..8).map(|v| self.cosines[py][v] * rows[v * 8 + px]).sum();
+                component.pixels[row + px] = (value + 128.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    fn decode_block(
+        &self,
+        reader: &mut BitReader,
+        component: &mut Component,
+        x: usize,
+        y: usize,
+    ) -> Result<(), String> {
+        let quantization = &self.quantization[component.quantization];
+        let mut coefficients = [0.0; 64];
+        let size = self.dc_tables[component.dc_table].decode(reader)?;
+        component.prediction += reader.receive_extend(size);
+        coefficients[0] = (component.prediction * quantization[0] as i32) as f32;
+        let ac = &self.ac_tables[component.ac_table];
+        let mut k = 1;
+        while k < 64 {
+            let symbol = ac.decode(reader)?;
+            let (run, size) = ((symbol >> 4) as usize, symbol & 15);
+            if size == 0 {
+                if run != 15 {
+                    break;
+                }
+                k += 16;
+                continue;
+            }
+            k += run;
+            if k > 63 {
+                return Err("invalid JPEG coefficient".to_string());
+            }
+            coefficients[ZIGZAG[k]] = (reader.receive_extend(size) * quantization[k] as i32) as f32;
+            k += 1;
+        }
+        self.idct(&coefficients, component, x, y);
+        Ok(())
+    }
+}
+
+impl Decoder {
+    fn read_quantization(&mut self, mut data: &[u8]) -> Result<(), String> {
+        while let Some(&info) = data.first() {
+            let (precision, id) = (info >> 4, (info & 15) as usize);
+            let size = if precision == 0 { 64 } else { 128 };
+            if id > 3 || data.len() < 1 + size {
+                return Err("invalid quantization table".to_string());
+            }
+            for k in 0..64 {
+                self.tables.quantization[id][k] = if precision == 0 {
+                    data[1 + k] as u16
+                } else {
+                    u16::from_be_bytes([data[1 + k * 2], data[2 + k * 2]])
+                };
+            }
+            data = &data[1 + size..];
+        }
+        Ok(())
+    }
+
+    fn read_huffman(&mut self, mut data: &[u8]) -> Result<(), String> {
+        while data.len() >= 17 {
+            let (class, id) = (data[0] >> 4, (data[0] & 15) as usize);
+            let counts = &data[1..17];
+            let total = counts.iter().map(|&c| c as usize).sum::<usize>();
+            if id > 3 || class > 1 || data.len() < 17 + total {
+                return Err("invalid Huffman table".to_string());
+            }
+            let table = Huffman::new(counts, &data[17..17 + total]);
+            if class == 0 {
+                self.tables.dc_tables[id] = table;
+            } else {
+                self.tables.ac_tables[id] = table;
+            }
+            data = &data[17 + total..];
+        }
+        Ok(())
+    }
+
+    fn read_frame(&mut self, data: &[u8]) -> Result<(), String> {
+        if data.len() < 6 || data[0] != 8 {
+            return Err("only 8-bit JPEG files are supported".to_string());
+        }
+        self.height = be16(data, 1)?;
+        self.width = be16(data, 3)?;
+        let count = data[5] as usize;
+        if self.width == 0 || self.height == 0 {
+            return Err("invalid JPEG image size".to_string());
+        }
+        if count != 1 && count != 3 {
+            return Err(format!("unsupported JPEG component count {}", count));
+        }
+        if data.len() < 6 + count * 3 {
+            return Err("truncated JPEG frame header".to_string());
+        }
+        for c in data[6..6 + count * 3].chunks_exact(3) {
+            let (h, v) = ((c[1] >> 4) as usize, (c[1] & 15) as usize);
+            if !(1..=4).contains(&h) || !(1..=4).contains(&v) || c[2] > 3 {
+                return Err("invalid JPEG component".to_string());
+            }
+            self.components.push(Component {
+                id: c[0],
+                h,
+                v,
+                quantization: c[2] as usize,
+                dc_table: 0,
+                ac_table: 0,
+                prediction: 0,
+                stride: 0,
+                pixels: Vec::new(),
+            });
+        }
+        let h_max = self.components.iter().map(|c| c.h).max().unwrap();
+        let v_max = self.components.iter().map(|c| c.v).max().unwrap();
+        self.mcus_x = self.width.div_ceil(8 * h_max);
+        self.mcus_y = self.height.div_ceil(8 * v_max);
+        for c in &mut self.components {
+            c.stride = self.mcus_x * c.h * 8;
+            c.pixels = vec![128; c.stride * self.mcus_y * c.v * 8];
+        }
+        Ok(())
+    }
+
+    //  Decodes the scan starting at `pos` and returns where its entropy-coded data ends.
+    fn read_scan(&mut self, bytes: &[u8], header: &[u8], pos: usize) -> Result<usize, String> {
+        let count = *header.first().ok_or("truncated JPEG scan header")? as usize;
+        if count == 0 || header.len() < 1 + count * 2 + 3 {
+            return Err("invalid JPEG scan header".to_string());
+        }
+        let mut scan = Vec::new();
+        for c in header[1..1 + count * 2].chunks_exact(2) {
+            let index = self
+                .components
+                .iter()
+                .position(|component| component.id == c[0])
+                .ok_or("unknown component in JPEG scan")?;
+            let component = &mut self.components[index];
+            component.dc_table = (c[1] >> 4) as usize & 3;
+            component.ac_table = (c[1] & 15) as usize & 3;
+            component.prediction = 0;
+            scan.push(index);
+        }
+
+        //  A scan with one component codes its blocks in raster order, covering only the blocks
+        //  that contain image pixels; otherwise each MCU holds h x v blocks of every component.
+        let h_max = self.components.iter().map(|c| c.h).max().unwrap();
+        let v_max = self.components.iter().map(|c| c.v).max().unwrap();
+        let mut units = Vec::new();
+        if let [index] = scan[..] {
+            let c = &self.components[index];
+            let blocks_x = (self.width * c.h).div_ceil(h_max).div_ceil(8);
+            let blocks_y = (self.height * c.v).div_ceil(v_max).div_ceil(8);
+            for y in 0..blocks_y {
+                for x in 0..blocks_x {
+                    units.push(vec![(index, x * 8, y * 8)]);
+                }
+            }
+        } else {
+            for mcu_y in 0..self.mcus_y {
+                for mcu_x in 0..self.mcus_x {
+                    let mut unit = Vec::new();
+                    for &index in &scan {
+                        let c = &self.components[index];
+                        for v in 0..c.v {
+                            for h in 0..c.h {
+                                unit.push((index, (mcu_x * c.h + h) * 8, (mcu_y * c.v + v) * 8));
+                            }
+                        }
+                    }
+                    units.push(unit);
+                }
+            }
+        }
+
+        let mut reader = BitReader {
+            data: bytes,
+            pos,
+            byte: 0,
+            count: 0,
+        };
+        let mut until_restart = self.restart_interval;
+        for (i, unit) in units.iter().enumerate() {
+            if self.restart_interval > 0 {
+                if until_restart == 0 {
+                    reader.restart()?;
+                    for &index in &scan {
+                        self.components[index].prediction = 0;
+                    }
+                    until_restart = self.restart_interval;
+                }
+                until_restart -= 1;
+            }
+            for &(index, x, y) in unit {
+                self.tables
+                    .decode_block(&mut reader, &mut self.components[index], x, y)
+                    .map_err(|e| format!("{} in MCU {}", e, i))?;
+            }
+        }
+        Ok(reader.pos)
+    }
+
+    fn to_image(&self) -> Image {
+        let mut image = Image::new(self.width as u32, self.height as u32);
+        let h_max = self.components.iter().map(|c| c.h).max().unwrap();
+        let v_max = self.components.iter().map(|c| c.v).max().unwrap();
+        let sample = |c: &Component, x: usize, y: usize| {
+            c.pixels[y * c.v / v_max * c.stride + x * c.h / h_max] as f32
+        };
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = match &self.components[..] {
+                    [gray] => {
+                        let l = sample(gray, x, y) as u8;
+                        [l, l, l, 255]
+                    }
+                    [luma, cb, cr] => {
+                        let l = sample(luma, x, y);
+                        let (cb, cr) = (sample(cb, x, y) - 128.0, sample(cr, x, y) - 128.0);
+                        let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+                        [
+                            channel(l + 1.402 * cr),
+                            channel(l - 0.344_136 * cb - 0.714_136 * cr),
+                            channel(l + 1.772 * cb),
+                            255,
+                        ]
+                    }
+                    _ => unreachable!(),
+                };
+                image.set_pixel(x as u32, y as u32, color);
+            }
+        }
+        image
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err("not a JPEG file".to_string());
+    }
+    let mut cosines = [[0.0; 8]; 8];
+    for (x, row) in cosines.iter_mut().enumerate() {
+        for (u, value) in row.iter_mut().enumerate() {
+            let scale = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            *value =
+                scale / 2.0 * ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos();
+        }
+    }
+    let mut decoder = Decoder {
+        tables: Tables {
+            quantization: [[1; 64]; 4],
+            dc_tables: Default::default(),
+            ac_tables: Default::default(),
+            cosines,
+        },
+        components: Vec::new(),
+        width: 0,
+        height: 0,
+        mcus_x: 0,
+        mcus_y: 0,
+        restart_interval: 0,
+    };
+    let mut scanned = false;
+    let mut pos = 2;
+    while pos + 1 < bytes.len() {
+        if bytes[pos] != 0xFF {
+            pos += 1;
+            continue;
+        }
+        let marker = bytes[pos + 1];
+        pos += 2;
+        match marker {
+            //  Fill bytes, stuffed zeros and restart markers left over from a scan.
+            0xFF => pos -= 1,
+            0x00 | 0x01 | 0xD0..=0xD8 => {}
+            0xD9 => break,
+            _ => {
+                let length = be16(bytes, pos)?;
+                let segment = bytes
+                    .get(pos + 2..pos + length)
+                    .ok_or("truncated JPEG segment")?;
+                pos += length;
+                match marker {
+                    0xDB => decoder.read_quantization(segment)?,
+                    0xC4 => decoder.read_huffman(segment)?,
+                    0xC0 | 0xC1 if decoder.components.is_empty() => decoder.read_frame(segment)?,
+                    0xC2 | 0xC6 | 0xCA | 0xCE => {
+                        return Err("progressive JPEG files are not supported".to_string())
+                    }
+                    0xC3 | 0xC5 | 0xC7 | 0xC9 | 0xCB | 0xCD | 0xCF => {
+                        return Err("unsupported JPEG coding".to_string())
+                    }
+                    0xDD => decoder.restart_interval = be16(segment, 0)?,
+                    0xDA => {
+                        if decoder.components.is_empty() {
+                            return Err("JPEG scan before the frame header".to_string());
+                        }
+                        pos = decoder.read_scan(bytes, segment, pos)?;
+                        scanned = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    if !scanned {
+        return Err("JPEG file without image data".to_string());
+    }
+    Ok(decoder.to_image())
+}
+
+#[test]
+fn test_decode_jpeg() {
+    //  A 37x23 gradient (r = 7x, g = 11y, b = 200 - 3x) encoded at quality 90 with 4:2:0 chroma,
+    //  optimized Huffman tables and a restart interval of two MCUs.
+    let image = decode(include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/images/gradient.jpg"
+    )))
+    .unwrap();
+    assert_eq!((image.width, image.height), (37, 23));
+    let mut total_error = 0;
+    for y in 0..23 {
+        for x in 0..37 {
+            let expected = [x * 7, y * 11, 200 - x * 3, 255];
+            let pixel = image.pixel(x, y);
+            for (&actual, &expected) in pixel.iter().zip(&expected) {
+                let error = (actual as i32 - expected as i32).abs();
+                assert!(error <= 24, "{:?} at {}, {}", pixel, x, y);
+                total_error += error;
+            }
+        }
+    }
+    //  Mostly from the subsampled chroma along the horizontal gradients.
+    assert!(total_error < 37 * 23 * 3 * 4, "{}", total_error);
+
+    assert!(decode(b"\xFF\xD8\xFF\xC2\x00\x02").is_err());
+}
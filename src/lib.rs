@@ -1,27 +1,77 @@
+//  The crate is named after the library Unity loads, and the plugin state lives in `static mut`s
+//  that only Unity's callbacks touch.
+#![allow(non_snake_case, static_mut_refs)]
+
+mod capture;
+mod debug_draw;
+mod image;
+mod inflate;
+mod jpeg;
+mod logger;
+mod math;
+mod recorder;
 mod render_api;
+mod render_api_software;
+pub mod replay;
+mod shader_assets;
+#[cfg(any(backend_d3d11, test))]
+mod shader_reflection;
+mod shader_watcher;
+mod shared_memory;
+pub mod texture_feed;
+mod texture_loader;
+mod timing;
+mod validation;
+mod video;
 
-#[cfg(target_os = "windows")]
+#[cfg(backend_d3d11)]
 mod render_api_d3d11;
 
-#[cfg(target_os = "windows")]
+#[cfg(backend_d3d11)]
 mod win_util;
 
-#[cfg(target_feature = "vulkan")]
+#[cfg(backend_vulkan)]
 mod render_api_vulkan;
 
-#[cfg(target_feature = "vulkan")]
+#[cfg(backend_vulkan)]
 mod vulkan_api;
 
+#[cfg(backend_vulkan)]
+mod vulkan_trace;
+
+#[cfg(test)]
+mod fake_unity;
+
+#[cfg(all(test, backend_vulkan))]
+mod fake_vulkan;
+
+#[cfg(test)]
+mod golden;
+
 static mut GRAPHICS: Option<unity_native_plugin::graphics::UnityGraphics> = None;
 
 unity_native_plugin::unity_native_plugin_entry_point! {
     fn unity_plugin_load(interfaces: &unity_native_plugin::interface::UnityInterfaces) {
+        logger::initialize(interfaces);
+        if std::env::var_os(shader_watcher::WATCH_ENV).is_some() {
+            shader_watcher::start();
+        }
+        if let Some(path) = std::env::var_os(recorder::RECORD_PATH_ENV) {
+            if let Err(e) = recorder::start(std::path::Path::new(&path)) {
+                logger::error(&format!("failed to start recording: {}", e));
+            }
+        }
+        if let Some(path) = std::env::var_os(capture::CAPTURE_PATH_ENV) {
+            if let Err(e) = capture::request(std::path::Path::new(&path)) {
+                logger::error(&e);
+            }
+        }
         unsafe {
             GRAPHICS = interfaces.interface::<unity_native_plugin::graphics::UnityGraphics>();
             if let Some(g) = &GRAPHICS {
                 g.register_device_event_callback(Some(on_grapihcs_device_event));
 
-                #[cfg(target_feature = "vulkan")]
+                #[cfg(backend_vulkan)]
                 if g.renderer() == unity_native_plugin::graphics::GfxRenderer::Vulkan {
                     render_api_vulkan::on_plugin_load(interfaces);
                 }
@@ -35,17 +85,73 @@ unity_native_plugin::unity_native_plugin_entry_point! {
                 g.unregister_device_event_callback(Some(on_grapihcs_device_event));
             }
         }
+        recorder::stop();
+        capture::cancel();
+        capture::finish();
+        shader_watcher::stop();
+        logger::finalize();
     }
 }
 
-static mut TIME: f32 = 0.0;
-
 #[no_mangle]
 #[allow(non_snake_case)]
 pub extern "system" fn SetTimeFromUnity(t: f32) {
-    unsafe {
-        TIME = t;
-    }
+    recorder::record(|| recorder::Call::SetTimeFromUnity(t));
+    timing::set_source_time(t as f64);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetTimeFromUnityDouble(t: f64) {
+    recorder::record(|| recorder::Call::SetTimeFromUnityDouble(t));
+    timing::set_source_time(t);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetTimePaused(paused: bool) {
+    recorder::record(|| recorder::Call::SetTimePaused(paused));
+    timing::set_paused(paused);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetTimeScale(scale: f64) {
+    recorder::record(|| recorder::Call::SetTimeScale(scale));
+    timing::set_scale(scale);
+}
+
+//  A step of 0 or less goes back to following the time from Unity.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetFixedTimeStep(step: f64) {
+    recorder::record(|| recorder::Call::SetFixedTimeStep(step));
+    timing::set_fixed_step(Some(step));
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn ResetTime() {
+    recorder::record(|| recorder::Call::ResetTime);
+    timing::reset();
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetTime() -> f64 {
+    timing::current().time
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetDeltaTime() -> f64 {
+    timing::current().delta_time
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetFrameIndex() -> u64 {
+    timing::current().frame_index
 }
 
 static mut TEXTURE_HANDLE: render_api::Handle = std::ptr::null_mut();
@@ -55,6 +161,11 @@ static mut TEXTURE_HEIGHT: i32 = 0;
 #[no_mangle]
 #[allow(non_snake_case)]
 pub extern "system" fn SetTextureFromUnity(handle: render_api::Handle, w: i32, h: i32) {
+    recorder::record(|| recorder::Call::SetTexture {
+        handle: handle as u64,
+        width: w,
+        height: h,
+    });
     unsafe {
         TEXTURE_HANDLE = handle;
         TEXTURE_WIDTTH = w;
@@ -75,6 +186,21 @@ struct MeshVertex {
 
 static mut VERTEX_SOURCE: Vec<MeshVertex> = Vec::<MeshVertex>::new();
 
+//  `source` holds `count` elements of `components` values each.
+fn copy_source<T: Copy>(source: *const T, count: i32, components: usize) -> Vec<T> {
+    let len = if count < 0 {
+        None
+    } else {
+        (count as usize).checked_mul(components)
+    };
+    match len {
+        Some(len) if len > 0 && !source.is_null() => {
+            unsafe { std::slice::from_raw_parts(source, len) }.to_vec()
+        }
+        _ => Vec::new(),
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub extern "system" fn SetMeshBuffersFromUnity(
@@ -84,6 +210,17 @@ pub extern "system" fn SetMeshBuffersFromUnity(
     source_normals: *const f32,
     source_uv: *const f32,
 ) {
+    if vertex_count < 0 {
+        logger::error(&format!("invalid vertex count {}", vertex_count));
+        return;
+    }
+    recorder::record(|| recorder::Call::SetMeshBuffers {
+        handle: handle as u64,
+        vertex_count,
+        vertices: copy_source(source_vertices, vertex_count, 3),
+        normals: copy_source(source_normals, vertex_count, 3),
+        uv: copy_source(source_uv, vertex_count, 2),
+    });
     unsafe {
         VERTEX_BUFFER_HANDLE = handle;
         VERTEX_BUFFER_VERTEX_COUNT = vertex_count;
@@ -116,6 +253,60 @@ pub extern "system" fn SetMeshBuffersFromUnity(
     }
 }
 
+//  Values of SetMeshIndexBufferFromUnity's `index_format`.
+pub use render_api::IndexFormat;
+
+static mut INDEX_BUFFER_HANDLE: render_api::Handle = std::ptr::null_mut();
+static mut INDEX_BUFFER_INDEX_COUNT: i32 = 0;
+static mut INDEX_BUFFER_FORMAT: render_api::IndexFormat = render_api::IndexFormat::UInt16;
+static mut INDEX_SOURCE: Vec<u32> = Vec::<u32>::new();
+
+//  `source` holds `count` 16 or 32-bit indices, widened to u32.
+fn read_indices(
+    source: *const std::ffi::c_void,
+    count: i32,
+    format: render_api::IndexFormat,
+) -> Vec<u32> {
+    match format {
+        render_api::IndexFormat::UInt16 => copy_source(source as *const u16, count, 1)
+            .into_iter()
+            .map(u32::from)
+            .collect(),
+        render_api::IndexFormat::UInt32 => copy_source(source as *const u32, count, 1),
+    }
+}
+
+//  `index_format` is an IndexFormat value and `source_indices` holds indices of that size.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetMeshIndexBufferFromUnity(
+    handle: render_api::Handle,
+    index_count: i32,
+    index_format: i32,
+    source_indices: *const std::ffi::c_void,
+) {
+    let format = match render_api::index_format_from_i32(index_format) {
+        Some(format) => format,
+        None => {
+            logger::error(&format!("invalid index format {}", index_format));
+            return;
+        }
+    };
+    let indices = read_indices(source_indices, index_count, format);
+    recorder::record(|| recorder::Call::SetMeshIndexBuffer {
+        handle: handle as u64,
+        index_count,
+        index_format,
+        indices: indices.clone(),
+    });
+    unsafe {
+        INDEX_BUFFER_HANDLE = handle;
+        INDEX_BUFFER_INDEX_COUNT = index_count;
+        INDEX_BUFFER_FORMAT = format;
+        INDEX_SOURCE = indices;
+    }
+}
+
 static mut CURRENT_API: Option<Box<dyn render_api::RenderAPI>> = None;
 static mut DEVICE_TYPE: unity_native_plugin::graphics::GfxRenderer =
     unity_native_plugin::graphics::GfxRenderer::Null;
@@ -127,6 +318,10 @@ extern "system" fn on_grapihcs_device_event(
         unsafe {
             DEVICE_TYPE = GRAPHICS.as_ref().unwrap().renderer();
             CURRENT_API = render_api::create_render_api(DEVICE_TYPE);
+            if CURRENT_API.is_none() {
+                let renderer = DEVICE_TYPE as i32;
+                logger::warning(&format!("no render API for renderer {}", renderer));
+            }
         }
     }
 
@@ -147,61 +342,158 @@ extern "system" fn on_grapihcs_device_event(
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetActiveRenderer() -> i32 {
+    unsafe { DEVICE_TYPE as i32 }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn IsRenderApiAvailable() -> bool {
+    unsafe { CURRENT_API.is_some() }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetUsesReverseZ() -> bool {
+    unsafe { CURRENT_API.as_ref() }
+        .map(|api| api.get_uses_reverse_z())
+        .unwrap_or(false)
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetShaderDirectory(path: *const std::os::raw::c_char) {
+    let path = path_from_c_str(path);
+    recorder::record(|| {
+        recorder::Call::SetShaderDirectory(path.as_ref().map(|p| p.display().to_string()))
+    });
+    shader_assets::set_shader_directory(path);
+}
+
+static mut CAMERA_MATRICES: Option<(math::Mat4, math::Mat4)> = None;
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetCameraMatrices(view: *const f32, projection: *const f32) {
+    unsafe {
+        CAMERA_MATRICES = if view.is_null() || projection.is_null() {
+            None
+        } else {
+            Some((
+                math::Mat4::from_cols_array(*(view as *const [f32; 16])),
+                math::Mat4::from_cols_array(*(projection as *const [f32; 16])),
+            ))
+        };
+    }
+    recorder::record(|| {
+        let matrices = unsafe { CAMERA_MATRICES };
+        recorder::Call::SetCameraMatrices(
+            matrices.map(|(view, projection)| (view.to_cols_array(), projection.to_cols_array())),
+        )
+    });
+}
+
+fn camera_view_projection_matrix(api: &dyn render_api::RenderAPI) -> Option<math::Mat4> {
+    unsafe {
+        CAMERA_MATRICES
+            .as_ref()
+            .map(|(view, projection)| render_api::view_projection_matrix(api, view, projection))
+    }
+}
+
 fn draw_colored_triangle() {
     let verts = [
         render_api::MyVertex {
             x: -0.5,
             y: -0.25,
             z: 0.0,
-            color: 0xFFff0000,
+            color: 0xFFFF0000,
         },
         render_api::MyVertex {
             x: 0.5,
             y: -0.25,
             z: 0.0,
-            color: 0xFF00ff00,
+            color: 0xFF00FF00,
         },
         render_api::MyVertex {
             x: 0.0,
             y: 0.5,
             z: 0.0,
-            color: 0xFF0000ff,
+            color: 0xFF0000FF,
         },
     ];
 
     if let Some(api) = unsafe { CURRENT_API.as_ref() } {
-        let phi = unsafe { TIME };
-        let cos_phi = phi.cos();
-        let sin_phi = phi.sin();
+        let phi = timing::current().angle(1.0);
+        //  The triangle has always turned clockwise (a world matrix with m[1] = -sin(phi)), so both
+        //  paths rotate by -phi.
+        let rotation = math::Mat4::rotation_z(-phi);
+        if let Some(view_projection) = camera_view_projection_matrix(api.as_ref()) {
+            api.draw_simple_triangles(view_projection.mul(&rotation).to_cols_array(), 1, &verts);
+            return;
+        }
+
         let depth = 0.7;
         let final_depth = if api.get_uses_reverse_z() {
             1.0 - depth
         } else {
             depth
         };
-        let world_matrix = [
-            cos_phi,
-            -sin_phi,
-            0.0,
-            0.0,
-            sin_phi,
-            cos_phi,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            1.0,
-            0.0,
-            0.0,
-            0.0,
-            final_depth,
-            1.0,
-        ];
+        let world_matrix = math::Mat4::translation(math::Vec3::new(0.0, 0.0, final_depth))
+            .mul(&rotation)
+            .to_cols_array();
 
         api.draw_simple_triangles(world_matrix, 1, &verts);
     }
 }
 
+static mut OVERLAY_TEXTURE_HANDLE: render_api::Handle = std::ptr::null_mut();
+static OVERLAY_TEXTURE_CHANGED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+//  The overlay texture the backend last drew, only used on the render thread.
+static mut OVERLAY_TEXTURE_DRAWN: render_api::Handle = std::ptr::null_mut();
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetOverlayTextureFromUnity(handle: render_api::Handle) {
+    recorder::record(|| recorder::Call::SetOverlayTexture(handle as u64));
+    unsafe {
+        OVERLAY_TEXTURE_HANDLE = handle;
+    }
+    OVERLAY_TEXTURE_CHANGED.store(true, std::sync::atomic::Ordering::Release);
+}
+
+fn draw_textured_quad() {
+    let handle = unsafe { OVERLAY_TEXTURE_HANDLE };
+    if let Some(api) = unsafe { CURRENT_API.as_ref() } {
+        if OVERLAY_TEXTURE_CHANGED.swap(false, std::sync::atomic::Ordering::Acquire) {
+            unsafe {
+                api.forget_texture(OVERLAY_TEXTURE_DRAWN);
+                OVERLAY_TEXTURE_DRAWN = std::ptr::null_mut();
+            }
+            api.forget_texture(handle);
+        }
+        if handle.is_null() {
+            return;
+        }
+        unsafe { OVERLAY_TEXTURE_DRAWN = handle };
+        let z = if api.get_uses_reverse_z() { 1.0 } else { 0.0 };
+        let vertex =
+            |x: f32, y: f32, u: f32, v: f32| render_api::MyTexturedVertex { x, y, z, u, v };
+        let verts = [
+            vertex(0.5, -0.5, 0.0, 0.0),
+            vertex(0.95, -0.5, 1.0, 0.0),
+            vertex(0.95, -0.95, 1.0, 1.0),
+            vertex(0.5, -0.5, 0.0, 0.0),
+            vertex(0.95, -0.95, 1.0, 1.0),
+            vertex(0.5, -0.95, 0.0, 1.0),
+        ];
+        api.draw_textured_triangles(math::Mat4::IDENTITY.to_cols_array(), 2, &verts, handle);
+    }
+}
+
 fn modify_texture_pixels() {
     unsafe {
         let handle = TEXTURE_HANDLE;
@@ -217,7 +509,7 @@ fn modify_texture_pixels() {
                     return;
                 }
 
-                let t = TIME * 4.0;
+                let t = timing::current().angle(4.0);
 
                 let mut dst = buffer.mut_ptr() as *mut u8;
                 for y in 0..height {
@@ -247,6 +539,38 @@ fn modify_texture_pixels() {
     }
 }
 
+fn recompute_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![[0.0f32; 3]; positions.len()];
+    for tri in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+        if i0 >= positions.len() || i1 >= positions.len() || i2 >= positions.len() {
+            continue;
+        }
+        let (p0, p1, p2) = (positions[i0], positions[i1], positions[i2]);
+        let e1 = [p1[0] - p0[0], p1[1] - p0[1], p1[2] - p0[2]];
+        let e2 = [p2[0] - p0[0], p2[1] - p0[1], p2[2] - p0[2]];
+        let face = [
+            e1[1] * e2[2] - e1[2] * e2[1],
+            e1[2] * e2[0] - e1[0] * e2[2],
+            e1[0] * e2[1] - e1[1] * e2[0],
+        ];
+        for &i in &[i0, i1, i2] {
+            normals[i][0] += face[0];
+            normals[i][1] += face[1];
+            normals[i][2] += face[2];
+        }
+    }
+    for n in normals.iter_mut() {
+        let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        if len > 0.0 {
+            n[0] /= len;
+            n[1] /= len;
+            n[2] /= len;
+        }
+    }
+    normals
+}
+
 fn modify_vertex_buffer() {
     unsafe {
         let handle = VERTEX_BUFFER_HANDLE;
@@ -257,20 +581,35 @@ fn modify_vertex_buffer() {
                     return;
                 }
                 let vertex_stride = buffer.size() / vertex_count;
-                let t = TIME * 3.0;
+                let t = timing::current().angle(3.0);
+
+                let positions = VERTEX_SOURCE
+                    .iter()
+                    .map(|src| {
+                        [
+                            src.pos[0],
+                            src.pos[1]
+                                + (src.pos[0] * 1.1 + t).sin() * 0.4
+                                + (src.pos[2] * 0.9 - t).sin() * 0.3,
+                            src.pos[2],
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                let normals = if INDEX_SOURCE.is_empty() {
+                    None
+                } else {
+                    Some(recompute_normals(&positions, &INDEX_SOURCE))
+                };
 
                 let mut buffer_ptr = buffer.mut_ptr() as *mut u8;
                 for i in 0..vertex_count {
                     let src = &VERTEX_SOURCE[i as usize];
-                    let mut dst = &mut *(buffer_ptr as *mut MeshVertex);
-                    dst.pos[0] = src.pos[0];
-                    dst.pos[1] = src.pos[1]
-                        + (src.pos[0] * 1.1 + t).sin() * 0.4
-                        + (src.pos[2] * 0.9 - t).sin() * 0.3;
-                    dst.pos[2] = src.pos[2];
-                    dst.normal[0] = src.normal[0];
-                    dst.normal[1] = src.normal[1];
-                    dst.normal[2] = src.normal[2];
+                    let dst = &mut *(buffer_ptr as *mut MeshVertex);
+                    dst.pos = positions[i as usize];
+                    dst.normal = match &normals {
+                        Some(normals) => normals[i as usize],
+                        None => src.normal,
+                    };
                     dst.uv[0] = src.uv[0];
                     dst.uv[1] = src.uv[1];
 
@@ -282,14 +621,97 @@ fn modify_vertex_buffer() {
     }
 }
 
-extern "system" fn on_render_event(_: std::os::raw::c_int) {
-    if unsafe { CURRENT_API.is_none() } {
+fn modify_index_buffer() {
+    unsafe {
+        let handle = INDEX_BUFFER_HANDLE;
+        let index_count = INDEX_BUFFER_INDEX_COUNT;
+        if handle.is_null() || INDEX_SOURCE.len() < index_count as usize {
+            return;
+        }
+        if let Some(api) = CURRENT_API.as_ref() {
+            if let Some(mut buffer) = api.begin_modify_index_buffer(handle, INDEX_BUFFER_FORMAT) {
+                if buffer.ptr().is_null() {
+                    return;
+                }
+                let index_count = index_count.min(buffer.size() / buffer.format().size());
+                let triangle_count = index_count / 3;
+                let phase = timing::current().angle(0.5);
+                let visible_count =
+                    ((phase.sin() * 0.5 + 0.5) * triangle_count as f32).ceil() as i32;
+
+                let buffer_ptr = buffer.mut_ptr();
+                for i in 0..index_count {
+                    let triangle = i / 3;
+                    let index = if triangle < visible_count {
+                        INDEX_SOURCE[i as usize]
+                    } else {
+                        INDEX_SOURCE[(triangle * 3) as usize]
+                    };
+                    match buffer.format() {
+                        render_api::IndexFormat::UInt16 => {
+                            *(buffer_ptr as *mut u16).offset(i as isize) = index as u16
+                        }
+                        render_api::IndexFormat::UInt32 => {
+                            *(buffer_ptr as *mut u32).offset(i as isize) = index
+                        }
+                    }
+                }
+            }
+            api.end_modify_index_buffer(handle);
+        }
+    }
+}
+
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderEventId {
+    Default = 1,
+    FlushDebugDraw = 2,
+}
+
+extern "system" fn on_render_event(event_id: std::os::raw::c_int) {
+    recorder::record(|| recorder::Call::RenderEvent(event_id));
+    //  A replay leaves shader changes pending for the live renderer.
+    let changed_shaders = if replay::is_active() {
+        Vec::new()
+    } else {
+        shader_watcher::take_changed()
+    };
+    if !changed_shaders.is_empty() {
+        if let Some(api) = unsafe { CURRENT_API.as_mut() } {
+            api.reload_shaders(&changed_shaders);
+        }
+    }
+
+    let api = match unsafe { CURRENT_API.as_ref() } {
+        Some(api) => api,
+        None => return,
+    };
+
+    if event_id == RenderEventId::FlushDebugDraw as std::os::raw::c_int {
+        let view_projection =
+            camera_view_projection_matrix(api.as_ref()).unwrap_or(math::Mat4::IDENTITY);
+        debug_draw::flush(api.as_ref(), view_projection.to_cols_array());
         return;
     }
 
+    let frame = timing::begin_frame();
     draw_colored_triangle();
-    modify_texture_pixels();
+    draw_textured_quad();
+    let (handle, width, height) = unsafe { (TEXTURE_HANDLE, TEXTURE_WIDTTH, TEXTURE_HEIGHT) };
+    if !texture_feed::update_texture(api.as_ref(), handle, width, height)
+        && !video::update_texture(api.as_ref(), handle, width, height)
+        && !texture_loader::update_texture(api.as_ref(), (handle, width, height))
+    {
+        modify_texture_pixels();
+    }
     modify_vertex_buffer();
+    modify_index_buffer();
+    capture::end_frame(
+        api.as_ref(),
+        unsafe { (TEXTURE_HANDLE, TEXTURE_WIDTTH, TEXTURE_HEIGHT) },
+        frame.frame_index,
+    );
 }
 
 #[no_mangle]
@@ -298,8 +720,518 @@ pub extern "system" fn GetRenderEventFunc() -> unity_native_plugin::graphics::Re
     Some(on_render_event)
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn DebugDrawLine(from: math::Vec3, to: math::Vec3, color: u32) {
+    recorder::record(|| recorder::Call::DebugDrawLine(from, to, color));
+    debug_draw::line(from, to, color);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn DebugDrawBox(center: math::Vec3, half_extents: math::Vec3, color: u32) {
+    recorder::record(|| recorder::Call::DebugDrawBox(center, half_extents, color));
+    debug_draw::wire_box(center, half_extents, color);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn DebugDrawSphere(center: math::Vec3, radius: f32, color: u32) {
+    recorder::record(|| recorder::Call::DebugDrawSphere(center, radius, color));
+    debug_draw::sphere(center, radius, color);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn DebugDrawArrow(from: math::Vec3, to: math::Vec3, color: u32) {
+    recorder::record(|| recorder::Call::DebugDrawArrow(from, to, color));
+    debug_draw::arrow(from, to, color);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn DebugDrawAxes(matrix: *const f32, size: f32) {
+    recorder::record(|| {
+        let matrix = if matrix.is_null() {
+            None
+        } else {
+            Some(unsafe { *(matrix as *const [f32; 16]) })
+        };
+        recorder::Call::DebugDrawAxes(matrix, size)
+    });
+    if matrix.is_null() {
+        return;
+    }
+    let matrix = unsafe { &*(matrix as *const [f32; 16]) };
+    debug_draw::axes(matrix, size);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn DebugDrawQuad(
+    p0: math::Vec3,
+    p1: math::Vec3,
+    p2: math::Vec3,
+    p3: math::Vec3,
+    color: u32,
+) {
+    recorder::record(|| recorder::Call::DebugDrawQuad([p0, p1, p2, p3], color));
+    debug_draw::quad(p0, p1, p2, p3, color);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn DebugDrawClear() {
+    recorder::record(|| recorder::Call::DebugDrawClear);
+    debug_draw::clear();
+}
+
+//  Back to the state right after loading, apart from the render API and the shader directory.
+fn reset_plugin_state() {
+    unsafe {
+        TEXTURE_HANDLE = std::ptr::null_mut();
+        TEXTURE_WIDTTH = 0;
+        TEXTURE_HEIGHT = 0;
+        VERTEX_BUFFER_HANDLE = std::ptr::null_mut();
+        VERTEX_BUFFER_VERTEX_COUNT = 0;
+        VERTEX_SOURCE = Vec::new();
+        INDEX_BUFFER_HANDLE = std::ptr::null_mut();
+        INDEX_BUFFER_INDEX_COUNT = 0;
+        INDEX_BUFFER_FORMAT = render_api::IndexFormat::UInt16;
+        INDEX_SOURCE = Vec::new();
+        CAMERA_MATRICES = None;
+        OVERLAY_TEXTURE_HANDLE = std::ptr::null_mut();
+    }
+    OVERLAY_TEXTURE_CHANGED.store(true, std::sync::atomic::Ordering::Release);
+    debug_draw::clear();
+    texture_feed::close();
+    texture_loader::clear();
+    video::clear();
+    timing::reset();
+}
+
+fn path_from_c_str(path: *const std::os::raw::c_char) -> Option<std::path::PathBuf> {
+    if path.is_null() {
+        return None;
+    }
+    unsafe { std::ffi::CStr::from_ptr(path) }
+        .to_str()
+        .ok()
+        .filter(|path| !path.is_empty())
+        .map(std::path::PathBuf::from)
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn StartRecording(path: *const std::os::raw::c_char) -> bool {
+    let path = match path_from_c_str(path) {
+        Some(path) => path,
+        None => return false,
+    };
+    recorder::stop();
+    match recorder::start(&path) {
+        Ok(_) => true,
+        Err(e) => {
+            logger::error(&format!("failed to start recording: {}", e));
+            false
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn StopRecording() {
+    recorder::stop();
+}
+
+//  Writes the next frame to `path` (.png, .ppm or .exr); `{frame}` in the path captures every frame.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn CaptureFrame(path: *const std::os::raw::c_char) -> bool {
+    let path = match path_from_c_str(path) {
+        Some(path) => path,
+        None => return false,
+    };
+    match capture::request(&path) {
+        Ok(_) => true,
+        Err(e) => {
+            logger::error(&e);
+            false
+        }
+    }
+}
+
+//  Writes the next frame of Unity's Vulkan calls to `path` as Chrome trace JSON. Only works when
+//  RENDERING_PLUGIN_VULKAN_TRACE enabled tracing before the device was created.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn DumpVulkanTrace(path: *const std::os::raw::c_char) -> bool {
+    let path = match path_from_c_str(path) {
+        Some(path) => path,
+        None => return false,
+    };
+    #[cfg(backend_vulkan)]
+    if vulkan_trace::is_enabled() {
+        return vulkan_trace::request_dump(&path);
+    }
+    logger::warning(&format!(
+        "Vulkan tracing is not enabled, not writing {}",
+        path.display()
+    ));
+    false
+}
+
+//  Fills the texture set with SetTextureFromUnity from the shared-memory feed at `path` (see
+//  texture_feed.rs) instead of the plasma effect. A null or empty path closes the feed.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetTextureFeed(path: *const std::os::raw::c_char) -> bool {
+    let path = path_from_c_str(path);
+    recorder::record(|| {
+        recorder::Call::SetTextureFeed(path.as_ref().map(|p| p.display().to_string()))
+    });
+    let path = match path {
+        Some(path) => path,
+        None => {
+            texture_feed::close();
+            return true;
+        }
+    };
+    match texture_feed::open(&path) {
+        Ok(_) => true,
+        Err(e) => {
+            logger::error(&format!(
+                "cannot open texture feed {}: {}",
+                path.display(),
+                e
+            ));
+            false
+        }
+    }
+}
+
+//  Decodes a PNG, JPEG, TGA or Radiance HDR file on a worker thread and uploads it into the
+//  texture, resized to the size given to SetTextureFromUnity, at a render event after decoding
+//  finished. The texture must be the one passed to SetTextureFromUnity. Returns false for a null or
+//  empty path or another texture; decoding errors are logged.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn LoadImageIntoTexture(
+    handle: render_api::Handle,
+    path: *const std::os::raw::c_char,
+) -> bool {
+    let path = path_from_c_str(path);
+    recorder::record(|| recorder::Call::LoadImageIntoTexture {
+        handle: handle as u64,
+        path: path.as_ref().map(|p| p.display().to_string()),
+    });
+    let (texture, width, height) = unsafe { (TEXTURE_HANDLE, TEXTURE_WIDTTH, TEXTURE_HEIGHT) };
+    match path {
+        Some(path) if !handle.is_null() && handle == texture && width > 0 && height > 0 => {
+            texture_loader::load(handle, path, (width, height));
+            true
+        }
+        Some(path) if !handle.is_null() => {
+            logger::error(&format!(
+                "cannot load {} into a texture that was not passed to SetTextureFromUnity",
+                path.display()
+            ));
+            false
+        }
+        _ => false,
+    }
+}
+
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VideoFormat {
+    Nv12 = 0,
+    I420 = 1,
+}
+
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum YuvColorSpace {
+    Bt601 = 0,
+    Bt709 = 1,
+}
+
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum YuvRange {
+    Limited = 0,
+    Full = 1,
+}
+
+//  A 4:2:0 frame in the caller's memory. For NV12, `u_plane` is the interleaved UV plane and
+//  `v_plane` is unused.
+#[repr(C)]
+pub struct VideoFrame {
+    pub format: i32,
+    pub width: i32,
+    pub height: i32,
+    pub color_space: i32,
+    pub range: i32,
+    pub y_plane: render_api::Handle,
+    pub y_stride: i32,
+    pub u_plane: render_api::Handle,
+    pub u_stride: i32,
+    pub v_plane: render_api::Handle,
+    pub v_stride: i32,
+}
+
+//  Copies a video frame, which is uploaded at the next render event: converted to RGBA into the
+//  texture set with SetTextureFromUnity (replacing the plasma effect), or into the plane textures
+//  when SetVideoPlaneTexturesFromUnity registered them.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SubmitVideoFrame(frame: VideoFrame) -> bool {
+    recorder::record(|| recorder::Call::SubmitVideoFrame {
+        format: frame.format,
+        width: frame.width,
+        height: frame.height,
+        color_space: frame.color_space,
+        range: frame.range,
+        planes: video::packed_planes(&frame),
+    });
+    match video::submit(&frame) {
+        Ok(_) => true,
+        Err(e) => {
+            logger::error(&e);
+            false
+        }
+    }
+}
+
+//  Luma goes to `luma` (Y in RGB) and chroma to `chroma` (U in R, V in G, at half the frame size),
+//  for a shader to convert with GetYuvToRgbMatrix. Null handles switch back to CPU conversion.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetVideoPlaneTexturesFromUnity(
+    luma: render_api::Handle,
+    luma_width: i32,
+    luma_height: i32,
+    chroma: render_api::Handle,
+    chroma_width: i32,
+    chroma_height: i32,
+) {
+    recorder::record(|| recorder::Call::SetVideoPlaneTextures {
+        luma: (luma as u64, luma_width, luma_height),
+        chroma: (chroma as u64, chroma_width, chroma_height),
+    });
+    video::set_plane_textures(
+        (luma, luma_width, luma_height),
+        (chroma, chroma_width, chroma_height),
+    );
+}
+
+//  Stops the video; the texture set with SetTextureFromUnity shows the plasma effect again.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn StopVideo() {
+    recorder::record(|| recorder::Call::StopVideo);
+    video::clear();
+}
+
+//  Writes the 4x4 column-major matrix for which rgb = (matrix * float4(y, u, v, 1)).rgb, with the
+//  samples read from the plane textures, to `matrix`. Returns false for unknown values.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetYuvToRgbMatrix(color_space: i32, range: i32, matrix: *mut f32) -> bool {
+    let (color_space, range) = match (
+        video::color_space_from_i32(color_space),
+        video::range_from_i32(range),
+    ) {
+        (Some(color_space), Some(range)) if !matrix.is_null() => (color_space, range),
+        _ => return false,
+    };
+    unsafe {
+        *(matrix as *mut [f32; 16]) = video::matrix(color_space, range).to_cols_array();
+    }
+    true
+}
+
+//  Bump whenever an export's signature or a payload layout changes incompatibly.
+pub const PLUGIN_ABI_VERSION: u32 = 2;
+
+static PLUGIN_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Capability {
+    Renderer = 1 << 0,
+    ColoredTriangle = 1 << 1,
+    TexturedQuad = 1 << 2,
+    ModifyTexture = 1 << 3,
+    ModifyVertexBuffer = 1 << 4,
+    ModifyIndexBuffer = 1 << 5,
+    DebugDraw = 1 << 6,
+    CameraMatrices = 1 << 7,
+    ShaderHotReload = 1 << 8,
+    FrameCapture = 1 << 9,
+    TextureFeed = 1 << 10,
+    VideoFrames = 1 << 11,
+    ImageLoading = 1 << 12,
+}
+
+//  Effects that only need ModifyTexture from the backend: they upload through begin/end_modify_texture.
+const TEXTURE_SOURCES: [Capability; 3] = [
+    Capability::TextureFeed,
+    Capability::VideoFrames,
+    Capability::ImageLoading,
+];
+
+fn query_capabilities(renderer: unity_native_plugin::graphics::GfxRenderer) -> u32 {
+    let api = match render_api::create_render_api(renderer) {
+        Some(api) => api,
+        None => return 0,
+    };
+    let mut bits = Capability::Renderer as u32 | api.capabilities();
+    if bits & Capability::ModifyTexture as u32 != 0 {
+        bits = TEXTURE_SOURCES
+            .iter()
+            .fold(bits, |bits, &source| bits | source as u32);
+    }
+    bits
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetPluginVersion() -> *const std::os::raw::c_char {
+    PLUGIN_VERSION.as_ptr() as _
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetPluginAbiVersion() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn QueryCapabilities(renderer: i32) -> u32 {
+    render_api::renderer_from_i32(renderer)
+        .map(query_capabilities)
+        .unwrap_or(0)
+}
+
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ValidationSeverity {
+    Verbose = 0,
+    Info = 1,
+    Warning = 2,
+    Error = 3,
+}
+
+//  Number of validation messages of a severity since load or the last reset. Validation is only
+//  enabled on Vulkan with RENDERING_PLUGIN_VULKAN_VALIDATION set; otherwise this stays 0.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetValidationMessageCount(severity: i32) -> u32 {
+    validation::severity_from_i32(severity)
+        .map(validation::count)
+        .unwrap_or(0)
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn ResetValidationMessageCounts() {
+    validation::reset_counts();
+}
+
+//  Messages below this severity are still counted but not logged.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn SetValidationLogSeverity(severity: i32) {
+    if let Some(severity) = validation::severity_from_i32(severity) {
+        validation::set_log_severity(severity);
+    }
+}
+
+#[test]
+fn test_recompute_normals() {
+    let positions = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [5.0, 5.0, 5.0],
+    ];
+    let normals = recompute_normals(&positions, &[0, 2, 1, 1, 2, 7]);
+    assert_eq!(normals[0], [0.0, 1.0, 0.0]);
+    assert_eq!(normals[1], [0.0, 1.0, 0.0]);
+    assert_eq!(normals[2], [0.0, 1.0, 0.0]);
+    assert_eq!(normals[3], [0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn test_copy_source() {
+    let values = [0.0f32, 1.0, 2.0, 3.0, 4.0, 5.0];
+    assert_eq!(copy_source(values.as_ptr(), 2, 3), values);
+    assert_eq!(copy_source(values.as_ptr(), 3, 2), values);
+    assert!(copy_source(values.as_ptr(), -1, 3).is_empty());
+    assert!(copy_source(std::ptr::null::<f32>(), 2, 3).is_empty());
+}
+
+#[test]
+fn test_read_indices() {
+    use render_api::index_format_from_i32;
+
+    let indices_16: [u16; 3] = [0, 1, 65535];
+    let indices_32: [u32; 3] = [0, 1, 65536];
+    assert_eq!(
+        read_indices(indices_16.as_ptr() as *const _, 3, IndexFormat::UInt16),
+        [0, 1, 65535]
+    );
+    assert_eq!(
+        read_indices(indices_32.as_ptr() as *const _, 3, IndexFormat::UInt32),
+        [0, 1, 65536]
+    );
+    assert!(read_indices(std::ptr::null(), 3, IndexFormat::UInt32).is_empty());
+    assert!(read_indices(indices_32.as_ptr() as *const _, -1, IndexFormat::UInt32).is_empty());
+    assert_eq!(index_format_from_i32(1), Some(IndexFormat::UInt32));
+    assert_eq!(index_format_from_i32(2), None);
+}
+
+#[test]
+fn test_bindings_are_up_to_date() {
+    //  Regenerate with RENDERING_PLUGIN_UPDATE_BINDINGS=1 cargo build
+    let normalize = |s: &str| s.replace("\r\n", "\n");
+    let generated_csharp = include_str!(concat!(env!("OUT_DIR"), "/RenderingPlugin.cs"));
+    let generated_c_header = include_str!(concat!(env!("OUT_DIR"), "/RenderingPlugin.h"));
+    assert_eq!(
+        normalize(include_str!("../bindings/RenderingPlugin.cs")),
+        normalize(generated_csharp)
+    );
+    assert_eq!(
+        normalize(include_str!("../bindings/RenderingPlugin.h")),
+        normalize(generated_c_header)
+    );
+}
+
+#[test]
+fn test_query_capabilities() {
+    let d3d11 = QueryCapabilities(2);
+    if cfg!(backend_d3d11) {
+        assert_ne!(d3d11 & Capability::Renderer as u32, 0);
+        assert_ne!(d3d11 & Capability::DebugDraw as u32, 0);
+        assert_ne!(d3d11 & Capability::ImageLoading as u32, 0);
+    } else {
+        assert_eq!(d3d11, 0);
+    }
+    assert_eq!(QueryCapabilities(4), 0);
+    //  Metal.
+    assert_eq!(QueryCapabilities(16), 0);
+    assert_eq!(QueryCapabilities(-1), 0);
+    let version = unsafe { std::ffi::CStr::from_ptr(GetPluginVersion()) };
+    assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+}
+
+#[cfg(backend_d3d11)]
 #[test]
 fn test_modify_texture_pixels() {
+    //  The tester loads the plugin into its globals, like a replay or the fake host.
+    let _replaying = replay::REPLAYING.lock().unwrap_or_else(|e| e.into_inner());
     let instant = std::time::Instant::now();
     unity_native_plugin_tester::d3d11::test_plugin_d3d11(
         (256, 256),
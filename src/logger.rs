@@ -0,0 +1,75 @@
+use std::os::raw::{c_char, c_int};
+use unity_native_plugin::define_unity_interface;
+use unity_native_plugin::interface::UnityInterface;
+
+//  unity-native-plugin 0.4 has no binding for IUnityLog, so it is declared here after
+//  IUnityLog.h.
+#[repr(C)]
+#[allow(non_snake_case)]
+pub struct IUnityLog {
+    Log: Option<
+        unsafe extern "system" fn(
+            log_type: c_int,
+            message: *const c_char,
+            file_name: *const c_char,
+            file_line: c_int,
+        ),
+    >,
+}
+
+define_unity_interface!(
+    UnityLog,
+    IUnityLog,
+    0x9E7507FA5B444D5D_u64,
+    0x92FB979515EA83FC_u64
+);
+
+//  UnityLogType
+#[repr(i32)]
+#[derive(Clone, Copy)]
+enum LogType {
+    Error = 0,
+    Warning = 2,
+    Log = 3,
+}
+
+static mut UNITY_LOG: Option<UnityLog> = None;
+
+pub fn initialize(interfaces: &unity_native_plugin::interface::UnityInterfaces) {
+    unsafe {
+        UNITY_LOG = interfaces.interface::<UnityLog>();
+    }
+}
+
+pub fn finalize() {
+    unsafe {
+        UNITY_LOG = None;
+    }
+}
+
+fn log(log_type: LogType, message: &str) {
+    unsafe {
+        if let Some(log) = UNITY_LOG
+            .as_ref()
+            .and_then(|unity_log| unity_log.interface().Log)
+        {
+            let message = std::ffi::CString::new(format!("[RenderingPlugin] {}", message))
+                .unwrap_or_default();
+            log(log_type as c_int, message.as_ptr(), b"\0".as_ptr() as _, 0);
+            return;
+        }
+    }
+    eprintln!("[RenderingPlugin] {}", message);
+}
+
+pub fn info(message: &str) {
+    log(LogType::Log, message);
+}
+
+pub fn warning(message: &str) {
+    log(LogType::Warning, message);
+}
+
+pub fn error(message: &str) {
+    log(LogType::Error, message);
+}
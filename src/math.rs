@@ -0,0 +1,194 @@
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    pub fn add(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+
+    pub fn sub(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+
+    pub fn scale(self, s: f32) -> Vec3 {
+        Vec3::new(self.x * s, self.y * s, self.z * s)
+    }
+
+    pub fn dot(self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Vec3 {
+        let len = self.length();
+        if len > 0.0 {
+            self.scale(1.0 / len)
+        } else {
+            self
+        }
+    }
+}
+
+//  Column-major storage with column vectors, the same memory layout as Unity's Matrix4x4.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mat4 {
+    pub m: [f32; 16],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        ],
+    };
+
+    pub const fn from_cols_array(m: [f32; 16]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn to_cols_array(self) -> [f32; 16] {
+        self.m
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f32 {
+        self.m[col * 4 + row]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: f32) {
+        self.m[col * 4 + row] = value;
+    }
+
+    pub fn translation(v: Vec3) -> Mat4 {
+        let mut ret = Mat4::IDENTITY;
+        ret.set(0, 3, v.x);
+        ret.set(1, 3, v.y);
+        ret.set(2, 3, v.z);
+        ret
+    }
+
+    pub fn rotation_z(phi: f32) -> Mat4 {
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let mut ret = Mat4::IDENTITY;
+        ret.set(0, 0, cos_phi);
+        ret.set(0, 1, -sin_phi);
+        ret.set(1, 0, sin_phi);
+        ret.set(1, 1, cos_phi);
+        ret
+    }
+
+    pub fn mul(&self, rhs: &Mat4) -> Mat4 {
+        let mut ret = Mat4 { m: [0.0; 16] };
+        for row in 0..4 {
+            for col in 0..4 {
+                let mut v = 0.0;
+                for k in 0..4 {
+                    v += self.get(row, k) * rhs.get(k, col);
+                }
+                ret.set(row, col, v);
+            }
+        }
+        ret
+    }
+
+    pub fn transform(&self, v: [f32; 4]) -> [f32; 4] {
+        let mut ret = [0.0; 4];
+        for (row, r) in ret.iter_mut().enumerate() {
+            *r = (0..4).map(|k| self.get(row, k) * v[k]).sum();
+        }
+        ret
+    }
+
+    #[cfg(test)]
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let v = self.transform([p.x, p.y, p.z, 1.0]);
+        if v[3] != 0.0 {
+            Vec3::new(v[0] / v[3], v[1] / v[3], v[2] / v[3])
+        } else {
+            Vec3::new(v[0], v[1], v[2])
+        }
+    }
+
+    //  Converts an OpenGL style projection matrix (Camera.projectionMatrix, clip z in -w..w)
+    //  into the convention of the active graphics API, like GL.GetGPUProjectionMatrix.
+    pub fn gpu_projection(&self, clip_space_zero_to_one: bool, reverse_z: bool) -> Mat4 {
+        let mut ret = *self;
+        for col in 0..4 {
+            let z = self.get(2, col);
+            let w = self.get(3, col);
+            let z = if clip_space_zero_to_one {
+                let z = z * 0.5 + w * 0.5;
+                if reverse_z {
+                    w - z
+                } else {
+                    z
+                }
+            } else if reverse_z {
+                -z
+            } else {
+                z
+            };
+            ret.set(2, col, z);
+        }
+        ret
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Mat4 {
+        Mat4::IDENTITY
+    }
+}
+
+#[test]
+fn test_mat4_mul_applies_right_hand_side_first() {
+    let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0))
+        .mul(&Mat4::rotation_z(std::f32::consts::FRAC_PI_2));
+    let p = m.transform_point(Vec3::new(1.0, 0.0, 0.0));
+    assert!((p.x - 1.0).abs() < 1e-6);
+    assert!((p.y - 3.0).abs() < 1e-6);
+    assert!((p.z - 3.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_gpu_projection_maps_depth_range() {
+    let (near, far) = (1.0f32, 11.0f32);
+    let mut proj = Mat4::IDENTITY;
+    proj.set(2, 2, -(far + near) / (far - near));
+    proj.set(2, 3, -2.0 * far * near / (far - near));
+    proj.set(3, 2, -1.0);
+    proj.set(3, 3, 0.0);
+
+    let depth = |m: &Mat4, z: f32| m.transform_point(Vec3::new(0.0, 0.0, -z)).z;
+
+    assert!((depth(&proj, near) + 1.0).abs() < 1e-5);
+    assert!((depth(&proj, far) - 1.0).abs() < 1e-5);
+
+    let d3d = proj.gpu_projection(true, false);
+    assert!(depth(&d3d, near).abs() < 1e-5);
+    assert!((depth(&d3d, far) - 1.0).abs() < 1e-5);
+
+    let reversed = proj.gpu_projection(true, true);
+    assert!((depth(&reversed, near) - 1.0).abs() < 1e-5);
+    assert!(depth(&reversed, far).abs() < 1e-5);
+}
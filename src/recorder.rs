@@ -0,0 +1,568 @@
+use crate::logger;
+use crate::math::Vec3;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+pub const RECORD_PATH_ENV: &str = "RENDERING_PLUGIN_RECORD";
+
+const MAGIC: &[u8; 8] = b"RPLGREC\0";
+const VERSION: u32 = 1;
+
+//  One recorded export call. Handles are recorded by value and only used to tell resources apart
+//  on replay.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Call {
+    SetTimeFromUnity(f32),
+    SetTimeFromUnityDouble(f64),
+    SetTimePaused(bool),
+    SetTimeScale(f64),
+    SetFixedTimeStep(f64),
+    ResetTime,
+    SetTexture {
+        handle: u64,
+        width: i32,
+        height: i32,
+    },
+    SetMeshBuffers {
+        handle: u64,
+        vertex_count: i32,
+        vertices: Vec<f32>,
+        normals: Vec<f32>,
+        uv: Vec<f32>,
+    },
+    SetMeshIndexBuffer {
+        handle: u64,
+        index_count: i32,
+        index_format: i32,
+        indices: Vec<u32>,
+    },
+    SetShaderDirectory(Option<String>),
+    SetTextureFeed(Option<String>),
+    //  The planes are stored without row padding, as (stride, data), and are empty for a frame the
+    //  plugin rejected.
+    SubmitVideoFrame {
+        format: i32,
+        width: i32,
+        height: i32,
+        color_space: i32,
+        range: i32,
+        planes: Vec<(i32, Vec<u8>)>,
+    },
+    SetVideoPlaneTextures {
+        luma: (u64, i32, i32),
+        chroma: (u64, i32, i32),
+    },
+    StopVideo,
+    LoadImageIntoTexture {
+        handle: u64,
+        path: Option<String>,
+    },
+    SetCameraMatrices(Option<([f32; 16], [f32; 16])>),
+    SetOverlayTexture(u64),
+    DebugDrawLine(Vec3, Vec3, u32),
+    DebugDrawBox(Vec3, Vec3, u32),
+    DebugDrawSphere(Vec3, f32, u32),
+    DebugDrawArrow(Vec3, Vec3, u32),
+    DebugDrawAxes(Option<[f32; 16]>, f32),
+    DebugDrawQuad([Vec3; 4], u32),
+    DebugDrawClear,
+    RenderEvent(i32),
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f64(&mut self, v: f64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn vec3(&mut self, v: Vec3) {
+        self.f32(v.x);
+        self.f32(v.y);
+        self.f32(v.z);
+    }
+
+    fn f32s(&mut self, v: &[f32]) {
+        self.u32(v.len() as u32);
+        v.iter().for_each(|&v| self.f32(v));
+    }
+
+    fn u32s(&mut self, v: &[u32]) {
+        self.u32(v.len() as u32);
+        v.iter().for_each(|&v| self.u32(v));
+    }
+
+    fn u8s(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.0.extend_from_slice(v);
+    }
+
+    fn texture(&mut self, (handle, width, height): (u64, i32, i32)) {
+        self.u64(handle);
+        self.i32(width);
+        self.i32(height);
+    }
+
+    //  An empty string stands for None.
+    fn path(&mut self, v: Option<&str>) {
+        let v = v.unwrap_or("");
+        self.u32(v.len() as u32);
+        self.0.extend_from_slice(v.as_bytes());
+    }
+
+    fn matrix(&mut self, v: Option<[f32; 16]>) {
+        match v {
+            Some(v) => {
+                self.u8(1);
+                v.iter().for_each(|&v| self.f32(v));
+            }
+            None => self.u8(0),
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        let (ret, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(ret)
+    }
+
+    fn array<const N: usize>(&mut self) -> std::io::Result<[u8; N]> {
+        let mut ret = [0u8; N];
+        ret.copy_from_slice(self.bytes(N)?);
+        Ok(ret)
+    }
+
+    fn u8(&mut self) -> std::io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> std::io::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> std::io::Result<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> std::io::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> std::io::Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> std::io::Result<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn vec3(&mut self) -> std::io::Result<Vec3> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn f32s(&mut self) -> std::io::Result<Vec<f32>> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.f32()).collect()
+    }
+
+    fn u32s(&mut self) -> std::io::Result<Vec<u32>> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.u32()).collect()
+    }
+
+    fn u8s(&mut self) -> std::io::Result<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Ok(self.bytes(len)?.to_vec())
+    }
+
+    fn texture(&mut self) -> std::io::Result<(u64, i32, i32)> {
+        Ok((self.u64()?, self.i32()?, self.i32()?))
+    }
+
+    fn path(&mut self) -> std::io::Result<Option<String>> {
+        let len = self.u32()? as usize;
+        let path = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        Ok(Some(path).filter(|path| !path.is_empty()))
+    }
+
+    fn matrix(&mut self) -> std::io::Result<Option<[f32; 16]>> {
+        if self.u8()? == 0 {
+            return Ok(None);
+        }
+        let mut ret = [0.0; 16];
+        for v in ret.iter_mut() {
+            *v = self.f32()?;
+        }
+        Ok(Some(ret))
+    }
+}
+
+impl Call {
+    fn encode(&self, w: &mut Writer) {
+        match self {
+            Call::SetTimeFromUnity(t) => {
+                w.u8(0);
+                w.f32(*t);
+            }
+            Call::SetTimeFromUnityDouble(t) => {
+                w.u8(1);
+                w.f64(*t);
+            }
+            Call::SetTimePaused(paused) => {
+                w.u8(2);
+                w.u8(*paused as u8);
+            }
+            Call::SetTimeScale(scale) => {
+                w.u8(3);
+                w.f64(*scale);
+            }
+            Call::SetFixedTimeStep(step) => {
+                w.u8(4);
+                w.f64(*step);
+            }
+            Call::ResetTime => w.u8(5),
+            Call::SetTexture {
+                handle,
+                width,
+                height,
+            } => {
+                w.u8(6);
+                w.u64(*handle);
+                w.i32(*width);
+                w.i32(*height);
+            }
+            Call::SetMeshBuffers {
+                handle,
+                vertex_count,
+                vertices,
+                normals,
+                uv,
+            } => {
+                w.u8(7);
+                w.u64(*handle);
+                w.i32(*vertex_count);
+                w.f32s(vertices);
+                w.f32s(normals);
+                w.f32s(uv);
+            }
+            Call::SetMeshIndexBuffer {
+                handle,
+                index_count,
+                index_format,
+                indices,
+            } => {
+                w.u8(8);
+                w.u64(*handle);
+                w.i32(*index_count);
+                w.i32(*index_format);
+                w.u32s(indices);
+            }
+            Call::SetShaderDirectory(path) => {
+                w.u8(9);
+                w.path(path.as_deref());
+            }
+            Call::SetCameraMatrices(matrices) => {
+                w.u8(10);
+                w.matrix(matrices.map(|(view, _)| view));
+                w.matrix(matrices.map(|(_, projection)| projection));
+            }
+            Call::SetOverlayTexture(handle) => {
+                w.u8(11);
+                w.u64(*handle);
+            }
+            Call::DebugDrawLine(from, to, color) => {
+                w.u8(12);
+                w.vec3(*from);
+                w.vec3(*to);
+                w.u32(*color);
+            }
+            Call::DebugDrawBox(center, half_extents, color) => {
+                w.u8(13);
+                w.vec3(*center);
+                w.vec3(*half_extents);
+                w.u32(*color);
+            }
+            Call::DebugDrawSphere(center, radius, color) => {
+                w.u8(14);
+                w.vec3(*center);
+                w.f32(*radius);
+                w.u32(*color);
+            }
+            Call::DebugDrawArrow(from, to, color) => {
+                w.u8(15);
+                w.vec3(*from);
+                w.vec3(*to);
+                w.u32(*color);
+            }
+            Call::DebugDrawAxes(matrix, size) => {
+                w.u8(16);
+                w.matrix(*matrix);
+                w.f32(*size);
+            }
+            Call::DebugDrawQuad(points, color) => {
+                w.u8(17);
+                points.iter().for_each(|&p| w.vec3(p));
+                w.u32(*color);
+            }
+            Call::DebugDrawClear => w.u8(18),
+            Call::RenderEvent(event_id) => {
+                w.u8(19);
+                w.i32(*event_id);
+            }
+            Call::SetTextureFeed(path) => {
+                w.u8(20);
+                w.path(path.as_deref());
+            }
+            Call::SubmitVideoFrame {
+                format,
+                width,
+                height,
+                color_space,
+                range,
+                planes,
+            } => {
+                w.u8(21);
+                w.i32(*format);
+                w.i32(*width);
+                w.i32(*height);
+                w.i32(*color_space);
+                w.i32(*range);
+                w.u32(planes.len() as u32);
+                for (stride, data) in planes {
+                    w.i32(*stride);
+                    w.u8s(data);
+                }
+            }
+            Call::SetVideoPlaneTextures { luma, chroma } => {
+                w.u8(22);
+                w.texture(*luma);
+                w.texture(*chroma);
+            }
+            Call::StopVideo => w.u8(23),
+            Call::LoadImageIntoTexture { handle, path } => {
+                w.u8(24);
+                w.u64(*handle);
+                w.path(path.as_deref());
+            }
+        }
+    }
+
+    fn decode(r: &mut Reader) -> std::io::Result<Call> {
+        Ok(match r.u8()? {
+            0 => Call::SetTimeFromUnity(r.f32()?),
+            1 => Call::SetTimeFromUnityDouble(r.f64()?),
+            2 => Call::SetTimePaused(r.u8()? != 0),
+            3 => Call::SetTimeScale(r.f64()?),
+            4 => Call::SetFixedTimeStep(r.f64()?),
+            5 => Call::ResetTime,
+            6 => Call::SetTexture {
+                handle: r.u64()?,
+                width: r.i32()?,
+                height: r.i32()?,
+            },
+            7 => Call::SetMeshBuffers {
+                handle: r.u64()?,
+                vertex_count: r.i32()?,
+                vertices: r.f32s()?,
+                normals: r.f32s()?,
+                uv: r.f32s()?,
+            },
+            8 => Call::SetMeshIndexBuffer {
+                handle: r.u64()?,
+                index_count: r.i32()?,
+                index_format: r.i32()?,
+                indices: r.u32s()?,
+            },
+            9 => Call::SetShaderDirectory(r.path()?),
+            10 => {
+                let view = r.matrix()?;
+                let projection = r.matrix()?;
+                Call::SetCameraMatrices(view.zip(projection))
+            }
+            11 => Call::SetOverlayTexture(r.u64()?),
+            12 => Call::DebugDrawLine(r.vec3()?, r.vec3()?, r.u32()?),
+            13 => Call::DebugDrawBox(r.vec3()?, r.vec3()?, r.u32()?),
+            14 => Call::DebugDrawSphere(r.vec3()?, r.f32()?, r.u32()?),
+            15 => Call::DebugDrawArrow(r.vec3()?, r.vec3()?, r.u32()?),
+            16 => Call::DebugDrawAxes(r.matrix()?, r.f32()?),
+            17 => Call::DebugDrawQuad([r.vec3()?, r.vec3()?, r.vec3()?, r.vec3()?], r.u32()?),
+            18 => Call::DebugDrawClear,
+            19 => Call::RenderEvent(r.i32()?),
+            20 => Call::SetTextureFeed(r.path()?),
+            21 => Call::SubmitVideoFrame {
+                format: r.i32()?,
+                width: r.i32()?,
+                height: r.i32()?,
+                color_space: r.i32()?,
+                range: r.i32()?,
+                planes: {
+                    let len = r.u32()? as usize;
+                    (0..len)
+                        .map(|_| Ok((r.i32()?, r.u8s()?)))
+                        .collect::<std::io::Result<_>>()?
+                },
+            },
+            22 => Call::SetVideoPlaneTextures {
+                luma: r.texture()?,
+                chroma: r.texture()?,
+            },
+            23 => Call::StopVideo,
+            24 => Call::LoadImageIntoTexture {
+                handle: r.u64()?,
+                path: r.path()?,
+            },
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "unknown call in recording",
+                ))
+            }
+        })
+    }
+}
+
+pub fn encode(calls: &[Call]) -> Vec<u8> {
+    let mut w = Writer(MAGIC.to_vec());
+    w.u32(VERSION);
+    calls.iter().for_each(|call| call.encode(&mut w));
+    w.0
+}
+
+pub fn decode(data: &[u8]) -> std::io::Result<Vec<Call>> {
+    let mut r = Reader(data);
+    if r.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) || r.u32()? != VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not a recording of this plugin version",
+        ));
+    }
+    let mut calls = Vec::new();
+    while !r.0.is_empty() {
+        calls.push(Call::decode(&mut r)?);
+    }
+    Ok(calls)
+}
+
+pub fn read_file(path: &std::path::Path) -> std::io::Result<Vec<Call>> {
+    let mut data = Vec::new();
+    std::fs::File::open(path)?.read_to_end(&mut data)?;
+    decode(&data)
+}
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static OUTPUT: Mutex<Option<std::io::BufWriter<std::fs::File>>> = Mutex::new(None);
+
+pub fn start(path: &std::path::Path) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::with_capacity(1 << 16, std::fs::File::create(path)?);
+    file.write_all(&encode(&[]))?;
+    *OUTPUT.lock().unwrap() = Some(file);
+    RECORDING.store(true, Ordering::Release);
+    logger::info(&format!("recording to {}", path.display()));
+    Ok(())
+}
+
+pub fn stop() {
+    RECORDING.store(false, Ordering::Release);
+    if let Some(mut file) = OUTPUT.lock().unwrap().take() {
+        if let Err(e) = file.flush() {
+            logger::error(&format!("cannot finish the recording: {}", e));
+        }
+    }
+}
+
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Acquire)
+}
+
+//  Takes a closure so nothing is copied unless a recording is running. Writes are buffered, so the
+//  render thread only touches the file when the buffer fills up, and `stop` flushes the rest. Calls
+//  made by a replay are skipped.
+pub fn record(call: impl FnOnce() -> Call) {
+    if !is_recording() || crate::replay::is_active() {
+        return;
+    }
+    let call = call();
+    let mut w = Writer(Vec::new());
+    call.encode(&mut w);
+    let mut output = OUTPUT.lock().unwrap();
+    if let Some(file) = output.as_mut() {
+        if let Err(e) = file.write_all(&w.0) {
+            logger::error(&format!("recording stopped: {}", e));
+            *output = None;
+            RECORDING.store(false, Ordering::Release);
+        }
+    }
+}
+
+#[test]
+fn test_encode_decode() {
+    let calls = vec![
+        Call::SetFixedTimeStep(1.0 / 60.0),
+        Call::SetTexture {
+            handle: 0x1000,
+            width: 64,
+            height: 32,
+        },
+        Call::SetMeshIndexBuffer {
+            handle: 0x2000,
+            index_count: 3,
+            index_format: 1,
+            indices: vec![0, 1, 2],
+        },
+        Call::SetShaderDirectory(Some("shaders".to_string())),
+        Call::SetCameraMatrices(Some(([1.0; 16], [2.0; 16]))),
+        Call::SetTextureFeed(Some("/dev/shm/feed".to_string())),
+        Call::SetTextureFeed(None),
+        Call::SubmitVideoFrame {
+            format: 1,
+            width: 2,
+            height: 2,
+            color_space: 0,
+            range: 0,
+            planes: vec![(2, vec![16, 235, 16, 235]), (1, vec![128]), (1, vec![128])],
+        },
+        Call::SetVideoPlaneTextures {
+            luma: (0x3000, 2, 2),
+            chroma: (0x4000, 1, 1),
+        },
+        Call::StopVideo,
+        Call::LoadImageIntoTexture {
+            handle: 0x1000,
+            path: Some("image.png".to_string()),
+        },
+        Call::DebugDrawQuad([Vec3::new(1.0, 2.0, 3.0); 4], 0xFF00FF00),
+        Call::RenderEvent(1),
+    ];
+    let data = encode(&calls);
+    assert_eq!(decode(&data).unwrap(), calls);
+    assert!(decode(&data[..data.len() - 1]).is_err());
+    assert!(decode(b"RPLGREC\0\x02\0\0\0").is_err());
+}
@@ -1,7 +1,34 @@
+use crate::image::Image;
+use crate::math::Mat4;
+use crate::shader_assets::ShaderId;
 use unity_native_plugin::graphics::GfxRenderer;
 
 pub type Handle = *mut std::ffi::c_void;
 
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndexFormat {
+    UInt16 = 0,
+    UInt32 = 1,
+}
+
+impl IndexFormat {
+    pub fn size(&self) -> i32 {
+        match self {
+            IndexFormat::UInt16 => 2,
+            IndexFormat::UInt32 => 4,
+        }
+    }
+}
+
+pub fn index_format_from_i32(value: i32) -> Option<IndexFormat> {
+    match value {
+        0 => Some(IndexFormat::UInt16),
+        1 => Some(IndexFormat::UInt32),
+        _ => None,
+    }
+}
+
 pub trait TextureBuffer {
     unsafe fn ptr(&self) -> *const std::ffi::c_void;
     unsafe fn mut_ptr(&mut self) -> *mut std::ffi::c_void;
@@ -14,6 +41,31 @@ pub trait VertexBuffer {
     fn size(&self) -> i32;
 }
 
+pub trait IndexBuffer {
+    unsafe fn ptr(&self) -> *const std::ffi::c_void;
+    unsafe fn mut_ptr(&mut self) -> *mut std::ffi::c_void;
+    fn size(&self) -> i32;
+    fn format(&self) -> IndexFormat;
+}
+
+//  Vertex layouts are matched against shader input signatures by the shader-based backends.
+#[cfg(any(backend_d3d11, test))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VertexFormat {
+    Float32x2,
+    Float32x3,
+    Unorm8x4,
+}
+
+#[cfg(any(backend_d3d11, test))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct VertexElement {
+    pub semantic: &'static str,
+    pub semantic_index: u32,
+    pub format: VertexFormat,
+    pub offset: u32,
+}
+
 #[repr(C)]
 pub struct MyVertex {
     pub x: f32,
@@ -22,15 +74,89 @@ pub struct MyVertex {
     pub color: u32,
 }
 
-pub trait RenderAPI: Drop {
+#[cfg(any(backend_d3d11, test))]
+impl MyVertex {
+    pub const LAYOUT: [VertexElement; 2] = [
+        VertexElement {
+            semantic: "POSITION",
+            semantic_index: 0,
+            format: VertexFormat::Float32x3,
+            offset: 0,
+        },
+        VertexElement {
+            semantic: "COLOR",
+            semantic_index: 0,
+            format: VertexFormat::Unorm8x4,
+            offset: 12,
+        },
+    ];
+}
+
+fn simple_primitive_element_count(
+    primitive_count: i32,
+    elements_per_primitive: usize,
+    element_count: usize,
+) -> Option<usize> {
+    if primitive_count <= 0 {
+        return None;
+    }
+    let count = primitive_count as usize * elements_per_primitive;
+    if count > element_count {
+        None
+    } else {
+        Some(count)
+    }
+}
+
+pub fn simple_triangles_element_count(triangle_count: i32, element_count: usize) -> Option<usize> {
+    simple_primitive_element_count(triangle_count, 3, element_count)
+}
+
+pub fn simple_lines_element_count(line_count: i32, element_count: usize) -> Option<usize> {
+    simple_primitive_element_count(line_count, 2, element_count)
+}
+
+#[repr(C)]
+pub struct MyTexturedVertex {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub u: f32,
+    pub v: f32,
+}
+
+#[cfg(any(backend_d3d11, test))]
+impl MyTexturedVertex {
+    pub const LAYOUT: [VertexElement; 2] = [
+        VertexElement {
+            semantic: "POSITION",
+            semantic_index: 0,
+            format: VertexFormat::Float32x3,
+            offset: 0,
+        },
+        VertexElement {
+            semantic: "TEXCOORD",
+            semantic_index: 0,
+            format: VertexFormat::Float32x2,
+            offset: 12,
+        },
+    ];
+}
+
+pub trait RenderAPI {
     fn process_device_event(
         &mut self,
         event_type: unity_native_plugin::graphics::GfxDeviceEventType,
         interfaces: &unity_native_plugin::interface::UnityInterfaces,
     );
 
+    //  `Capability` bits of the effects this backend can render.
+    fn capabilities(&self) -> u32;
+
     fn get_uses_reverse_z(&self) -> bool;
 
+    fn get_clip_space_zero_to_one(&self) -> bool;
+
     fn draw_simple_triangles(
         &self,
         world_matrix: [f32; 16],
@@ -38,6 +164,33 @@ pub trait RenderAPI: Drop {
         vertices_float3_byte4: &[MyVertex],
     );
 
+    fn draw_simple_indexed_triangles(
+        &self,
+        world_matrix: [f32; 16],
+        triangle_count: i32,
+        vertices_float3_byte4: &[MyVertex],
+        indices: &[u32],
+    );
+
+    fn draw_simple_lines(
+        &self,
+        world_matrix: [f32; 16],
+        line_count: i32,
+        vertices_float3_byte4: &[MyVertex],
+    );
+
+    fn draw_textured_triangles(
+        &self,
+        world_matrix: [f32; 16],
+        triangle_count: i32,
+        vertices_float3_float2: &[MyTexturedVertex],
+        texture_handle: Handle,
+    );
+
+    //  Drops anything cached for the texture (e.g. its shader resource view). Called when a texture
+    //  is registered again, since Unity may have recreated it at the same address.
+    fn forget_texture(&self, texture_handle: Handle);
+
     fn begin_modify_texture(
         &self,
         texture_handle: Handle,
@@ -56,13 +209,60 @@ pub trait RenderAPI: Drop {
     fn begin_modify_vertex_buffer(&self, buffer_handle: Handle) -> Option<Box<dyn VertexBuffer>>;
 
     fn end_modify_vertex_buffer(&self, buffer_handle: Handle);
+
+    fn begin_modify_index_buffer(
+        &self,
+        buffer_handle: Handle,
+        index_format: IndexFormat,
+    ) -> Option<Box<dyn IndexBuffer>>;
+
+    fn end_modify_index_buffer(&self, buffer_handle: Handle);
+
+    //  RGBA8 copies of the bound render target and of a texture, for frame captures.
+    fn read_back_frame(&self) -> Option<Image>;
+
+    fn read_back_texture(
+        &self,
+        texture_handle: Handle,
+        texture_width: i32,
+        texture_height: i32,
+    ) -> Option<Image>;
+
+    fn reload_shaders(&mut self, shader_ids: &[ShaderId]);
+}
+
+pub fn view_projection_matrix(api: &dyn RenderAPI, view: &Mat4, projection: &Mat4) -> Mat4 {
+    projection
+        .gpu_projection(api.get_clip_space_zero_to_one(), api.get_uses_reverse_z())
+        .mul(view)
 }
 
 pub fn create_render_api(
     api_type: GfxRenderer,
 ) -> Option<Box<dyn RenderAPI>> {
     match api_type {
+        #[cfg(backend_d3d11)]
         GfxRenderer::D3D11 => Some(crate::render_api_d3d11::RenderAPID3D11::new()),
         _ => None
     }
 }
+
+//  Every UnityGfxRenderer the plugin's unity_native_plugin version defines, so values from C#
+//  (UnityGfxRenderer / GraphicsDeviceType) map to a renderer without transmuting unknown ones.
+const RENDERERS: [GfxRenderer; 8] = [
+    GfxRenderer::D3D11,
+    GfxRenderer::Null,
+    GfxRenderer::OpenGLES20,
+    GfxRenderer::OpenGLES30,
+    GfxRenderer::Metal,
+    GfxRenderer::OpenGLCore,
+    GfxRenderer::D3D12,
+    GfxRenderer::Vulkan,
+];
+
+pub fn renderer_from_i32(value: i32) -> Option<GfxRenderer> {
+    RENDERERS
+        .iter()
+        .copied()
+        .find(|&renderer| renderer as i32 == value)
+}
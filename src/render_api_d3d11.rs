@@ -1,6 +1,13 @@
+use crate::image::Image;
+use crate::logger;
 use crate::render_api;
 use crate::render_api::RenderAPI;
+use crate::shader_assets::{self, ShaderFormat, ShaderId, ShaderStage};
+use crate::shader_reflection;
 use crate::win_util;
+use crate::Capability;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use unity_native_plugin::graphics::GfxDeviceEventType;
 use unity_native_plugin::interface::UnityInterfaces;
 use winapi::_core::ffi::c_void;
@@ -9,6 +16,7 @@ use winapi::shared::minwindef::*;
 use winapi::shared::winerror::*;
 use winapi::um::d3d11::*;
 use winapi::um::d3dcommon::*;
+use winapi::um::d3dcompiler::*;
 use wio::com::ComPtr;
 
 pub struct TextureBuffer {
@@ -18,12 +26,8 @@ pub struct TextureBuffer {
 
 impl TextureBuffer {
     pub fn new(buffer_size: usize, row_pitch: i32) -> TextureBuffer {
-        let mut buf = Vec::<u8>::with_capacity(buffer_size);
-        unsafe {
-            buf.set_len(buffer_size);
-        }
         TextureBuffer {
-            buffer: buf,
+            buffer: vec![0; buffer_size],
             row_ptch: row_pitch,
         }
     }
@@ -50,7 +54,10 @@ pub struct VertexBuffer {
 
 impl VertexBuffer {
     pub fn new(buffer: *mut u8, buffer_size: i32) -> VertexBuffer {
-        VertexBuffer { buffer: buffer, buffer_size: buffer_size }
+        VertexBuffer {
+            buffer,
+            buffer_size,
+        }
     }
 }
 
@@ -68,9 +75,116 @@ impl render_api::VertexBuffer for VertexBuffer {
     }
 }
 
+pub struct IndexBuffer {
+    buffer: *mut u8,
+    buffer_size: i32,
+    format: render_api::IndexFormat,
+}
+
+impl IndexBuffer {
+    pub fn new(buffer: *mut u8, buffer_size: i32, format: render_api::IndexFormat) -> IndexBuffer {
+        IndexBuffer {
+            buffer,
+            buffer_size,
+            format,
+        }
+    }
+}
+
+impl render_api::IndexBuffer for IndexBuffer {
+    unsafe fn ptr(&self) -> *const std::ffi::c_void {
+        self.buffer as _
+    }
+
+    unsafe fn mut_ptr(&mut self) -> *mut std::ffi::c_void {
+        self.buffer as _
+    }
+
+    fn size(&self) -> i32 {
+        self.buffer_size
+    }
+
+    fn format(&self) -> render_api::IndexFormat {
+        self.format
+    }
+}
+
+struct RingBuffer {
+    buffer: Option<ComPtr<ID3D11Buffer>>,
+    bind_flags: D3D11_BIND_FLAG,
+    capacity: u32,
+    offset: u32,
+}
+
+impl RingBuffer {
+    const INITIAL_CAPACITY: u32 = 64 * 1024;
+    //  D3D11_REQ_RESOURCE_SIZE_IN_MEGABYTES_EXPRESSION_A_TERM
+    const MAX_CAPACITY: u32 = 128 * 1024 * 1024;
+
+    fn new(bind_flags: D3D11_BIND_FLAG) -> RingBuffer {
+        RingBuffer {
+            buffer: None,
+            bind_flags,
+            capacity: 0,
+            offset: 0,
+        }
+    }
+
+    unsafe fn upload(
+        &mut self,
+        device: &ComPtr<ID3D11Device>,
+        ctx: &ComPtr<ID3D11DeviceContext>,
+        data: *const c_void,
+        size: u32,
+    ) -> Result<(ComPtr<ID3D11Buffer>, u32), HRESULT> {
+        if size > RingBuffer::MAX_CAPACITY {
+            return Err(E_INVALIDARG);
+        }
+        if self.buffer.is_none() || size > self.capacity {
+            let capacity = size.next_power_of_two().clamp(
+                self.capacity.max(RingBuffer::INITIAL_CAPACITY),
+                RingBuffer::MAX_CAPACITY,
+            );
+            let desc = D3D11_BUFFER_DESC {
+                Usage: D3D11_USAGE_DYNAMIC,
+                ByteWidth: capacity,
+                BindFlags: self.bind_flags,
+                CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
+                ..std::mem::zeroed()
+            };
+            self.buffer = Some(win_util::get_comptr_with_result(|ret| {
+                device.CreateBuffer(&desc, std::ptr::null(), ret)
+            })?);
+            self.capacity = capacity;
+            self.offset = capacity;
+        }
+
+        let buffer = self.buffer.as_ref().unwrap().clone();
+        let map_type = if size > self.capacity - self.offset.min(self.capacity) {
+            self.offset = 0;
+            D3D11_MAP_WRITE_DISCARD
+        } else {
+            D3D11_MAP_WRITE_NO_OVERWRITE
+        };
+        let mut mapped = std::mem::zeroed::<D3D11_MAPPED_SUBRESOURCE>();
+        win_util::check_hr(ctx.Map(buffer.as_raw() as _, 0, map_type, 0, &mut mapped))?;
+        std::ptr::copy_nonoverlapping(
+            data as *const u8,
+            (mapped.pData as *mut u8).offset(self.offset as isize),
+            size as usize,
+        );
+        ctx.Unmap(buffer.as_raw() as _, 0);
+
+        let offset = self.offset;
+        self.offset = self.offset.saturating_add((size + 15) & !15);
+        Ok((buffer, offset))
+    }
+}
+
 pub struct RenderAPID3D11 {
     device: Option<ComPtr<ID3D11Device>>,
-    vb: Option<ComPtr<ID3D11Buffer>>,
+    vb: RefCell<RingBuffer>,
+    ib: RefCell<RingBuffer>,
     cb: Option<ComPtr<ID3D11Buffer>>,
     vertex_shader: Option<ComPtr<ID3D11VertexShader>>,
     pixel_shader: Option<ComPtr<ID3D11PixelShader>>,
@@ -78,6 +192,13 @@ pub struct RenderAPID3D11 {
     rasterizer_state: Option<ComPtr<ID3D11RasterizerState>>,
     blend_state: Option<ComPtr<ID3D11BlendState>>,
     depth_state: Option<ComPtr<ID3D11DepthStencilState>>,
+    textured_vertex_shader: Option<ComPtr<ID3D11VertexShader>>,
+    textured_pixel_shader: Option<ComPtr<ID3D11PixelShader>>,
+    textured_input_layout: Option<ComPtr<ID3D11InputLayout>>,
+    sampler_state: Option<ComPtr<ID3D11SamplerState>>,
+    alpha_blend_state: Option<ComPtr<ID3D11BlendState>>,
+    //  Shader resource views of drawn textures, by texture address.
+    texture_views: RefCell<HashMap<usize, ComPtr<ID3D11ShaderResourceView>>>,
 }
 
 impl Drop for RenderAPID3D11 {
@@ -96,8 +217,8 @@ impl render_api::RenderAPI for RenderAPID3D11 {
                 self.device =
                     unsafe { Some(ComPtr::from_raw(intf.unwrap().device() as *mut ID3D11Device)) };
                 unsafe { self.device.as_ref().unwrap().AddRef() };
-                if let Err(_) = self.create_resources() {
-                    //  TODO:
+                if let Err(hr) = self.create_resources() {
+                    logger::error(&format!("failed to create D3D11 resources (0x{:08X})", hr));
                 }
             }
             GfxDeviceEventType::Shutdown => self.release_resources(),
@@ -105,6 +226,22 @@ impl render_api::RenderAPI for RenderAPID3D11 {
         }
     }
 
+    fn capabilities(&self) -> u32 {
+        [
+            Capability::ColoredTriangle,
+            Capability::TexturedQuad,
+            Capability::ModifyTexture,
+            Capability::ModifyVertexBuffer,
+            Capability::ModifyIndexBuffer,
+            Capability::DebugDraw,
+            Capability::CameraMatrices,
+            Capability::ShaderHotReload,
+            Capability::FrameCapture,
+        ]
+        .iter()
+        .fold(0, |bits, &capability| bits | capability as u32)
+    }
+
     fn get_uses_reverse_z(&self) -> bool {
         unsafe {
             if let Some(device) = self.device.as_ref() {
@@ -115,67 +252,185 @@ impl render_api::RenderAPI for RenderAPID3D11 {
         }
     }
 
+    fn get_clip_space_zero_to_one(&self) -> bool {
+        true
+    }
+
     fn draw_simple_triangles(
         &self,
         world_matrix: [f32; 16],
         triangle_count: i32,
         vertices_float3_byte4: &[render_api::MyVertex],
     ) {
+        let vertex_count = match render_api::simple_triangles_element_count(
+            triangle_count,
+            vertices_float3_byte4.len(),
+        ) {
+            Some(count) => count,
+            None => return,
+        };
         if let Some(device) = &self.device {
             unsafe {
                 let ctx = win_util::get_comptr(|ret| device.GetImmediateContext(ret));
+                if self
+                    .setup_simple_draw(
+                        device,
+                        &ctx,
+                        world_matrix,
+                        &vertices_float3_byte4[..vertex_count],
+                        D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+                    )
+                    .is_ok()
+                {
+                    ctx.Draw(vertex_count as _, 0);
+                }
+            }
+        }
+    }
 
-                ctx.OMSetDepthStencilState(self.depth_state.as_ref().unwrap().as_raw(), 0);
-                ctx.RSSetState(self.rasterizer_state.as_ref().unwrap().as_raw());
-                ctx.OMSetBlendState(
-                    self.blend_state.as_ref().unwrap().as_raw(),
-                    &[1.0, 1.0, 1.0, 1.0],
-                    0xFFFFFFFF,
-                );
-
-                ctx.UpdateSubresource(
-                    self.cb.as_ref().unwrap().as_raw() as _,
-                    0,
-                    std::ptr::null(),
-                    world_matrix.as_ptr() as _,
-                    64,
-                    0,
-                );
+    fn draw_simple_indexed_triangles(
+        &self,
+        world_matrix: [f32; 16],
+        triangle_count: i32,
+        vertices_float3_byte4: &[render_api::MyVertex],
+        indices: &[u32],
+    ) {
+        let index_count =
+            match render_api::simple_triangles_element_count(triangle_count, indices.len()) {
+                Some(count) => count,
+                None => return,
+            };
+        if vertices_float3_byte4.is_empty() {
+            return;
+        }
+        if let Some(device) = &self.device {
+            unsafe {
+                let ctx = win_util::get_comptr(|ret| device.GetImmediateContext(ret));
+                if self
+                    .setup_simple_draw(
+                        device,
+                        &ctx,
+                        world_matrix,
+                        vertices_float3_byte4,
+                        D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+                    )
+                    .is_err()
+                {
+                    return;
+                }
+                let indices = &indices[..index_count];
+                let (buffer, offset) = match self.ib.borrow_mut().upload(
+                    device,
+                    &ctx,
+                    indices.as_ptr() as _,
+                    std::mem::size_of_val(indices) as u32,
+                ) {
+                    Ok(ret) => ret,
+                    Err(_) => return,
+                };
+                ctx.IASetIndexBuffer(buffer.as_raw(), DXGI_FORMAT_R32_UINT, offset);
+                ctx.DrawIndexed(index_count as _, 0, 0);
+            }
+        }
+    }
 
-                let buffers = [self.cb.as_ref().unwrap().as_raw()];
-                ctx.VSSetConstantBuffers(0, buffers.len() as u32, buffers.as_ptr());
-                ctx.VSSetShader(
-                    self.vertex_shader.as_ref().unwrap().as_raw(),
-                    std::ptr::null(),
-                    0,
-                );
-                ctx.PSSetShader(
-                    self.pixel_shader.as_ref().unwrap().as_raw(),
-                    std::ptr::null(),
-                    0,
-                );
+    fn draw_simple_lines(
+        &self,
+        world_matrix: [f32; 16],
+        line_count: i32,
+        vertices_float3_byte4: &[render_api::MyVertex],
+    ) {
+        let vertex_count =
+            match render_api::simple_lines_element_count(line_count, vertices_float3_byte4.len()) {
+                Some(count) => count,
+                None => return,
+            };
+        if let Some(device) = &self.device {
+            unsafe {
+                let ctx = win_util::get_comptr(|ret| device.GetImmediateContext(ret));
+                if self
+                    .setup_simple_draw(
+                        device,
+                        &ctx,
+                        world_matrix,
+                        &vertices_float3_byte4[..vertex_count],
+                        D3D11_PRIMITIVE_TOPOLOGY_LINELIST,
+                    )
+                    .is_ok()
+                {
+                    ctx.Draw(vertex_count as _, 0);
+                }
+            }
+        }
+    }
 
-                let vertex_size = 12 + 4;
-                ctx.UpdateSubresource(
-                    self.vb.as_ref().unwrap().as_raw() as _,
-                    0,
-                    std::ptr::null(),
-                    vertices_float3_byte4.as_ptr() as _,
-                    (triangle_count * 3 * vertex_size) as u32,
-                    0,
-                );
+    fn draw_textured_triangles(
+        &self,
+        world_matrix: [f32; 16],
+        triangle_count: i32,
+        vertices_float3_float2: &[render_api::MyTexturedVertex],
+        texture_handle: render_api::Handle,
+    ) {
+        let vertex_count = match render_api::simple_triangles_element_count(
+            triangle_count,
+            vertices_float3_float2.len(),
+        ) {
+            Some(count) => count,
+            None => return,
+        };
+        if texture_handle.is_null() {
+            return;
+        }
+        if let Some(device) = &self.device {
+            unsafe {
+                let ctx = win_util::get_comptr(|ret| device.GetImmediateContext(ret));
+                let srv = match self.texture_view(device, texture_handle) {
+                    Ok(srv) => srv,
+                    Err(_) => return,
+                };
+                let (vertex_shader, pixel_shader, input_layout, sampler_state) = match (
+                    &self.textured_vertex_shader,
+                    &self.textured_pixel_shader,
+                    &self.textured_input_layout,
+                    &self.sampler_state,
+                ) {
+                    (Some(vs), Some(ps), Some(layout), Some(sampler)) => (vs, ps, layout, sampler),
+                    _ => return,
+                };
+                if self
+                    .set_common_state(&ctx, world_matrix, self.alpha_blend_state.as_ref())
+                    .is_err()
+                {
+                    return;
+                }
+                ctx.VSSetShader(vertex_shader.as_raw(), std::ptr::null(), 0);
+                ctx.PSSetShader(pixel_shader.as_raw(), std::ptr::null(), 0);
+                let views = [srv.as_raw()];
+                ctx.PSSetShaderResources(0, views.len() as u32, views.as_ptr());
+                let samplers = [sampler_state.as_raw()];
+                ctx.PSSetSamplers(0, samplers.len() as u32, samplers.as_ptr());
 
-                ctx.IASetInputLayout(self.input_layout.as_ref().unwrap().as_raw());
+                ctx.IASetInputLayout(input_layout.as_raw());
                 ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-                let stride = vertex_size as u32;
-                let offset = 0;
-                let buffers = [self.vb.as_ref().unwrap().as_raw()];
-                ctx.IASetVertexBuffers(0, buffers.len() as u32, buffers.as_ptr(), &stride, &offset);
-                ctx.Draw((triangle_count * 3) as _, 0);
+                if self
+                    .bind_vertices(device, &ctx, &vertices_float3_float2[..vertex_count])
+                    .is_ok()
+                {
+                    ctx.Draw(vertex_count as _, 0);
+                }
+
+                let views = [std::ptr::null_mut()];
+                ctx.PSSetShaderResources(0, views.len() as u32, views.as_ptr());
             }
         }
     }
 
+    fn forget_texture(&self, texture_handle: render_api::Handle) {
+        self.texture_views
+            .borrow_mut()
+            .remove(&(texture_handle as usize));
+    }
+
     fn begin_modify_texture(
         &self,
         _: *mut c_void,
@@ -241,13 +496,87 @@ impl render_api::RenderAPI for RenderAPID3D11 {
             }
         }
     }
+
+    fn begin_modify_index_buffer(
+        &self,
+        buffer_handle: *mut c_void,
+        index_format: render_api::IndexFormat,
+    ) -> Option<Box<dyn render_api::IndexBuffer>> {
+        if let Some(device) = &self.device {
+            unsafe {
+                let d3dbuf = buffer_handle as *mut ID3D11Buffer;
+
+                let mut desc = std::mem::zeroed::<D3D11_BUFFER_DESC>();
+                (*d3dbuf).GetDesc(&mut desc);
+                let ctx = win_util::get_comptr(|ret| device.GetImmediateContext(ret));
+                let mut mapped = std::mem::zeroed::<D3D11_MAPPED_SUBRESOURCE>();
+                ctx.Map(d3dbuf as _, 0, D3D11_MAP_WRITE_DISCARD, 0, &mut mapped);
+                Some(Box::new(IndexBuffer::new(
+                    mapped.pData as _,
+                    desc.ByteWidth as _,
+                    index_format,
+                )))
+            }
+        } else {
+            None
+        }
+    }
+
+    fn end_modify_index_buffer(&self, buffer_handle: *mut c_void) {
+        self.end_modify_vertex_buffer(buffer_handle);
+    }
+
+    fn read_back_frame(&self) -> Option<Image> {
+        let device = self.device.as_ref()?;
+        unsafe {
+            let ctx = win_util::get_comptr(|ret| device.GetImmediateContext(ret));
+            let mut rtv = std::ptr::null_mut();
+            ctx.OMGetRenderTargets(1, &mut rtv, std::ptr::null_mut());
+            if rtv.is_null() {
+                return None;
+            }
+            let rtv = ComPtr::<ID3D11RenderTargetView>::from_raw(rtv);
+            let resource = win_util::get_comptr(|ret| rtv.GetResource(ret));
+            let texture = resource.cast::<ID3D11Texture2D>().ok()?;
+            RenderAPID3D11::read_back_texture2d(device, &ctx, texture.as_raw())
+        }
+    }
+
+    fn read_back_texture(&self, texture_handle: *mut c_void, _: i32, _: i32) -> Option<Image> {
+        if texture_handle.is_null() {
+            return None;
+        }
+        let device = self.device.as_ref()?;
+        unsafe {
+            let ctx = win_util::get_comptr(|ret| device.GetImmediateContext(ret));
+            RenderAPID3D11::read_back_texture2d(device, &ctx, texture_handle as _)
+        }
+    }
+
+    fn reload_shaders(&mut self, shader_ids: &[ShaderId]) {
+        let device = match &self.device {
+            Some(device) => device.clone(),
+            None => return,
+        };
+        for &id in shader_ids {
+            match unsafe { self.create_shader(&device, id) } {
+                Ok(_) => logger::info(&format!("reloaded shader {}", id.name())),
+                Err(hr) => logger::error(&format!(
+                    "failed to reload shader {} (0x{:08X}), keeping the previous one",
+                    id.name(),
+                    hr
+                )),
+            }
+        }
+    }
 }
 
 impl RenderAPID3D11 {
     pub fn new() -> Box<RenderAPID3D11> {
         Box::new(RenderAPID3D11 {
             device: None,
-            vb: None,
+            vb: RefCell::new(RingBuffer::new(D3D11_BIND_VERTEX_BUFFER)),
+            ib: RefCell::new(RingBuffer::new(D3D11_BIND_INDEX_BUFFER)),
             cb: None,
             vertex_shader: None,
             pixel_shader: None,
@@ -255,167 +584,459 @@ impl RenderAPID3D11 {
             rasterizer_state: None,
             blend_state: None,
             depth_state: None,
+            textured_vertex_shader: None,
+            textured_pixel_shader: None,
+            textured_input_layout: None,
+            sampler_state: None,
+            alpha_blend_state: None,
+            texture_views: RefCell::new(HashMap::new()),
+        })
+    }
+
+    //  Draws are skipped while a state or shader they need is missing; the failure to create it was
+    //  logged then, and a shader reload can still bring the shaders back.
+    unsafe fn set_common_state(
+        &self,
+        ctx: &ComPtr<ID3D11DeviceContext>,
+        world_matrix: [f32; 16],
+        blend_state: Option<&ComPtr<ID3D11BlendState>>,
+    ) -> Result<(), HRESULT> {
+        let (depth_state, rasterizer_state, blend_state, cb) = match (
+            &self.depth_state,
+            &self.rasterizer_state,
+            blend_state,
+            &self.cb,
+        ) {
+            (Some(depth), Some(rasterizer), Some(blend), Some(cb)) => {
+                (depth, rasterizer, blend, cb)
+            }
+            _ => return Err(E_FAIL),
+        };
+        ctx.OMSetDepthStencilState(depth_state.as_raw(), 0);
+        ctx.RSSetState(rasterizer_state.as_raw());
+        ctx.OMSetBlendState(blend_state.as_raw(), &[1.0, 1.0, 1.0, 1.0], 0xFFFFFFFF);
+
+        ctx.UpdateSubresource(
+            cb.as_raw() as _,
+            0,
+            std::ptr::null(),
+            world_matrix.as_ptr() as _,
+            64,
+            0,
+        );
+
+        let buffers = [cb.as_raw()];
+        ctx.VSSetConstantBuffers(0, buffers.len() as u32, buffers.as_ptr());
+        Ok(())
+    }
+
+    unsafe fn bind_vertices<T>(
+        &self,
+        device: &ComPtr<ID3D11Device>,
+        ctx: &ComPtr<ID3D11DeviceContext>,
+        vertices: &[T],
+    ) -> Result<(), HRESULT> {
+        let stride = std::mem::size_of::<T>() as u32;
+        let (vb, offset) = self.vb.borrow_mut().upload(
+            device,
+            ctx,
+            vertices.as_ptr() as _,
+            vertices.len() as u32 * stride,
+        )?;
+        let buffers = [vb.as_raw()];
+        ctx.IASetVertexBuffers(0, buffers.len() as u32, buffers.as_ptr(), &stride, &offset);
+        Ok(())
+    }
+
+    unsafe fn setup_simple_draw(
+        &self,
+        device: &ComPtr<ID3D11Device>,
+        ctx: &ComPtr<ID3D11DeviceContext>,
+        world_matrix: [f32; 16],
+        vertices_float3_byte4: &[render_api::MyVertex],
+        topology: D3D11_PRIMITIVE_TOPOLOGY,
+    ) -> Result<(), HRESULT> {
+        let (vertex_shader, pixel_shader, input_layout) =
+            match (&self.vertex_shader, &self.pixel_shader, &self.input_layout) {
+                (Some(vs), Some(ps), Some(layout)) => (vs, ps, layout),
+                _ => return Err(E_FAIL),
+            };
+        self.set_common_state(ctx, world_matrix, self.blend_state.as_ref())?;
+        ctx.VSSetShader(vertex_shader.as_raw(), std::ptr::null(), 0);
+        ctx.PSSetShader(pixel_shader.as_raw(), std::ptr::null(), 0);
+
+        ctx.IASetInputLayout(input_layout.as_raw());
+        ctx.IASetPrimitiveTopology(topology);
+        self.bind_vertices(device, ctx, vertices_float3_byte4)
+    }
+
+    unsafe fn texture_view(
+        &self,
+        device: &ComPtr<ID3D11Device>,
+        texture_handle: render_api::Handle,
+    ) -> Result<ComPtr<ID3D11ShaderResourceView>, HRESULT> {
+        let mut views = self.texture_views.borrow_mut();
+        if let Some(srv) = views.get(&(texture_handle as usize)) {
+            return Ok(srv.clone());
+        }
+        let srv = RenderAPID3D11::create_texture_view(device, texture_handle)?;
+        views.insert(texture_handle as usize, srv.clone());
+        Ok(srv)
+    }
+
+    unsafe fn create_texture_view(
+        device: &ComPtr<ID3D11Device>,
+        texture_handle: render_api::Handle,
+    ) -> Result<ComPtr<ID3D11ShaderResourceView>, HRESULT> {
+        let texture = texture_handle as *mut ID3D11Texture2D;
+        let mut tex_desc = std::mem::zeroed::<D3D11_TEXTURE2D_DESC>();
+        (*texture).GetDesc(&mut tex_desc);
+
+        let mut desc = std::mem::zeroed::<D3D11_SHADER_RESOURCE_VIEW_DESC>();
+        desc.Format = match tex_desc.Format {
+            DXGI_FORMAT_R8G8B8A8_TYPELESS => DXGI_FORMAT_R8G8B8A8_UNORM,
+            DXGI_FORMAT_B8G8R8A8_TYPELESS => DXGI_FORMAT_B8G8R8A8_UNORM,
+            DXGI_FORMAT_R16G16B16A16_TYPELESS => DXGI_FORMAT_R16G16B16A16_FLOAT,
+            format => format,
+        };
+        desc.ViewDimension = D3D11_SRV_DIMENSION_TEXTURE2D;
+        desc.u.Texture2D_mut().MipLevels = tex_desc.MipLevels;
+
+        win_util::get_comptr_with_result(|ret| {
+            device.CreateShaderResourceView(texture as _, &desc, ret)
+        })
+    }
+
+    //  Copies the first mip of an 8-bit RGBA/BGRA texture through a staging texture. This stalls
+    //  until the GPU has finished the frame so far, which is fine for captures.
+    unsafe fn read_back_texture2d(
+        device: &ComPtr<ID3D11Device>,
+        ctx: &ComPtr<ID3D11DeviceContext>,
+        texture: *mut ID3D11Texture2D,
+    ) -> Option<Image> {
+        let mut desc = std::mem::zeroed::<D3D11_TEXTURE2D_DESC>();
+        (*texture).GetDesc(&mut desc);
+        let bgra = match desc.Format {
+            DXGI_FORMAT_R8G8B8A8_TYPELESS
+            | DXGI_FORMAT_R8G8B8A8_UNORM
+            | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => false,
+            DXGI_FORMAT_B8G8R8A8_TYPELESS
+            | DXGI_FORMAT_B8G8R8A8_UNORM
+            | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => true,
+            format => {
+                logger::warning(&format!("cannot read back texture format {}", format));
+                return None;
+            }
+        };
+        if desc.SampleDesc.Count > 1 {
+            logger::warning("cannot read back a multisampled texture");
+            return None;
+        }
+
+        let staging_desc = D3D11_TEXTURE2D_DESC {
+            MipLevels: 1,
+            ArraySize: 1,
+            Usage: D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            CPUAccessFlags: D3D11_CPU_ACCESS_READ,
+            MiscFlags: 0,
+            ..desc
+        };
+        let staging = win_util::get_comptr_with_result(|ret| {
+            device.CreateTexture2D(&staging_desc, std::ptr::null(), ret)
+        })
+        .ok()?;
+        ctx.CopySubresourceRegion(
+            staging.as_raw() as _,
+            0,
+            0,
+            0,
+            0,
+            texture as _,
+            0,
+            std::ptr::null(),
+        );
+
+        let mut mapped = std::mem::zeroed::<D3D11_MAPPED_SUBRESOURCE>();
+        win_util::check_hr(ctx.Map(staging.as_raw() as _, 0, D3D11_MAP_READ, 0, &mut mapped))
+            .ok()?;
+        let mut image = Image::new(desc.Width, desc.Height);
+        let row_pitch = image.row_pitch();
+        for (y, row) in image.pixels.chunks_exact_mut(row_pitch).enumerate() {
+            let src = (mapped.pData as *const u8).add(y * mapped.RowPitch as usize);
+            row.copy_from_slice(std::slice::from_raw_parts(src, row_pitch));
+            if bgra {
+                for pixel in row.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+        }
+        ctx.Unmap(staging.as_raw() as _, 0);
+        Some(image)
+    }
+
+    unsafe fn compile_shader(
+        source: &str,
+        entry_point: &str,
+        target: &str,
+    ) -> Result<ComPtr<ID3DBlob>, HRESULT> {
+        let entry_point = std::ffi::CString::new(entry_point).unwrap();
+        let target = std::ffi::CString::new(target).unwrap();
+        let mut errors: *mut ID3DBlob = std::ptr::null_mut();
+        let ret = win_util::get_comptr_with_result(|ret| {
+            D3DCompile(
+                source.as_ptr() as _,
+                source.len(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                entry_point.as_ptr(),
+                target.as_ptr(),
+                D3DCOMPILE_OPTIMIZATION_LEVEL3,
+                0,
+                ret,
+                &mut errors,
+            )
+        });
+        if !errors.is_null() {
+            let message = std::slice::from_raw_parts(
+                (*errors).GetBufferPointer() as *const u8,
+                (*errors).GetBufferSize(),
+            );
+            if ret.is_err() {
+                logger::error(&String::from_utf8_lossy(message));
+            }
+            (*errors).Release();
+        }
+        ret
+    }
+
+    unsafe fn load_shader_code(id: ShaderId) -> Result<Vec<u8>, HRESULT> {
+        let blob =
+            shader_assets::load(id, &[ShaderFormat::Dxbc, ShaderFormat::Hlsl]).map_err(|e| {
+                logger::error(&format!("failed to load shader {}: {}", id.name(), e));
+                E_FAIL
+            })?;
+        if let Some(path) = &blob.path {
+            logger::info(&format!(
+                "using shader {} from {}",
+                blob.id.name(),
+                path.display()
+            ));
+        }
+        if blob.format != ShaderFormat::Hlsl {
+            return Ok(blob.code);
+        }
+        let target = match id.stage() {
+            ShaderStage::Vertex => "vs_4_0_level_9_3",
+            ShaderStage::Pixel => "ps_4_0_level_9_3",
+        };
+        let source = std::str::from_utf8(&blob.code).map_err(|_| E_FAIL)?;
+        let code = RenderAPID3D11::compile_shader(source, id.entry_point(), target)?;
+        let code =
+            std::slice::from_raw_parts(code.GetBufferPointer() as *const u8, code.GetBufferSize());
+        Ok(code.to_vec())
+    }
+
+    unsafe fn create_vertex_shader(
+        device: &ComPtr<ID3D11Device>,
+        id: ShaderId,
+        vertex_layout: &[render_api::VertexElement],
+    ) -> Result<(ComPtr<ID3D11VertexShader>, ComPtr<ID3D11InputLayout>), HRESULT> {
+        let code = RenderAPID3D11::load_shader_code(id)?;
+        let layout = shader_reflection::reflect(ShaderFormat::Dxbc, &code)
+            .and_then(|reflection| {
+                shader_reflection::validate_constant_buffer(&reflection, 0, 64)?;
+                shader_reflection::derive_input_layout(&reflection, vertex_layout)
+            })
+            .map_err(|e| {
+                logger::error(&format!("shader {} does not match: {}", id.name(), e));
+                E_INVALIDARG
+            })?;
+        let semantic_names = layout
+            .iter()
+            .map(|element| std::ffi::CString::new(element.semantic).unwrap())
+            .collect::<Vec<_>>();
+        let layout = layout
+            .iter()
+            .zip(&semantic_names)
+            .map(|(element, semantic_name)| D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: semantic_name.as_ptr(),
+                SemanticIndex: element.semantic_index,
+                Format: dxgi_vertex_format(element.format),
+                InputSlot: 0,
+                AlignedByteOffset: element.offset,
+                InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+                InstanceDataStepRate: 0,
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_shader = win_util::get_comptr_with_result(|ret| {
+            device.CreateVertexShader(code.as_ptr() as _, code.len(), std::ptr::null_mut(), ret)
+        })?;
+        let input_layout = win_util::get_comptr_with_result(|ret| {
+            device.CreateInputLayout(
+                layout.as_ptr(),
+                layout.len() as _,
+                code.as_ptr() as _,
+                code.len(),
+                ret,
+            )
+        })?;
+        Ok((vertex_shader, input_layout))
+    }
+
+    unsafe fn create_pixel_shader(
+        device: &ComPtr<ID3D11Device>,
+        id: ShaderId,
+    ) -> Result<ComPtr<ID3D11PixelShader>, HRESULT> {
+        let code = RenderAPID3D11::load_shader_code(id)?;
+        win_util::get_comptr_with_result(|ret| {
+            device.CreatePixelShader(code.as_ptr() as _, code.len(), std::ptr::null_mut(), ret)
         })
     }
 
+    unsafe fn create_shader(
+        &mut self,
+        device: &ComPtr<ID3D11Device>,
+        id: ShaderId,
+    ) -> Result<(), HRESULT> {
+        match id {
+            ShaderId::SimpleVertex => {
+                let (vertex_shader, input_layout) = RenderAPID3D11::create_vertex_shader(
+                    device,
+                    id,
+                    &render_api::MyVertex::LAYOUT,
+                )?;
+                self.vertex_shader = Some(vertex_shader);
+                self.input_layout = Some(input_layout);
+            }
+            ShaderId::SimplePixel => {
+                self.pixel_shader = Some(RenderAPID3D11::create_pixel_shader(device, id)?);
+            }
+            ShaderId::TexturedVertex => {
+                let (vertex_shader, input_layout) = RenderAPID3D11::create_vertex_shader(
+                    device,
+                    id,
+                    &render_api::MyTexturedVertex::LAYOUT,
+                )?;
+                self.textured_vertex_shader = Some(vertex_shader);
+                self.textured_input_layout = Some(input_layout);
+            }
+            ShaderId::TexturedPixel => {
+                self.textured_pixel_shader = Some(RenderAPID3D11::create_pixel_shader(device, id)?);
+            }
+        }
+        Ok(())
+    }
+
+    //  The fixed-function states do not depend on the shaders, so a shader that fails to load only
+    //  disables the draws that use it until it is reloaded.
     fn create_resources(&mut self) -> Result<(), HRESULT> {
-        if let Some(device) = &self.device {
-            unsafe {
-                let desc = D3D11_BUFFER_DESC {
-                    Usage: D3D11_USAGE_DEFAULT,
-                    ByteWidth: 1024,
-                    BindFlags: D3D11_BIND_VERTEX_BUFFER,
-                    ..std::mem::zeroed()
-                };
-                self.vb = Some(win_util::get_comptr_with_result(|ret| {
-                    device.CreateBuffer(&desc, std::ptr::null(), ret)
-                })?);
-
-                let desc = D3D11_BUFFER_DESC {
-                    Usage: D3D11_USAGE_DEFAULT,
-                    ByteWidth: 64,
-                    BindFlags: D3D11_BIND_CONSTANT_BUFFER,
-                    CPUAccessFlags: 0,
-                    ..std::mem::zeroed()
-                };
-                self.cb = Some(win_util::get_comptr_with_result(|ret| {
-                    device.CreateBuffer(&desc, std::ptr::null(), ret)
-                })?);
-
-                self.vertex_shader = Some(win_util::get_comptr_with_result(|ret| {
-                    device.CreateVertexShader(
-                        VERTEX_SHADER_CODE.as_ptr() as _,
-                        VERTEX_SHADER_CODE.len(),
-                        std::ptr::null_mut(),
-                        ret,
-                    )
-                })?);
-
-                self.pixel_shader = Some(win_util::get_comptr_with_result(|ret| {
-                    device.CreatePixelShader(
-                        PIXEL_SHADER_CODE.as_ptr() as _,
-                        PIXEL_SHADER_CODE.len(),
-                        std::ptr::null_mut(),
-                        ret,
-                    )
-                })?);
-
-                if (&self.vertex_shader).is_some() {
-                    let desc = [
-                        D3D11_INPUT_ELEMENT_DESC {
-                            SemanticName: "POSITION\0".as_ptr() as _,
-                            SemanticIndex: 0,
-                            Format: DXGI_FORMAT_R32G32B32_FLOAT,
-                            InputSlot: 0,
-                            AlignedByteOffset: 0,
-                            InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
-                            InstanceDataStepRate: 0,
-                        },
-                        D3D11_INPUT_ELEMENT_DESC {
-                            SemanticName: "COLOR\0".as_ptr() as _,
-                            SemanticIndex: 0,
-                            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
-                            InputSlot: 0,
-                            AlignedByteOffset: 12,
-                            InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
-                            InstanceDataStepRate: 0,
-                        },
-                    ];
-
-                    self.input_layout = Some(win_util::get_comptr_with_result(|ret| {
-                        device.CreateInputLayout(
-                            desc.as_ptr(),
-                            desc.len() as _,
-                            VERTEX_SHADER_CODE.as_ptr() as _,
-                            VERTEX_SHADER_CODE.len(),
-                            ret,
-                        )
-                    })?);
-
-                    let desc = D3D11_RASTERIZER_DESC {
-                        FillMode: D3D11_FILL_SOLID,
-                        CullMode: D3D11_CULL_NONE,
-                        DepthClipEnable: TRUE,
-                        ..std::mem::zeroed()
-                    };
-                    self.rasterizer_state = Some(win_util::get_comptr_with_result(|ret| {
-                        device.CreateRasterizerState(&desc, ret)
-                    })?);
-
-                    let desc = D3D11_DEPTH_STENCIL_DESC {
-                        DepthEnable: TRUE,
-                        DepthWriteMask: D3D11_DEPTH_WRITE_MASK_ZERO,
-                        DepthFunc: if self.get_uses_reverse_z() {
-                            D3D11_COMPARISON_GREATER_EQUAL
-                        } else {
-                            D3D11_COMPARISON_LESS_EQUAL
-                        },
-                        ..std::mem::zeroed()
-                    };
-                    self.depth_state = Some(win_util::get_comptr_with_result(|ret| {
-                        device.CreateDepthStencilState(&desc, ret)
-                    })?);
-
-                    let mut desc: D3D11_BLEND_DESC = std::mem::zeroed();
-                    desc.RenderTarget[0] = D3D11_RENDER_TARGET_BLEND_DESC {
-                        BlendEnable: FALSE,
-                        RenderTargetWriteMask: 0xf,
-                        ..std::mem::zeroed()
-                    };
-                    self.blend_state = Some(win_util::get_comptr_with_result(|ret| {
-                        device.CreateBlendState(&desc, ret)
-                    })?);
+        let device = match &self.device {
+            Some(device) => device.clone(),
+            None => return Err(S_FALSE),
+        };
+        unsafe {
+            *self.vb.borrow_mut() = RingBuffer::new(D3D11_BIND_VERTEX_BUFFER);
+            *self.ib.borrow_mut() = RingBuffer::new(D3D11_BIND_INDEX_BUFFER);
+
+            let desc = D3D11_BUFFER_DESC {
+                Usage: D3D11_USAGE_DEFAULT,
+                ByteWidth: 64,
+                BindFlags: D3D11_BIND_CONSTANT_BUFFER,
+                CPUAccessFlags: 0,
+                ..std::mem::zeroed()
+            };
+            self.cb = Some(win_util::get_comptr_with_result(|ret| {
+                device.CreateBuffer(&desc, std::ptr::null(), ret)
+            })?);
+
+            let desc = D3D11_RASTERIZER_DESC {
+                FillMode: D3D11_FILL_SOLID,
+                CullMode: D3D11_CULL_NONE,
+                DepthClipEnable: TRUE,
+                ..std::mem::zeroed()
+            };
+            self.rasterizer_state = Some(win_util::get_comptr_with_result(|ret| {
+                device.CreateRasterizerState(&desc, ret)
+            })?);
+
+            let desc = D3D11_DEPTH_STENCIL_DESC {
+                DepthEnable: TRUE,
+                DepthWriteMask: D3D11_DEPTH_WRITE_MASK_ZERO,
+                DepthFunc: if self.get_uses_reverse_z() {
+                    D3D11_COMPARISON_GREATER_EQUAL
+                } else {
+                    D3D11_COMPARISON_LESS_EQUAL
+                },
+                ..std::mem::zeroed()
+            };
+            self.depth_state = Some(win_util::get_comptr_with_result(|ret| {
+                device.CreateDepthStencilState(&desc, ret)
+            })?);
+
+            let mut desc: D3D11_BLEND_DESC = std::mem::zeroed();
+            desc.RenderTarget[0] = D3D11_RENDER_TARGET_BLEND_DESC {
+                BlendEnable: FALSE,
+                RenderTargetWriteMask: 0xf,
+                ..std::mem::zeroed()
+            };
+            self.blend_state = Some(win_util::get_comptr_with_result(|ret| {
+                device.CreateBlendState(&desc, ret)
+            })?);
+
+            let desc = D3D11_SAMPLER_DESC {
+                Filter: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+                AddressU: D3D11_TEXTURE_ADDRESS_CLAMP,
+                AddressV: D3D11_TEXTURE_ADDRESS_CLAMP,
+                AddressW: D3D11_TEXTURE_ADDRESS_CLAMP,
+                MaxAnisotropy: 1,
+                ComparisonFunc: D3D11_COMPARISON_NEVER,
+                MaxLOD: D3D11_FLOAT32_MAX,
+                ..std::mem::zeroed()
+            };
+            self.sampler_state = Some(win_util::get_comptr_with_result(|ret| {
+                device.CreateSamplerState(&desc, ret)
+            })?);
+
+            let mut desc: D3D11_BLEND_DESC = std::mem::zeroed();
+            desc.RenderTarget[0] = D3D11_RENDER_TARGET_BLEND_DESC {
+                BlendEnable: TRUE,
+                SrcBlend: D3D11_BLEND_SRC_ALPHA,
+                DestBlend: D3D11_BLEND_INV_SRC_ALPHA,
+                BlendOp: D3D11_BLEND_OP_ADD,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_INV_SRC_ALPHA,
+                BlendOpAlpha: D3D11_BLEND_OP_ADD,
+                RenderTargetWriteMask: 0xf,
+            };
+            self.alpha_blend_state = Some(win_util::get_comptr_with_result(|ret| {
+                device.CreateBlendState(&desc, ret)
+            })?);
+
+            for &id in ShaderId::ALL.iter() {
+                if let Err(hr) = self.create_shader(&device, id) {
+                    logger::error(&format!(
+                        "failed to create shader {} (0x{:08X}), draws using it are skipped",
+                        id.name(),
+                        hr
+                    ));
                 }
             }
-            Ok(())
-        } else {
-            Err(S_FALSE)
         }
+        Ok(())
     }
 
     fn release_resources(&mut self) {
-        std::mem::drop(self);
+        *self = *RenderAPID3D11::new();
     }
 }
 
-static VERTEX_SHADER_CODE: [u8; 680] = [
-    68, 88, 66, 67, 86, 189, 21, 50, 166, 106, 171, 1, 10, 62, 115, 48, 224, 137, 163, 129, 1, 0,
-    0, 0, 168, 2, 0, 0, 4, 0, 0, 0, 48, 0, 0, 0, 0, 1, 0, 0, 4, 2, 0, 0, 84, 2, 0, 0, 65, 111, 110,
-    57, 200, 0, 0, 0, 200, 0, 0, 0, 0, 2, 254, 255, 148, 0, 0, 0, 52, 0, 0, 0, 1, 0, 36, 0, 0, 0,
-    48, 0, 0, 0, 48, 0, 0, 0, 36, 0, 1, 0, 48, 0, 0, 0, 0, 0, 4, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    1, 2, 254, 255, 31, 0, 0, 2, 5, 0, 0, 128, 0, 0, 15, 144, 31, 0, 0, 2, 5, 0, 1, 128, 1, 0, 15,
-    144, 5, 0, 0, 3, 0, 0, 15, 128, 0, 0, 85, 144, 2, 0, 228, 160, 4, 0, 0, 4, 0, 0, 15, 128, 1, 0,
-    228, 160, 0, 0, 0, 144, 0, 0, 228, 128, 4, 0, 0, 4, 0, 0, 15, 128, 3, 0, 228, 160, 0, 0, 170,
-    144, 0, 0, 228, 128, 2, 0, 0, 3, 0, 0, 15, 128, 0, 0, 228, 128, 4, 0, 228, 160, 4, 0, 0, 4, 0,
-    0, 3, 192, 0, 0, 255, 128, 0, 0, 228, 160, 0, 0, 228, 128, 1, 0, 0, 2, 0, 0, 12, 192, 0, 0,
-    228, 128, 1, 0, 0, 2, 0, 0, 15, 224, 1, 0, 228, 144, 255, 255, 0, 0, 83, 72, 68, 82, 252, 0, 0,
-    0, 64, 0, 1, 0, 63, 0, 0, 0, 89, 0, 0, 4, 70, 142, 32, 0, 0, 0, 0, 0, 4, 0, 0, 0, 95, 0, 0, 3,
-    114, 16, 16, 0, 0, 0, 0, 0, 95, 0, 0, 3, 242, 16, 16, 0, 1, 0, 0, 0, 101, 0, 0, 3, 242, 32, 16,
-    0, 0, 0, 0, 0, 103, 0, 0, 4, 242, 32, 16, 0, 1, 0, 0, 0, 1, 0, 0, 0, 104, 0, 0, 2, 1, 0, 0, 0,
-    54, 0, 0, 5, 242, 32, 16, 0, 0, 0, 0, 0, 70, 30, 16, 0, 1, 0, 0, 0, 56, 0, 0, 8, 242, 0, 16, 0,
-    0, 0, 0, 0, 86, 21, 16, 0, 0, 0, 0, 0, 70, 142, 32, 0, 0, 0, 0, 0, 1, 0, 0, 0, 50, 0, 0, 10,
-    242, 0, 16, 0, 0, 0, 0, 0, 70, 142, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 6, 16, 16, 0, 0, 0, 0, 0,
-    70, 14, 16, 0, 0, 0, 0, 0, 50, 0, 0, 10, 242, 0, 16, 0, 0, 0, 0, 0, 70, 142, 32, 0, 0, 0, 0, 0,
-    2, 0, 0, 0, 166, 26, 16, 0, 0, 0, 0, 0, 70, 14, 16, 0, 0, 0, 0, 0, 0, 0, 0, 8, 242, 32, 16, 0,
-    1, 0, 0, 0, 70, 14, 16, 0, 0, 0, 0, 0, 70, 142, 32, 0, 0, 0, 0, 0, 3, 0, 0, 0, 62, 0, 0, 1, 73,
-    83, 71, 78, 72, 0, 0, 0, 2, 0, 0, 0, 8, 0, 0, 0, 56, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0,
-    0, 0, 0, 0, 0, 7, 7, 0, 0, 65, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, 15, 15,
-    0, 0, 80, 79, 83, 73, 84, 73, 79, 78, 0, 67, 79, 76, 79, 82, 0, 171, 79, 83, 71, 78, 76, 0, 0,
-    0, 2, 0, 0, 0, 8, 0, 0, 0, 56, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 15, 0,
-    0, 0, 62, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, 15, 0, 0, 0, 67, 79, 76, 79,
-    82, 0, 83, 86, 95, 80, 111, 115, 105, 116, 105, 111, 110, 0, 171, 171,
-];
-
-static PIXEL_SHADER_CODE: [u8; 288] = [
-    68, 88, 66, 67, 196, 65, 213, 199, 14, 78, 29, 150, 87, 236, 231, 156, 203, 125, 244, 112, 1,
-    0, 0, 0, 32, 1, 0, 0, 4, 0, 0, 0, 48, 0, 0, 0, 124, 0, 0, 0, 188, 0, 0, 0, 236, 0, 0, 0, 65,
-    111, 110, 57, 68, 0, 0, 0, 68, 0, 0, 0, 0, 2, 255, 255, 32, 0, 0, 0, 36, 0, 0, 0, 0, 0, 36, 0,
-    0, 0, 36, 0, 0, 0, 36, 0, 0, 0, 36, 0, 0, 0, 36, 0, 1, 2, 255, 255, 31, 0, 0, 2, 0, 0, 0, 128,
-    0, 0, 15, 176, 1, 0, 0, 2, 0, 8, 15, 128, 0, 0, 228, 176, 255, 255, 0, 0, 83, 72, 68, 82, 56,
-    0, 0, 0, 64, 0, 0, 0, 14, 0, 0, 0, 98, 16, 0, 3, 242, 16, 16, 0, 0, 0, 0, 0, 101, 0, 0, 3, 242,
-    32, 16, 0, 0, 0, 0, 0, 54, 0, 0, 5, 242, 32, 16, 0, 0, 0, 0, 0, 70, 30, 16, 0, 0, 0, 0, 0, 62,
-    0, 0, 1, 73, 83, 71, 78, 40, 0, 0, 0, 1, 0, 0, 0, 8, 0, 0, 0, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 3, 0, 0, 0, 0, 0, 0, 0, 15, 15, 0, 0, 67, 79, 76, 79, 82, 0, 171, 171, 79, 83, 71, 78, 44,
-    0, 0, 0, 1, 0, 0, 0, 8, 0, 0, 0, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0,
-    15, 0, 0, 0, 83, 86, 95, 84, 65, 82, 71, 69, 84, 0, 171, 171,
-];
+fn dxgi_vertex_format(format: render_api::VertexFormat) -> DXGI_FORMAT {
+    match format {
+        render_api::VertexFormat::Float32x2 => DXGI_FORMAT_R32G32_FLOAT,
+        render_api::VertexFormat::Float32x3 => DXGI_FORMAT_R32G32B32_FLOAT,
+        render_api::VertexFormat::Unorm8x4 => DXGI_FORMAT_R8G8B8A8_UNORM,
+    }
+}
@@ -0,0 +1,498 @@
+use crate::image::Image;
+use crate::math::Mat4;
+use crate::render_api;
+use crate::render_api::{Handle, IndexFormat, MyTexturedVertex, MyVertex};
+use crate::shader_assets::ShaderId;
+use crate::Capability;
+use std::cell::RefCell;
+use std::rc::Rc;
+use unity_native_plugin::graphics::GfxDeviceEventType;
+use unity_native_plugin::interface::UnityInterfaces;
+
+//  CPU rasterizer backend for replays and tests. There is no GPU device behind it: texture handles
+//  point at an `Image` and buffer handles at a `Vec<u8>`, both owned by the caller the same way
+//  Unity owns the resources behind the native pointers it passes to the GPU backends.
+
+struct MappedTexture {
+    pixels: *mut u8,
+    row_pitch: i32,
+}
+
+impl render_api::TextureBuffer for MappedTexture {
+    unsafe fn ptr(&self) -> *const std::ffi::c_void {
+        self.pixels as _
+    }
+
+    unsafe fn mut_ptr(&mut self) -> *mut std::ffi::c_void {
+        self.pixels as _
+    }
+
+    fn row_pitch(&self) -> i32 {
+        self.row_pitch
+    }
+}
+
+struct MappedBuffer {
+    buffer: *mut u8,
+    buffer_size: i32,
+}
+
+impl MappedBuffer {
+    fn new(buffer: &mut Vec<u8>) -> MappedBuffer {
+        MappedBuffer {
+            buffer: buffer.as_mut_ptr(),
+            buffer_size: buffer.len() as i32,
+        }
+    }
+}
+
+impl render_api::VertexBuffer for MappedBuffer {
+    unsafe fn ptr(&self) -> *const std::ffi::c_void {
+        self.buffer as _
+    }
+
+    unsafe fn mut_ptr(&mut self) -> *mut std::ffi::c_void {
+        self.buffer as _
+    }
+
+    fn size(&self) -> i32 {
+        self.buffer_size
+    }
+}
+
+struct MappedIndexBuffer {
+    buffer: MappedBuffer,
+    format: IndexFormat,
+}
+
+impl render_api::IndexBuffer for MappedIndexBuffer {
+    unsafe fn ptr(&self) -> *const std::ffi::c_void {
+        self.buffer.buffer as _
+    }
+
+    unsafe fn mut_ptr(&mut self) -> *mut std::ffi::c_void {
+        self.buffer.buffer as _
+    }
+
+    fn size(&self) -> i32 {
+        self.buffer.buffer_size
+    }
+
+    fn format(&self) -> IndexFormat {
+        self.format
+    }
+}
+
+fn unpack_color(color: u32) -> [f32; 4] {
+    let c = color.to_le_bytes();
+    [c[0] as f32, c[1] as f32, c[2] as f32, c[3] as f32]
+}
+
+fn pack_color(color: [f32; 4]) -> [u8; 4] {
+    let mut ret = [0u8; 4];
+    for (dst, src) in ret.iter_mut().zip(color.iter()) {
+        *dst = src.clamp(0.0, 255.0).round() as u8;
+    }
+    ret
+}
+
+fn blend(dst: [u8; 4], src: [f32; 4]) -> [u8; 4] {
+    let a = src[3].clamp(0.0, 255.0) / 255.0;
+    let mut color = [0.0; 4];
+    for i in 0..3 {
+        color[i] = src[i] * a + dst[i] as f32 * (1.0 - a);
+    }
+    color[3] = src[3] + dst[3] as f32 * (1.0 - a);
+    pack_color(color)
+}
+
+fn sample(texture: &Image, u: f32, v: f32) -> [f32; 4] {
+    let x = ((u * texture.width as f32) as i64)
+        .max(0)
+        .min(texture.width as i64 - 1);
+    let y = ((v * texture.height as f32) as i64)
+        .max(0)
+        .min(texture.height as i64 - 1);
+    let c = texture.pixel(x as u32, y as u32);
+    [c[0] as f32, c[1] as f32, c[2] as f32, c[3] as f32]
+}
+
+pub struct RenderAPISoftware {
+    framebuffer: Rc<RefCell<Image>>,
+}
+
+impl Drop for RenderAPISoftware {
+    fn drop(&mut self) {}
+}
+
+impl RenderAPISoftware {
+    pub fn new(width: u32, height: u32) -> Box<RenderAPISoftware> {
+        Box::new(RenderAPISoftware {
+            framebuffer: Rc::new(RefCell::new(Image::new(width, height))),
+        })
+    }
+
+    pub fn framebuffer(&self) -> Rc<RefCell<Image>> {
+        self.framebuffer.clone()
+    }
+
+    //  Returns the framebuffer position of a vertex, or None when it is behind the camera.
+    fn to_screen(
+        world_matrix: &Mat4,
+        framebuffer: &Image,
+        x: f32,
+        y: f32,
+        z: f32,
+    ) -> Option<[f32; 3]> {
+        let clip = world_matrix.transform([x, y, z, 1.0]);
+        if clip[3] <= 0.0 {
+            return None;
+        }
+        Some([
+            (clip[0] / clip[3] * 0.5 + 0.5) * framebuffer.width as f32,
+            (0.5 - clip[1] / clip[3] * 0.5) * framebuffer.height as f32,
+            clip[2] / clip[3],
+        ])
+    }
+
+    fn rasterize_triangle(
+        framebuffer: &mut Image,
+        p: [[f32; 3]; 3],
+        mut shade: impl FnMut([f32; 3], [u8; 4]) -> [u8; 4],
+    ) {
+        let edge = |a: [f32; 3], b: [f32; 3], x: f32, y: f32| {
+            (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0])
+        };
+        let area = edge(p[0], p[1], p[2][0], p[2][1]);
+        if area == 0.0 {
+            return;
+        }
+        let min_x = p
+            .iter()
+            .map(|v| v[0])
+            .fold(f32::MAX, f32::min)
+            .floor()
+            .max(0.0) as u32;
+        let min_y = p
+            .iter()
+            .map(|v| v[1])
+            .fold(f32::MAX, f32::min)
+            .floor()
+            .max(0.0) as u32;
+        let max_x = p
+            .iter()
+            .map(|v| v[0])
+            .fold(f32::MIN, f32::max)
+            .ceil()
+            .min(framebuffer.width as f32) as u32;
+        let max_y = p
+            .iter()
+            .map(|v| v[1])
+            .fold(f32::MIN, f32::max)
+            .ceil()
+            .min(framebuffer.height as f32) as u32;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w = [
+                    edge(p[1], p[2], px, py) / area,
+                    edge(p[2], p[0], px, py) / area,
+                    edge(p[0], p[1], px, py) / area,
+                ];
+                if w.iter().all(|&w| w >= 0.0) {
+                    let dst = framebuffer.pixel(x, y);
+                    framebuffer.set_pixel(x, y, shade(w, dst));
+                }
+            }
+        }
+    }
+
+    fn draw_triangles<T>(
+        &self,
+        world_matrix: [f32; 16],
+        vertices: &[T],
+        indices: &mut dyn Iterator<Item = [usize; 3]>,
+        position: impl Fn(&T) -> [f32; 3],
+        mut shade: impl FnMut([&T; 3], [f32; 3], [u8; 4]) -> [u8; 4],
+    ) {
+        let world_matrix = Mat4::from_cols_array(world_matrix);
+        let mut framebuffer = self.framebuffer.borrow_mut();
+        for triangle in indices {
+            if triangle.iter().any(|&i| i >= vertices.len()) {
+                continue;
+            }
+            let v = [
+                &vertices[triangle[0]],
+                &vertices[triangle[1]],
+                &vertices[triangle[2]],
+            ];
+            let mut p = [[0.0; 3]; 3];
+            let mut visible = true;
+            for i in 0..3 {
+                let [x, y, z] = position(v[i]);
+                match RenderAPISoftware::to_screen(&world_matrix, &framebuffer, x, y, z) {
+                    Some(screen) => p[i] = screen,
+                    None => visible = false,
+                }
+            }
+            if visible {
+                RenderAPISoftware::rasterize_triangle(&mut framebuffer, p, |w, dst| {
+                    shade(v, w, dst)
+                });
+            }
+        }
+    }
+}
+
+fn interpolate_color(v: [&MyVertex; 3], w: [f32; 3]) -> [f32; 4] {
+    let c = [
+        unpack_color(v[0].color),
+        unpack_color(v[1].color),
+        unpack_color(v[2].color),
+    ];
+    let mut color = [0.0; 4];
+    for i in 0..4 {
+        color[i] = c[0][i] * w[0] + c[1][i] * w[1] + c[2][i] * w[2];
+    }
+    color
+}
+
+impl render_api::RenderAPI for RenderAPISoftware {
+    fn process_device_event(&mut self, _type: GfxDeviceEventType, _interfaces: &UnityInterfaces) {}
+
+    //  No shaders to reload.
+    fn capabilities(&self) -> u32 {
+        [
+            Capability::ColoredTriangle,
+            Capability::TexturedQuad,
+            Capability::ModifyTexture,
+            Capability::ModifyVertexBuffer,
+            Capability::ModifyIndexBuffer,
+            Capability::DebugDraw,
+            Capability::CameraMatrices,
+            Capability::FrameCapture,
+        ]
+        .iter()
+        .fold(0, |bits, &capability| bits | capability as u32)
+    }
+
+    fn get_uses_reverse_z(&self) -> bool {
+        false
+    }
+
+    fn get_clip_space_zero_to_one(&self) -> bool {
+        true
+    }
+
+    fn draw_simple_triangles(
+        &self,
+        world_matrix: [f32; 16],
+        triangle_count: i32,
+        vertices_float3_byte4: &[MyVertex],
+    ) {
+        let count = match render_api::simple_triangles_element_count(
+            triangle_count,
+            vertices_float3_byte4.len(),
+        ) {
+            Some(count) => count,
+            None => return,
+        };
+        self.draw_triangles(
+            world_matrix,
+            &vertices_float3_byte4[..count],
+            &mut (0..count / 3).map(|i| [i * 3, i * 3 + 1, i * 3 + 2]),
+            |v| [v.x, v.y, v.z],
+            |v, w, _| pack_color(interpolate_color(v, w)),
+        );
+    }
+
+    fn draw_simple_indexed_triangles(
+        &self,
+        world_matrix: [f32; 16],
+        triangle_count: i32,
+        vertices_float3_byte4: &[MyVertex],
+        indices: &[u32],
+    ) {
+        let count = match render_api::simple_triangles_element_count(triangle_count, indices.len())
+        {
+            Some(count) => count,
+            None => return,
+        };
+        self.draw_triangles(
+            world_matrix,
+            vertices_float3_byte4,
+            &mut indices[..count]
+                .chunks_exact(3)
+                .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize]),
+            |v| [v.x, v.y, v.z],
+            |v, w, _| pack_color(interpolate_color(v, w)),
+        );
+    }
+
+    fn draw_simple_lines(
+        &self,
+        world_matrix: [f32; 16],
+        line_count: i32,
+        vertices_float3_byte4: &[MyVertex],
+    ) {
+        let count =
+            match render_api::simple_lines_element_count(line_count, vertices_float3_byte4.len()) {
+                Some(count) => count,
+                None => return,
+            };
+        let world_matrix = Mat4::from_cols_array(world_matrix);
+        let mut framebuffer = self.framebuffer.borrow_mut();
+        for line in vertices_float3_byte4[..count].chunks_exact(2) {
+            let p0 = RenderAPISoftware::to_screen(
+                &world_matrix,
+                &framebuffer,
+                line[0].x,
+                line[0].y,
+                line[0].z,
+            );
+            let p1 = RenderAPISoftware::to_screen(
+                &world_matrix,
+                &framebuffer,
+                line[1].x,
+                line[1].y,
+                line[1].z,
+            );
+            let (p0, p1) = match (p0, p1) {
+                (Some(p0), Some(p1)) => (p0, p1),
+                _ => continue,
+            };
+            let (c0, c1) = (unpack_color(line[0].color), unpack_color(line[1].color));
+            let steps = (p1[0] - p0[0])
+                .abs()
+                .max((p1[1] - p0[1]).abs())
+                .ceil()
+                .max(1.0) as u32;
+            for step in 0..=steps {
+                let t = step as f32 / steps as f32;
+                let x = p0[0] + (p1[0] - p0[0]) * t;
+                let y = p0[1] + (p1[1] - p0[1]) * t;
+                if x < 0.0
+                    || y < 0.0
+                    || x >= framebuffer.width as f32
+                    || y >= framebuffer.height as f32
+                {
+                    continue;
+                }
+                let mut color = [0.0; 4];
+                for i in 0..4 {
+                    color[i] = c0[i] + (c1[i] - c0[i]) * t;
+                }
+                framebuffer.set_pixel(x as u32, y as u32, pack_color(color));
+            }
+        }
+    }
+
+    fn draw_textured_triangles(
+        &self,
+        world_matrix: [f32; 16],
+        triangle_count: i32,
+        vertices_float3_float2: &[MyTexturedVertex],
+        texture_handle: Handle,
+    ) {
+        if texture_handle.is_null() {
+            return;
+        }
+        let count = match render_api::simple_triangles_element_count(
+            triangle_count,
+            vertices_float3_float2.len(),
+        ) {
+            Some(count) => count,
+            None => return,
+        };
+        let texture = unsafe { &*(texture_handle as *const Image) };
+        self.draw_triangles(
+            world_matrix,
+            &vertices_float3_float2[..count],
+            &mut (0..count / 3).map(|i| [i * 3, i * 3 + 1, i * 3 + 2]),
+            |v| [v.x, v.y, v.z],
+            |v, w, dst| {
+                let u = v[0].u * w[0] + v[1].u * w[1] + v[2].u * w[2];
+                let tv = v[0].v * w[0] + v[1].v * w[1] + v[2].v * w[2];
+                blend(dst, sample(texture, u, tv))
+            },
+        );
+    }
+
+    fn begin_modify_texture(
+        &self,
+        texture_handle: Handle,
+        texture_width: i32,
+        texture_height: i32,
+    ) -> Option<Box<dyn render_api::TextureBuffer>> {
+        if texture_handle.is_null() {
+            return None;
+        }
+        let texture = unsafe { &mut *(texture_handle as *mut Image) };
+        if texture.width as i32 != texture_width || texture.height as i32 != texture_height {
+            return None;
+        }
+        Some(Box::new(MappedTexture {
+            pixels: texture.pixels.as_mut_ptr(),
+            row_pitch: texture.row_pitch() as i32,
+        }))
+    }
+
+    fn end_modify_texture(
+        &self,
+        _texture_handle: Handle,
+        _texture_width: i32,
+        _texture_height: i32,
+        _buffer: Box<dyn render_api::TextureBuffer>,
+    ) {
+    }
+
+    fn begin_modify_vertex_buffer(
+        &self,
+        buffer_handle: Handle,
+    ) -> Option<Box<dyn render_api::VertexBuffer>> {
+        if buffer_handle.is_null() {
+            return None;
+        }
+        let buffer = unsafe { &mut *(buffer_handle as *mut Vec<u8>) };
+        Some(Box::new(MappedBuffer::new(buffer)))
+    }
+
+    fn end_modify_vertex_buffer(&self, _buffer_handle: Handle) {}
+
+    fn begin_modify_index_buffer(
+        &self,
+        buffer_handle: Handle,
+        index_format: IndexFormat,
+    ) -> Option<Box<dyn render_api::IndexBuffer>> {
+        if buffer_handle.is_null() {
+            return None;
+        }
+        let buffer = unsafe { &mut *(buffer_handle as *mut Vec<u8>) };
+        Some(Box::new(MappedIndexBuffer {
+            buffer: MappedBuffer::new(buffer),
+            format: index_format,
+        }))
+    }
+
+    fn end_modify_index_buffer(&self, _buffer_handle: Handle) {}
+
+    fn read_back_frame(&self) -> Option<Image> {
+        Some(self.framebuffer.borrow().clone())
+    }
+
+    fn read_back_texture(
+        &self,
+        texture_handle: Handle,
+        _texture_width: i32,
+        _texture_height: i32,
+    ) -> Option<Image> {
+        if texture_handle.is_null() {
+            return None;
+        }
+        Some(unsafe { &*(texture_handle as *const Image) }.clone())
+    }
+
+    fn reload_shaders(&mut self, _shader_ids: &[ShaderId]) {}
+
+    fn forget_texture(&self, _texture_handle: Handle) {}
+}
@@ -1,11 +1,47 @@
+use crate::logger;
+
+pub const INSTANCE_EXTENSIONS_ENV: &str = "RENDERING_PLUGIN_VULKAN_INSTANCE_EXTENSIONS";
+pub const LAYERS_ENV: &str = "RENDERING_PLUGIN_VULKAN_LAYERS";
+pub const DEVICE_EXTENSIONS_ENV: &str = "RENDERING_PLUGIN_VULKAN_DEVICE_EXTENSIONS";
+
+//  Comma-separated names, e.g. `VK_KHR_external_memory_capabilities,VK_KHR_surface`.
+fn request_from_env(name: &str, request: fn(&std::ffi::CStr)) {
+    if let Ok(value) = std::env::var(name) {
+        for item in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            if let Ok(item) = std::ffi::CString::new(item) {
+                request(&item);
+            }
+        }
+    }
+}
+
 pub fn on_plugin_load(interfaces: &unity_native_plugin::interface::UnityInterfaces) {
-    unsafe {
-        interfaces
-            .interface::<unity_native_plugin_vulkan::vulkan::UnityGraphicsVulkan>()
-            .unwrap()
-            .intercept_initialization(
+    request_from_env(
+        INSTANCE_EXTENSIONS_ENV,
+        crate::vulkan_api::request_instance_extension,
+    );
+    request_from_env(LAYERS_ENV, crate::vulkan_api::request_instance_layer);
+    request_from_env(
+        DEVICE_EXTENSIONS_ENV,
+        crate::vulkan_api::request_device_extension,
+    );
+    if let Some(severity) = std::env::var(crate::validation::VALIDATION_ENV)
+        .ok()
+        .and_then(|value| crate::validation::parse_env(&value))
+    {
+        crate::validation::set_log_severity(severity);
+        crate::vulkan_api::enable_validation();
+    }
+    crate::vulkan_trace::enable_from_env();
+    match interfaces.interface::<unity_native_plugin_vulkan::vulkan::UnityGraphicsVulkan>() {
+        Some(vulkan) => unsafe {
+            vulkan.intercept_initialization(
                 Some(crate::vulkan_api::vulkan_functions::intercept_vulkan_initialization),
                 std::ptr::null_mut(),
             );
+        },
+        None => logger::warning(
+            "IUnityGraphicsVulkan is not available, Vulkan initialization is not intercepted",
+        ),
     }
 }
@@ -0,0 +1,396 @@
+use crate::image::Image;
+use crate::recorder::Call;
+use crate::render_api_software::RenderAPISoftware;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+pub const DEFAULT_FRAMEBUFFER_SIZE: (u32, u32) = (256, 256);
+
+//  Fallback size for textures that are first seen as an overlay, which is passed without a size.
+const OVERLAY_TEXTURE_SIZE: i32 = 64;
+
+//  Held while the plugin's global state is borrowed, by a replay or a test host.
+pub(crate) static REPLAYING: Mutex<()> = Mutex::new(());
+//  Set while `replay` drives the exports, so its calls are not recorded and do not take the shader
+//  watcher's pending changes.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn is_active() -> bool {
+    ACTIVE.load(Ordering::Acquire)
+}
+
+//  Software stand-ins for the Unity resources a recording refers to, keyed by the recorded handle.
+//  They are boxed because the plugin keeps raw pointers to them across rehashes.
+#[derive(Default)]
+#[allow(clippy::box_collection)]
+pub(crate) struct Resources {
+    textures: HashMap<u64, Box<Image>>,
+    buffers: HashMap<u64, Box<Vec<u8>>>,
+}
+
+impl Resources {
+    fn texture(&mut self, handle: u64, width: i32, height: i32) -> crate::render_api::Handle {
+        if handle == 0 || width <= 0 || height <= 0 {
+            return std::ptr::null_mut();
+        }
+        let texture = self
+            .textures
+            .entry(handle)
+            .or_insert_with(|| Box::new(Image::new(width as u32, height as u32)));
+        if texture.width != width as u32 || texture.height != height as u32 {
+            **texture = Image::new(width as u32, height as u32);
+        }
+        texture.as_mut() as *mut Image as _
+    }
+
+    fn overlay_texture(&mut self, handle: u64) -> crate::render_api::Handle {
+        match self.textures.get(&handle) {
+            Some(texture) => {
+                let (width, height) = (texture.width as i32, texture.height as i32);
+                self.texture(handle, width, height)
+            }
+            None => {
+                let texture = self.texture(handle, OVERLAY_TEXTURE_SIZE, OVERLAY_TEXTURE_SIZE);
+                if let Some(texture) = self.textures.get_mut(&handle) {
+                    texture.fill([255, 255, 255, 255]);
+                }
+                texture
+            }
+        }
+    }
+
+    //  Null for a handle the recording never registered, which the exports reject.
+    fn existing_texture(&mut self, handle: u64) -> crate::render_api::Handle {
+        match self.textures.get_mut(&handle) {
+            Some(texture) => texture.as_mut() as *mut Image as _,
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    fn buffer(&mut self, handle: u64, size: usize) -> crate::render_api::Handle {
+        if handle == 0 {
+            return std::ptr::null_mut();
+        }
+        let buffer = self.buffers.entry(handle).or_default();
+        buffer.resize(size.max(buffer.len()), 0);
+        buffer.as_mut() as *mut Vec<u8> as _
+    }
+
+    #[cfg(test)]
+    pub(crate) fn get_texture(&self, handle: u64) -> Option<&Image> {
+        self.textures.get(&handle).map(|texture| texture.as_ref())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn get_buffer(&self, handle: u64) -> Option<&[u8]> {
+        self.buffers.get(&handle).map(|buffer| buffer.as_slice())
+    }
+}
+
+fn as_ptr<T>(v: &[T]) -> *const T {
+    if v.is_empty() {
+        std::ptr::null()
+    } else {
+        v.as_ptr()
+    }
+}
+
+//  Drives the plugin's exports with the recorded calls against the software backend, calling
+//  `on_frame` with the framebuffer and resources at the end of every frame (a frame starts at each
+//  `RenderEventId::Default` event). The plugin state is reset before and after, so this is meant
+//  for offline use, not while Unity is rendering with the plugin. Returns the number of frames.
+pub(crate) fn replay(
+    calls: &[Call],
+    framebuffer_size: (u32, u32),
+    mut on_frame: impl FnMut(u64, &Image, &Resources),
+) -> u64 {
+    let _replaying = REPLAYING.lock().unwrap_or_else(|e| e.into_inner());
+    let api = RenderAPISoftware::new(framebuffer_size.0, framebuffer_size.1);
+    let framebuffer = api.framebuffer();
+    let previous_api = unsafe { (*std::ptr::addr_of_mut!(crate::CURRENT_API)).replace(api) };
+    crate::reset_plugin_state();
+    ACTIVE.store(true, Ordering::Release);
+
+    let mut resources = Resources::default();
+    let mut frame_count = 0;
+    for call in calls {
+        match call {
+            Call::SetTimeFromUnity(t) => crate::SetTimeFromUnity(*t),
+            Call::SetTimeFromUnityDouble(t) => crate::SetTimeFromUnityDouble(*t),
+            Call::SetTimePaused(paused) => crate::SetTimePaused(*paused),
+            Call::SetTimeScale(scale) => crate::SetTimeScale(*scale),
+            Call::SetFixedTimeStep(step) => crate::SetFixedTimeStep(*step),
+            Call::ResetTime => crate::ResetTime(),
+            Call::SetTexture {
+                handle,
+                width,
+                height,
+            } => crate::SetTextureFromUnity(
+                resources.texture(*handle, *width, *height),
+                *width,
+                *height,
+            ),
+            Call::SetMeshBuffers {
+                handle,
+                vertex_count,
+                vertices,
+                normals,
+                uv,
+            } => {
+                let vertex_count = (*vertex_count)
+                    .min((vertices.len() / 3) as i32)
+                    .min((normals.len() / 3) as i32)
+                    .min((uv.len() / 2) as i32)
+                    .max(0);
+                let size = vertex_count as usize * std::mem::size_of::<crate::MeshVertex>();
+                crate::SetMeshBuffersFromUnity(
+                    resources.buffer(*handle, size),
+                    vertex_count,
+                    as_ptr(vertices),
+                    as_ptr(normals),
+                    as_ptr(uv),
+                );
+            }
+            Call::SetMeshIndexBuffer {
+                handle,
+                index_count,
+                index_format,
+                indices,
+            } => {
+                //  An invalid format is passed through for the export to reject.
+                let format = crate::render_api::index_format_from_i32(*index_format)
+                    .unwrap_or(crate::render_api::IndexFormat::UInt16);
+                let index_count = (*index_count).min(indices.len() as i32).max(0);
+                let size = index_count as usize * format.size() as usize;
+                let indices_16 = indices.iter().map(|&i| i as u16).collect::<Vec<_>>();
+                let source = match format {
+                    crate::render_api::IndexFormat::UInt16 => as_ptr(&indices_16) as *const _,
+                    crate::render_api::IndexFormat::UInt32 => as_ptr(indices) as *const _,
+                };
+                crate::SetMeshIndexBufferFromUnity(
+                    resources.buffer(*handle, size),
+                    index_count,
+                    *index_format,
+                    source,
+                );
+            }
+            //  The software backend has no shaders, and the live shader directory is kept.
+            Call::SetShaderDirectory(_) => {}
+            //  The feed's frames come from another process and are not in the log, so replays keep
+            //  drawing the plasma effect instead of reading whatever the file holds now.
+            Call::SetTextureFeed(_) => {}
+            Call::SubmitVideoFrame {
+                format,
+                width,
+                height,
+                color_space,
+                range,
+                planes,
+            } => {
+                let plane = |i: usize| {
+                    planes
+                        .get(i)
+                        .map_or((std::ptr::null_mut(), 0), |(stride, data)| {
+                            (as_ptr(data) as crate::render_api::Handle, *stride)
+                        })
+                };
+                let ((y_plane, y_stride), (u_plane, u_stride), (v_plane, v_stride)) =
+                    (plane(0), plane(1), plane(2));
+                crate::SubmitVideoFrame(crate::VideoFrame {
+                    format: *format,
+                    width: *width,
+                    height: *height,
+                    color_space: *color_space,
+                    range: *range,
+                    y_plane,
+                    y_stride,
+                    u_plane,
+                    u_stride,
+                    v_plane,
+                    v_stride,
+                });
+            }
+            Call::SetVideoPlaneTextures { luma, chroma } => {
+                crate::SetVideoPlaneTexturesFromUnity(
+                    resources.texture(luma.0, luma.1, luma.2),
+                    luma.1,
+                    luma.2,
+                    resources.texture(chroma.0, chroma.1, chroma.2),
+                    chroma.1,
+                    chroma.2,
+                );
+            }
+            Call::StopVideo => crate::StopVideo(),
+            //  The image is read from the path again, so a replay shows the file as it is now.
+            Call::LoadImageIntoTexture { handle, path } => {
+                let path = path
+                    .as_ref()
+                    .and_then(|path| std::ffi::CString::new(path.as_str()).ok());
+                crate::LoadImageIntoTexture(
+                    resources.existing_texture(*handle),
+                    path.as_ref().map_or(std::ptr::null(), |path| path.as_ptr()),
+                );
+            }
+            Call::SetCameraMatrices(matrices) => match matrices {
+                Some((view, projection)) => {
+                    crate::SetCameraMatrices(view.as_ptr(), projection.as_ptr())
+                }
+                None => crate::SetCameraMatrices(std::ptr::null(), std::ptr::null()),
+            },
+            Call::SetOverlayTexture(handle) => {
+                crate::SetOverlayTextureFromUnity(resources.overlay_texture(*handle))
+            }
+            Call::DebugDrawLine(from, to, color) => crate::DebugDrawLine(*from, *to, *color),
+            Call::DebugDrawBox(center, half_extents, color) => {
+                crate::DebugDrawBox(*center, *half_extents, *color)
+            }
+            Call::DebugDrawSphere(center, radius, color) => {
+                crate::DebugDrawSphere(*center, *radius, *color)
+            }
+            Call::DebugDrawArrow(from, to, color) => crate::DebugDrawArrow(*from, *to, *color),
+            Call::DebugDrawAxes(matrix, size) => crate::DebugDrawAxes(
+                matrix.as_ref().map_or(std::ptr::null(), |m| m.as_ptr()),
+                *size,
+            ),
+            Call::DebugDrawQuad(p, color) => crate::DebugDrawQuad(p[0], p[1], p[2], p[3], *color),
+            Call::DebugDrawClear => crate::DebugDrawClear(),
+            Call::RenderEvent(event_id) => {
+                if *event_id == crate::RenderEventId::Default as i32 {
+                    if frame_count > 0 {
+                        on_frame(frame_count - 1, &framebuffer.borrow(), &resources);
+                    }
+                    framebuffer.borrow_mut().fill([0, 0, 0, 255]);
+                    frame_count += 1;
+                }
+                crate::texture_loader::finish();
+                crate::on_render_event(*event_id);
+            }
+        }
+    }
+    if frame_count > 0 {
+        on_frame(frame_count - 1, &framebuffer.borrow(), &resources);
+    }
+
+    ACTIVE.store(false, Ordering::Release);
+    crate::reset_plugin_state();
+    unsafe {
+        crate::CURRENT_API = previous_api;
+    }
+    frame_count
+}
+
+//  Offline replay of a log written by the recorder, for Rust tools linking the rlib: `on_frame`
+//  gets each frame's index and RGBA8 pixels (`framebuffer_size.0 * 4` bytes per row), and
+//  `DEFAULT_FRAMEBUFFER_SIZE` is a reasonable size. It drives the plugin's global state, so it must
+//  not run in a process where Unity uses the plugin.
+pub fn replay_file(
+    path: &std::path::Path,
+    framebuffer_size: (u32, u32),
+    mut on_frame: impl FnMut(u64, &[u8]),
+) -> std::io::Result<u64> {
+    let calls = crate::recorder::read_file(path)?;
+    Ok(replay(
+        &calls,
+        framebuffer_size,
+        |frame_index, framebuffer, _| on_frame(frame_index, &framebuffer.pixels),
+    ))
+}
+
+#[test]
+fn test_replay() {
+    let calls = [
+        Call::SetFixedTimeStep(0.5),
+        Call::SetTexture {
+            handle: 1,
+            width: 32,
+            height: 32,
+        },
+        Call::RenderEvent(crate::RenderEventId::Default as i32),
+        Call::RenderEvent(crate::RenderEventId::Default as i32),
+    ];
+    let calls = crate::recorder::decode(&crate::recorder::encode(&calls)).unwrap();
+    let run = || {
+        let mut frames = Vec::new();
+        let count = replay(&calls, (64, 64), |_, image, _| frames.push(image.clone()));
+        assert_eq!(count, frames.len() as u64);
+        frames
+    };
+    let frames = run();
+    assert_eq!(frames.len(), 2);
+    assert_ne!(frames[0].pixel(32, 32), [0, 0, 0, 255]);
+    assert_ne!(frames[0], frames[1]);
+    assert_eq!(frames, run());
+}
+
+#[test]
+fn test_replay_video() {
+    let texture = Call::SetTexture {
+        handle: 1,
+        width: 2,
+        height: 2,
+    };
+    //  A white 2x2 I420 frame in BT.601 limited range.
+    let video_frame = Call::SubmitVideoFrame {
+        format: crate::VideoFormat::I420 as i32,
+        width: 2,
+        height: 2,
+        color_space: crate::YuvColorSpace::Bt601 as i32,
+        range: crate::YuvRange::Limited as i32,
+        planes: vec![(2, vec![235; 4]), (1, vec![128]), (1, vec![128])],
+    };
+    let render_event = Call::RenderEvent(crate::RenderEventId::Default as i32);
+    let calls = [
+        texture,
+        video_frame.clone(),
+        render_event.clone(),
+        Call::SetVideoPlaneTextures {
+            luma: (2, 2, 2),
+            chroma: (3, 1, 1),
+        },
+        video_frame,
+        render_event.clone(),
+        Call::StopVideo,
+        render_event,
+    ];
+    let calls = crate::recorder::decode(&crate::recorder::encode(&calls)).unwrap();
+    let mut frames = Vec::new();
+    replay(&calls, (8, 8), |_, _, resources| {
+        let texture = |handle| resources.get_texture(handle).map(|t| t.pixel(0, 0));
+        frames.push((texture(1), texture(2), texture(3)));
+    });
+    let white = Some([255, 255, 255, 255]);
+    assert_eq!(frames[0].0, white);
+    //  With plane textures registered the planes go there, and the texture shows the plasma again.
+    assert_ne!(frames[1].0, white);
+    assert_eq!(frames[1].1, Some([235, 235, 235, 255]));
+    assert_eq!(frames[1].2.map(|p| p[..2].to_vec()), Some(vec![128, 128]));
+    //  StopVideo drops the plane textures too, so a new frame would be converted on the CPU.
+    assert_ne!(frames[2].0, white);
+    assert_eq!(frames.len(), 3);
+}
+
+#[test]
+fn test_replay_file() {
+    let calls = [
+        Call::SetTexture {
+            handle: 1,
+            width: 32,
+            height: 32,
+        },
+        Call::RenderEvent(crate::RenderEventId::Default as i32),
+    ];
+    let path = std::env::temp_dir().join(format!(
+        "rendering_plugin_replay_{}.bin",
+        std::process::id()
+    ));
+    std::fs::write(&path, crate::recorder::encode(&calls)).unwrap();
+    let mut frames = Vec::new();
+    let count = replay_file(&path, (16, 8), |frame_index, pixels| {
+        frames.push((frame_index, pixels.len()))
+    });
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(count.unwrap(), 1);
+    assert_eq!(frames, [(0, 16 * 8 * 4)]);
+    assert!(replay_file(&path, (16, 8), |_, _| {}).is_err());
+}
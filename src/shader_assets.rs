@@ -0,0 +1,284 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+//  Shader loading is only used by the backends that draw with shaders.
+#[cfg(any(backend_d3d11, test))]
+include!(concat!(env!("OUT_DIR"), "/embedded_shaders.rs"));
+
+pub const SHADER_DIRECTORY_ENV: &str = "RENDERING_PLUGIN_SHADER_DIR";
+
+#[cfg(backend_d3d11)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ShaderStage {
+    Vertex,
+    Pixel,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ShaderId {
+    SimpleVertex,
+    SimplePixel,
+    TexturedVertex,
+    TexturedPixel,
+}
+
+impl ShaderId {
+    pub const ALL: [ShaderId; 4] = [
+        ShaderId::SimpleVertex,
+        ShaderId::SimplePixel,
+        ShaderId::TexturedVertex,
+        ShaderId::TexturedPixel,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShaderId::SimpleVertex => "simple_vs",
+            ShaderId::SimplePixel => "simple_ps",
+            ShaderId::TexturedVertex => "textured_vs",
+            ShaderId::TexturedPixel => "textured_ps",
+        }
+    }
+
+    pub fn source_name(&self) -> &'static str {
+        match self {
+            ShaderId::SimpleVertex | ShaderId::SimplePixel => "simple",
+            ShaderId::TexturedVertex | ShaderId::TexturedPixel => "textured",
+        }
+    }
+
+    #[cfg(backend_d3d11)]
+    pub fn stage(&self) -> ShaderStage {
+        match self {
+            ShaderId::SimpleVertex | ShaderId::TexturedVertex => ShaderStage::Vertex,
+            ShaderId::SimplePixel | ShaderId::TexturedPixel => ShaderStage::Pixel,
+        }
+    }
+
+    #[cfg(backend_d3d11)]
+    pub fn entry_point(&self) -> &'static str {
+        match self.stage() {
+            ShaderStage::Vertex => "VS",
+            ShaderStage::Pixel => "PS",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShaderFormat {
+    Dxbc,
+    Dxil,
+    SpirV,
+    Glsl,
+    Hlsl,
+}
+
+impl ShaderFormat {
+    pub const ALL: [ShaderFormat; 5] = [
+        ShaderFormat::Dxbc,
+        ShaderFormat::Dxil,
+        ShaderFormat::SpirV,
+        ShaderFormat::Glsl,
+        ShaderFormat::Hlsl,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ShaderFormat::Dxbc => "dxbc",
+            ShaderFormat::Dxil => "dxil",
+            ShaderFormat::SpirV => "spv",
+            ShaderFormat::Glsl => "glsl",
+            ShaderFormat::Hlsl => "hlsl",
+        }
+    }
+
+    pub fn file_name(&self, id: ShaderId) -> String {
+        match self {
+            ShaderFormat::Hlsl => format!("{}.{}", id.source_name(), self.extension()),
+            _ => format!("{}.{}", id.name(), self.extension()),
+        }
+    }
+}
+
+#[cfg(any(backend_d3d11, test))]
+#[derive(Debug)]
+pub enum ShaderError {
+    Io(std::io::Error),
+    InvalidHeader(&'static str),
+    NotFound(ShaderId),
+}
+
+#[cfg(any(backend_d3d11, test))]
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderError::Io(e) => write!(f, "{}", e),
+            ShaderError::InvalidHeader(reason) => write!(f, "invalid shader header: {}", reason),
+            ShaderError::NotFound(id) => write!(f, "shader {} not found", id.name()),
+        }
+    }
+}
+
+#[cfg(any(backend_d3d11, test))]
+pub struct ShaderBlob {
+    pub id: ShaderId,
+    pub format: ShaderFormat,
+    pub code: Vec<u8>,
+    pub path: Option<PathBuf>,
+}
+
+static SHADER_DIRECTORY: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn set_shader_directory(path: Option<PathBuf>) {
+    *SHADER_DIRECTORY.lock().unwrap() = path;
+}
+
+pub fn shader_directory() -> Option<PathBuf> {
+    SHADER_DIRECTORY
+        .lock()
+        .unwrap()
+        .clone()
+        .or_else(|| std::env::var_os(SHADER_DIRECTORY_ENV).map(PathBuf::from))
+}
+
+#[cfg(any(backend_d3d11, test))]
+fn read_u32(code: &[u8], offset: usize) -> Option<u32> {
+    code.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+//  A chunk's FourCC and its data.
+#[cfg(any(backend_d3d11, test))]
+pub type DxbcChunk<'a> = ([u8; 4], &'a [u8]);
+
+#[cfg(any(backend_d3d11, test))]
+pub fn dxbc_chunks(code: &[u8]) -> Result<Vec<DxbcChunk<'_>>, ShaderError> {
+    if code.len() < 32 || &code[0..4] != b"DXBC" {
+        return Err(ShaderError::InvalidHeader("missing DXBC magic"));
+    }
+    if read_u32(code, 24) != Some(code.len() as u32) {
+        return Err(ShaderError::InvalidHeader("DXBC size mismatch"));
+    }
+    let chunk_count = read_u32(code, 28).unwrap() as usize;
+    let mut chunks = Vec::with_capacity(chunk_count.min(code.len() / 4));
+    for i in 0..chunk_count {
+        let offset = read_u32(code, 32 + i * 4)
+            .ok_or(ShaderError::InvalidHeader("truncated DXBC chunk table"))?
+            as usize;
+        let size = read_u32(code, offset + 4)
+            .ok_or(ShaderError::InvalidHeader("DXBC chunk out of bounds"))?
+            as usize;
+        let data = code
+            .get(offset + 8..offset + 8 + size)
+            .ok_or(ShaderError::InvalidHeader("DXBC chunk out of bounds"))?;
+        let mut fourcc = [0u8; 4];
+        fourcc.copy_from_slice(&code[offset..offset + 4]);
+        chunks.push((fourcc, data));
+    }
+    Ok(chunks)
+}
+
+#[cfg(any(backend_d3d11, test))]
+pub fn validate(format: ShaderFormat, code: &[u8]) -> Result<(), ShaderError> {
+    match format {
+        ShaderFormat::Dxbc | ShaderFormat::Dxil => {
+            let chunks = dxbc_chunks(code)?;
+            let expected: &[&[u8; 4]] = if format == ShaderFormat::Dxbc {
+                &[b"SHDR", b"SHEX"]
+            } else {
+                &[b"DXIL"]
+            };
+            if chunks.iter().any(|(fourcc, _)| expected.contains(&fourcc)) {
+                Ok(())
+            } else {
+                Err(ShaderError::InvalidHeader("missing shader bytecode chunk"))
+            }
+        }
+        ShaderFormat::SpirV => {
+            if code.len() < 20 || !code.chunks_exact(4).remainder().is_empty() {
+                Err(ShaderError::InvalidHeader("truncated SPIR-V module"))
+            } else if read_u32(code, 0) != Some(0x0723_0203) {
+                Err(ShaderError::InvalidHeader("missing SPIR-V magic"))
+            } else {
+                Ok(())
+            }
+        }
+        ShaderFormat::Glsl | ShaderFormat::Hlsl => match std::str::from_utf8(code) {
+            Err(_) => Err(ShaderError::InvalidHeader("shader source is not UTF-8")),
+            Ok(source) if format == ShaderFormat::Glsl && !source.contains("#version") => Err(
+                ShaderError::InvalidHeader("missing GLSL #version directive"),
+            ),
+            Ok(_) => Ok(()),
+        },
+    }
+}
+
+#[cfg(any(backend_d3d11, test))]
+pub fn embedded(id: ShaderId, format: ShaderFormat) -> Option<&'static [u8]> {
+    let name = match format {
+        ShaderFormat::Hlsl => id.source_name(),
+        _ => id.name(),
+    };
+    EMBEDDED_SHADERS
+        .iter()
+        .find(|(n, ext, _)| *n == name && *ext == format.extension())
+        .map(|(_, _, code)| *code)
+}
+
+#[cfg(any(backend_d3d11, test))]
+pub fn load_file(id: ShaderId, format: ShaderFormat) -> Option<Result<ShaderBlob, ShaderError>> {
+    let path = shader_directory()?.join(format.file_name(id));
+    if !path.is_file() {
+        return None;
+    }
+    Some(
+        std::fs::read(&path)
+            .map_err(ShaderError::Io)
+            .and_then(|code| {
+                validate(format, &code)?;
+                Ok(ShaderBlob {
+                    id,
+                    format,
+                    code,
+                    path: Some(path),
+                })
+            }),
+    )
+}
+
+#[cfg(any(backend_d3d11, test))]
+pub fn load(id: ShaderId, formats: &[ShaderFormat]) -> Result<ShaderBlob, ShaderError> {
+    for &format in formats {
+        if let Some(blob) = load_file(id, format) {
+            return blob;
+        }
+    }
+    for &format in formats {
+        if let Some(code) = embedded(id, format) {
+            validate(format, code)?;
+            return Ok(ShaderBlob {
+                id,
+                format,
+                code: code.to_vec(),
+                path: None,
+            });
+        }
+    }
+    Err(ShaderError::NotFound(id))
+}
+
+#[test]
+fn test_embedded_dxbc_is_valid() {
+    for &id in &[ShaderId::SimpleVertex, ShaderId::SimplePixel] {
+        let blob = load(id, &[ShaderFormat::Dxbc]).unwrap();
+        assert_eq!(blob.id, id);
+        assert_eq!(blob.format, ShaderFormat::Dxbc);
+        assert_eq!(blob.code, embedded(id, ShaderFormat::Dxbc).unwrap());
+        assert!(blob.path.is_none());
+    }
+    assert!(validate(
+        ShaderFormat::Dxbc,
+        &embedded(ShaderId::SimpleVertex, ShaderFormat::Dxbc).unwrap()[..100]
+    )
+    .is_err());
+    assert!(validate(ShaderFormat::SpirV, &[0x03, 0x02, 0x23, 0x07, 0, 0, 1, 0]).is_err());
+}
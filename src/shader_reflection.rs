@@ -0,0 +1,746 @@
+use crate::render_api::{VertexElement, VertexFormat};
+use crate::shader_assets::{self, ShaderFormat};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ComponentType {
+    Unknown,
+    UInt32,
+    SInt32,
+    Float32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ShaderInput {
+    pub semantic_name: String,
+    pub semantic_index: u32,
+    pub location: u32,
+    pub component_type: ComponentType,
+    pub component_count: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ConstantBuffer {
+    pub name: String,
+    pub binding: Option<u32>,
+    pub size: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ShaderReflection {
+    pub inputs: Vec<ShaderInput>,
+    pub constant_buffers: Option<Vec<ConstantBuffer>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ReflectionError {
+    InvalidContainer(&'static str),
+    Unsupported(ShaderFormat),
+    MissingVertexElement(String, u32),
+    IncompatibleVertexElement(String, u32),
+    MissingConstantBuffer(u32),
+    ConstantBufferSize {
+        binding: u32,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl std::fmt::Display for ReflectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReflectionError::InvalidContainer(reason) => write!(f, "invalid shader: {}", reason),
+            ReflectionError::Unsupported(format) => {
+                write!(f, "reflection of {:?} shaders is not supported", format)
+            }
+            ReflectionError::MissingVertexElement(name, index) => {
+                write!(
+                    f,
+                    "vertex layout has no element for input {}{}",
+                    name, index
+                )
+            }
+            ReflectionError::IncompatibleVertexElement(name, index) => write!(
+                f,
+                "vertex layout element for input {}{} is incompatible",
+                name, index
+            ),
+            ReflectionError::MissingConstantBuffer(binding) => {
+                write!(f, "shader has no constant buffer at binding {}", binding)
+            }
+            ReflectionError::ConstantBufferSize {
+                binding,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "constant buffer at binding {} is {} bytes, expected {}",
+                binding, actual, expected
+            ),
+        }
+    }
+}
+
+impl ShaderReflection {
+    pub fn constant_buffer(&self, binding: u32) -> Option<&ConstantBuffer> {
+        self.constant_buffers
+            .as_ref()?
+            .iter()
+            .find(|cb| cb.binding == Some(binding))
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, ReflectionError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(ReflectionError::InvalidContainer("truncated chunk"))
+}
+
+fn read_cstr(data: &[u8], offset: usize) -> Result<String, ReflectionError> {
+    let bytes = data
+        .get(offset..)
+        .ok_or(ReflectionError::InvalidContainer("string out of bounds"))?;
+    let len = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(ReflectionError::InvalidContainer("unterminated string"))?;
+    Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+fn parse_signature(data: &[u8], element_size: usize) -> Result<Vec<ShaderInput>, ReflectionError> {
+    let count = read_u32(data, 0)? as usize;
+    let mut inputs = Vec::with_capacity(count);
+    for i in 0..count {
+        //  ISG1 elements are prefixed with a stream index.
+        let base = 8 + i * element_size + if element_size == 32 { 4 } else { 0 };
+        let component_type = match read_u32(data, base + 12)? {
+            1 => ComponentType::UInt32,
+            2 => ComponentType::SInt32,
+            3 => ComponentType::Float32,
+            _ => ComponentType::Unknown,
+        };
+        let mask = *data
+            .get(base + 20)
+            .ok_or(ReflectionError::InvalidContainer("truncated signature"))?;
+        inputs.push(ShaderInput {
+            semantic_name: read_cstr(data, read_u32(data, base)? as usize)?,
+            semantic_index: read_u32(data, base + 4)?,
+            location: read_u32(data, base + 16)?,
+            component_type,
+            component_count: 4 - (mask as u32 & 0xf).leading_zeros().saturating_sub(28),
+        });
+    }
+    Ok(inputs)
+}
+
+fn parse_rdef(data: &[u8]) -> Result<Vec<ConstantBuffer>, ReflectionError> {
+    let cb_count = read_u32(data, 0)? as usize;
+    let cb_offset = read_u32(data, 4)? as usize;
+    let binding_count = read_u32(data, 8)? as usize;
+    let binding_offset = read_u32(data, 12)? as usize;
+
+    let mut bindings = Vec::new();
+    for i in 0..binding_count {
+        let base = binding_offset + i * 32;
+        //  D3D_SIT_CBUFFER
+        if read_u32(data, base + 4)? == 0 {
+            bindings.push((
+                read_cstr(data, read_u32(data, base)? as usize)?,
+                read_u32(data, base + 20)?,
+            ));
+        }
+    }
+
+    let mut constant_buffers = Vec::new();
+    for i in 0..cb_count {
+        let base = cb_offset + i * 24;
+        let name = read_cstr(data, read_u32(data, base)? as usize)?;
+        let binding = bindings
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, binding)| *binding);
+        constant_buffers.push(ConstantBuffer {
+            name,
+            binding,
+            size: read_u32(data, base + 12)?,
+        });
+    }
+    Ok(constant_buffers)
+}
+
+//  dcl_constantbuffer instructions of a SHDR/SHEX chunk, so blobs compiled with /Qstrip_reflect
+//  (no RDEF) still report their constant buffers, named cb<slot>.
+fn parse_constant_buffer_declarations(data: &[u8]) -> Result<Vec<ConstantBuffer>, ReflectionError> {
+    const OPCODE_CUSTOMDATA: u32 = 53;
+    const OPCODE_DCL_CONSTANT_BUFFER: u32 = 89;
+    const OPERAND_TYPE_CONSTANT_BUFFER: u32 = 8;
+
+    let length = (read_u32(data, 4)? as usize).min(data.len() / 4);
+    let mut constant_buffers = Vec::new();
+    let mut position = 2;
+    while position < length {
+        let token = read_u32(data, position * 4)?;
+        let opcode = token & 0x7ff;
+        let instruction_length = if opcode == OPCODE_CUSTOMDATA {
+            read_u32(data, position * 4 + 4)? as usize
+        } else {
+            (token >> 24 & 0x7f) as usize
+        };
+        if instruction_length == 0 {
+            return Err(ReflectionError::InvalidContainer(
+                "invalid instruction length",
+            ));
+        }
+        if opcode == OPCODE_DCL_CONSTANT_BUFFER {
+            //  Skip extended opcode tokens.
+            let mut operand = position + 1;
+            while read_u32(data, (operand - 1) * 4)? & 0x8000_0000 != 0 {
+                operand += 1;
+            }
+            let operand_token = read_u32(data, operand * 4)?;
+            let index_dimension = operand_token >> 20 & 3;
+            //  Shader model 5.1 declarations (3D, register ranges) are not reflected.
+            if operand_token >> 12 & 0xff == OPERAND_TYPE_CONSTANT_BUFFER && index_dimension == 2 {
+                let slot = read_u32(data, operand * 4 + 4)?;
+                let vector_count = read_u32(data, operand * 4 + 8)?;
+                constant_buffers.push(ConstantBuffer {
+                    name: format!("cb{}", slot),
+                    binding: Some(slot),
+                    size: vector_count.saturating_mul(16),
+                });
+            }
+        }
+        position += instruction_length;
+    }
+    Ok(constant_buffers)
+}
+
+fn reflect_dxbc(code: &[u8]) -> Result<ShaderReflection, ReflectionError> {
+    let chunks = shader_assets::dxbc_chunks(code)
+        .map_err(|_| ReflectionError::InvalidContainer("invalid DXBC container"))?;
+    let mut reflection = ShaderReflection::default();
+    let mut declared_constant_buffers = None;
+    for (fourcc, data) in chunks {
+        match &fourcc {
+            b"ISGN" => reflection.inputs = parse_signature(data, 24)?,
+            b"ISG1" => reflection.inputs = parse_signature(data, 32)?,
+            b"RDEF" => reflection.constant_buffers = Some(parse_rdef(data)?),
+            b"SHDR" | b"SHEX" => {
+                declared_constant_buffers = Some(parse_constant_buffer_declarations(data)?)
+            }
+            _ => {}
+        }
+    }
+    if reflection.constant_buffers.is_none() {
+        reflection.constant_buffers = declared_constant_buffers;
+    }
+    Ok(reflection)
+}
+
+mod spirv_op {
+    pub const NAME: u32 = 5;
+    pub const TYPE_INT: u32 = 21;
+    pub const TYPE_FLOAT: u32 = 22;
+    pub const TYPE_VECTOR: u32 = 23;
+    pub const TYPE_MATRIX: u32 = 24;
+    pub const TYPE_ARRAY: u32 = 28;
+    pub const TYPE_STRUCT: u32 = 30;
+    pub const TYPE_POINTER: u32 = 32;
+    pub const CONSTANT: u32 = 43;
+    pub const VARIABLE: u32 = 59;
+    pub const DECORATE: u32 = 71;
+    pub const MEMBER_DECORATE: u32 = 72;
+}
+
+mod spirv_decoration {
+    pub const BLOCK: u32 = 2;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const OFFSET: u32 = 35;
+}
+
+const SPIRV_STORAGE_CLASS_INPUT: u32 = 1;
+const SPIRV_STORAGE_CLASS_UNIFORM: u32 = 2;
+
+#[derive(Clone)]
+enum SpirvType {
+    Scalar(ComponentType, u32),
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Array(u32, u32),
+    Struct(Vec<u32>),
+    Pointer(u32),
+}
+
+#[derive(Default)]
+struct SpirvModule {
+    names: std::collections::HashMap<u32, String>,
+    types: std::collections::HashMap<u32, SpirvType>,
+    constants: std::collections::HashMap<u32, u32>,
+    decorations: std::collections::HashMap<(u32, u32), u32>,
+    member_decorations: std::collections::HashMap<(u32, u32, u32), u32>,
+    variables: Vec<(u32, u32, u32)>,
+}
+
+impl SpirvModule {
+    fn parse(words: &[u32]) -> Result<SpirvModule, ReflectionError> {
+        let mut module = SpirvModule::default();
+        let mut i = 5;
+        while i < words.len() {
+            let word_count = (words[i] >> 16) as usize;
+            let opcode = words[i] & 0xffff;
+            if word_count == 0 || i + word_count > words.len() {
+                return Err(ReflectionError::InvalidContainer(
+                    "truncated SPIR-V instruction",
+                ));
+            }
+            let ops = &words[i + 1..i + word_count];
+            match opcode {
+                spirv_op::NAME if !ops.is_empty() => {
+                    let bytes = ops[1..]
+                        .iter()
+                        .flat_map(|w| w.to_le_bytes().to_vec())
+                        .take_while(|&b| b != 0)
+                        .collect::<Vec<_>>();
+                    module
+                        .names
+                        .insert(ops[0], String::from_utf8_lossy(&bytes).into_owned());
+                }
+                spirv_op::TYPE_INT if ops.len() >= 3 => {
+                    let component_type = if ops[2] != 0 {
+                        ComponentType::SInt32
+                    } else {
+                        ComponentType::UInt32
+                    };
+                    module
+                        .types
+                        .insert(ops[0], SpirvType::Scalar(component_type, ops[1] / 8));
+                }
+                spirv_op::TYPE_FLOAT if ops.len() >= 2 => {
+                    module.types.insert(
+                        ops[0],
+                        SpirvType::Scalar(ComponentType::Float32, ops[1] / 8),
+                    );
+                }
+                spirv_op::TYPE_VECTOR if ops.len() >= 3 => {
+                    module
+                        .types
+                        .insert(ops[0], SpirvType::Vector(ops[1], ops[2]));
+                }
+                spirv_op::TYPE_MATRIX if ops.len() >= 3 => {
+                    module
+                        .types
+                        .insert(ops[0], SpirvType::Matrix(ops[1], ops[2]));
+                }
+                spirv_op::TYPE_ARRAY if ops.len() >= 3 => {
+                    module
+                        .types
+                        .insert(ops[0], SpirvType::Array(ops[1], ops[2]));
+                }
+                spirv_op::TYPE_STRUCT if !ops.is_empty() => {
+                    module
+                        .types
+                        .insert(ops[0], SpirvType::Struct(ops[1..].to_vec()));
+                }
+                spirv_op::TYPE_POINTER if ops.len() >= 3 => {
+                    module.types.insert(ops[0], SpirvType::Pointer(ops[2]));
+                }
+                spirv_op::CONSTANT if ops.len() >= 3 => {
+                    module.constants.insert(ops[1], ops[2]);
+                }
+                spirv_op::VARIABLE if ops.len() >= 3 => {
+                    module.variables.push((ops[0], ops[1], ops[2]));
+                }
+                spirv_op::DECORATE if ops.len() >= 2 => {
+                    module
+                        .decorations
+                        .insert((ops[0], ops[1]), ops.get(2).copied().unwrap_or(0));
+                }
+                spirv_op::MEMBER_DECORATE if ops.len() >= 3 => {
+                    module
+                        .member_decorations
+                        .insert((ops[0], ops[1], ops[2]), ops.get(3).copied().unwrap_or(0));
+                }
+                _ => {}
+            }
+            i += word_count;
+        }
+        Ok(module)
+    }
+
+    fn size_of(&self, type_id: u32, matrix_stride: Option<u32>) -> Option<u32> {
+        match self.types.get(&type_id)? {
+            SpirvType::Scalar(_, size) => Some(*size),
+            SpirvType::Vector(component, count) => Some(self.size_of(*component, None)? * count),
+            SpirvType::Matrix(column, count) => {
+                let stride = match matrix_stride {
+                    Some(stride) => stride,
+                    None => self.size_of(*column, None)?,
+                };
+                Some(stride * count)
+            }
+            SpirvType::Array(element, length) => {
+                let length = *self.constants.get(length)?;
+                let stride = match self
+                    .decorations
+                    .get(&(type_id, spirv_decoration::ARRAY_STRIDE))
+                {
+                    Some(stride) => *stride,
+                    None => self.size_of(*element, None)?,
+                };
+                Some(stride * length)
+            }
+            SpirvType::Struct(members) => {
+                let mut size = 0;
+                for (i, member) in members.iter().enumerate() {
+                    let i = i as u32;
+                    let offset = self
+                        .member_decorations
+                        .get(&(type_id, i, spirv_decoration::OFFSET))
+                        .copied()
+                        .unwrap_or(size);
+                    let stride = self
+                        .member_decorations
+                        .get(&(type_id, i, spirv_decoration::MATRIX_STRIDE))
+                        .copied();
+                    size = size.max(offset + self.size_of(*member, stride)?);
+                }
+                Some(size)
+            }
+            SpirvType::Pointer(_) => None,
+        }
+    }
+
+    fn components_of(&self, type_id: u32) -> Option<(ComponentType, u32)> {
+        match self.types.get(&type_id)? {
+            SpirvType::Scalar(component_type, _) => Some((*component_type, 1)),
+            SpirvType::Vector(component, count) => {
+                Some((self.components_of(*component)?.0, *count))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn split_semantic(name: &str) -> (String, u32) {
+    let name = name.strip_prefix("in.var.").unwrap_or(name);
+    let digits = name
+        .bytes()
+        .rev()
+        .take_while(|b| b.is_ascii_digit())
+        .count();
+    let (semantic, index) = name.split_at(name.len() - digits);
+    (semantic.to_string(), index.parse().unwrap_or(0))
+}
+
+fn reflect_spirv(code: &[u8]) -> Result<ShaderReflection, ReflectionError> {
+    shader_assets::validate(ShaderFormat::SpirV, code)
+        .map_err(|_| ReflectionError::InvalidContainer("invalid SPIR-V module"))?;
+    let words = code
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect::<Vec<_>>();
+    let module = SpirvModule::parse(&words)?;
+
+    let mut reflection = ShaderReflection {
+        inputs: Vec::new(),
+        constant_buffers: Some(Vec::new()),
+    };
+    for &(pointer_type, id, storage_class) in &module.variables {
+        let pointee = match module.types.get(&pointer_type) {
+            Some(SpirvType::Pointer(pointee)) => *pointee,
+            _ => continue,
+        };
+        let name = module.names.get(&id).cloned().unwrap_or_default();
+        match storage_class {
+            SPIRV_STORAGE_CLASS_INPUT => {
+                if module
+                    .decorations
+                    .contains_key(&(id, spirv_decoration::BUILT_IN))
+                {
+                    continue;
+                }
+                let (component_type, component_count) = match module.components_of(pointee) {
+                    Some(components) => components,
+                    None => continue,
+                };
+                let (semantic_name, semantic_index) = split_semantic(&name);
+                reflection.inputs.push(ShaderInput {
+                    semantic_name,
+                    semantic_index,
+                    location: module
+                        .decorations
+                        .get(&(id, spirv_decoration::LOCATION))
+                        .copied()
+                        .unwrap_or(0),
+                    component_type,
+                    component_count,
+                });
+            }
+            SPIRV_STORAGE_CLASS_UNIFORM
+                if module
+                    .decorations
+                    .contains_key(&(pointee, spirv_decoration::BLOCK)) =>
+            {
+                reflection
+                    .constant_buffers
+                    .as_mut()
+                    .unwrap()
+                    .push(ConstantBuffer {
+                        name,
+                        binding: module
+                            .decorations
+                            .get(&(id, spirv_decoration::BINDING))
+                            .copied(),
+                        size: module
+                            .size_of(pointee, None)
+                            .ok_or(ReflectionError::InvalidContainer("unsized uniform block"))?,
+                    });
+            }
+            _ => {}
+        }
+    }
+    reflection.inputs.sort_by_key(|input| input.location);
+    Ok(reflection)
+}
+
+pub fn reflect(format: ShaderFormat, code: &[u8]) -> Result<ShaderReflection, ReflectionError> {
+    match format {
+        ShaderFormat::Dxbc | ShaderFormat::Dxil => reflect_dxbc(code),
+        ShaderFormat::SpirV => reflect_spirv(code),
+        _ => Err(ReflectionError::Unsupported(format)),
+    }
+}
+
+//  Normalized formats are read as floats by the input assembler.
+fn vertex_format_component_type(format: VertexFormat) -> ComponentType {
+    match format {
+        VertexFormat::Float32x2 | VertexFormat::Float32x3 | VertexFormat::Unorm8x4 => {
+            ComponentType::Float32
+        }
+    }
+}
+
+//  Returns the vertex elements consumed by the shader, in shader input order. Inputs without a
+//  semantic name (SPIR-V compiled from GLSL) are matched by location against the element index.
+pub fn derive_input_layout(
+    reflection: &ShaderReflection,
+    vertex_layout: &[VertexElement],
+) -> Result<Vec<VertexElement>, ReflectionError> {
+    reflection
+        .inputs
+        .iter()
+        .map(|input| {
+            let element = if input.semantic_name.is_empty() {
+                vertex_layout.get(input.location as usize)
+            } else {
+                vertex_layout.iter().find(|element| {
+                    element.semantic.eq_ignore_ascii_case(&input.semantic_name)
+                        && element.semantic_index == input.semantic_index
+                })
+            }
+            .ok_or_else(|| {
+                ReflectionError::MissingVertexElement(
+                    input.semantic_name.clone(),
+                    input.semantic_index,
+                )
+            })?;
+            if vertex_format_component_type(element.format) != input.component_type {
+                return Err(ReflectionError::IncompatibleVertexElement(
+                    input.semantic_name.clone(),
+                    input.semantic_index,
+                ));
+            }
+            Ok(*element)
+        })
+        .collect()
+}
+
+//  Stripped blobs carry no constant buffer information, which is not treated as a mismatch.
+pub fn validate_constant_buffer(
+    reflection: &ShaderReflection,
+    binding: u32,
+    expected_size: u32,
+) -> Result<(), ReflectionError> {
+    let cb = reflection
+        .constant_buffer(binding)
+        .ok_or(ReflectionError::MissingConstantBuffer(binding))?;
+    if cb.size != expected_size {
+        return Err(ReflectionError::ConstantBufferSize {
+            binding,
+            expected: expected_size,
+            actual: cb.size,
+        });
+    }
+    Ok(())
+}
+
+#[test]
+fn test_reflect_dxbc_input_layout() {
+    use crate::render_api::{MyTexturedVertex, MyVertex};
+    use crate::shader_assets::ShaderId;
+
+    let code = shader_assets::embedded(ShaderId::SimpleVertex, ShaderFormat::Dxbc).unwrap();
+    let reflection = reflect(ShaderFormat::Dxbc, code).unwrap();
+    assert_eq!(reflection.inputs.len(), 2);
+    assert_eq!(reflection.inputs[0].semantic_name, "POSITION");
+    assert_eq!(reflection.inputs[0].component_count, 3);
+    assert_eq!(reflection.inputs[1].semantic_name, "COLOR");
+    assert_eq!(reflection.inputs[1].component_type, ComponentType::Float32);
+    assert_eq!(reflection.inputs[1].component_count, 4);
+    //  The embedded blob has no RDEF, so the constant buffer comes from its dcl_constantbuffer.
+    assert_eq!(
+        reflection.constant_buffers,
+        Some(vec![ConstantBuffer {
+            name: "cb0".to_string(),
+            binding: Some(0),
+            size: 64,
+        }])
+    );
+    assert!(validate_constant_buffer(&reflection, 0, 64).is_ok());
+    assert_eq!(
+        validate_constant_buffer(&reflection, 0, 128),
+        Err(ReflectionError::ConstantBufferSize {
+            binding: 0,
+            expected: 128,
+            actual: 64,
+        })
+    );
+
+    let code = shader_assets::embedded(ShaderId::SimplePixel, ShaderFormat::Dxbc).unwrap();
+    let pixel_reflection = reflect(ShaderFormat::Dxbc, code).unwrap();
+    assert_eq!(
+        validate_constant_buffer(&pixel_reflection, 0, 64),
+        Err(ReflectionError::MissingConstantBuffer(0))
+    );
+
+    assert_eq!(
+        derive_input_layout(&reflection, &MyVertex::LAYOUT).unwrap(),
+        MyVertex::LAYOUT.to_vec()
+    );
+    assert_eq!(
+        derive_input_layout(&reflection, &MyTexturedVertex::LAYOUT),
+        Err(ReflectionError::MissingVertexElement(
+            "COLOR".to_string(),
+            0
+        ))
+    );
+}
+
+#[test]
+fn test_reflect_spirv() {
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut ret = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        ret.extend_from_slice(operands);
+        ret
+    }
+    let mut name = vec![10];
+    name.extend(
+        b"in.var.TEXCOORD0\0\0\0\0"
+            .chunks(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+    );
+    let mut words = vec![0x0723_0203, 0x0001_0000, 0, 100, 0];
+    words.extend(instruction(spirv_op::NAME, &name));
+    words.extend(instruction(
+        spirv_op::DECORATE,
+        &[10, spirv_decoration::LOCATION, 1],
+    ));
+    words.extend(instruction(
+        spirv_op::DECORATE,
+        &[4, spirv_decoration::BLOCK],
+    ));
+    words.extend(instruction(
+        spirv_op::DECORATE,
+        &[11, spirv_decoration::BINDING, 0],
+    ));
+    words.extend(instruction(
+        spirv_op::MEMBER_DECORATE,
+        &[4, 0, spirv_decoration::OFFSET, 0],
+    ));
+    words.extend(instruction(
+        spirv_op::MEMBER_DECORATE,
+        &[4, 0, spirv_decoration::MATRIX_STRIDE, 16],
+    ));
+    words.extend(instruction(spirv_op::TYPE_FLOAT, &[1, 32]));
+    words.extend(instruction(spirv_op::TYPE_VECTOR, &[2, 1, 4]));
+    words.extend(instruction(spirv_op::TYPE_MATRIX, &[3, 2, 4]));
+    words.extend(instruction(spirv_op::TYPE_STRUCT, &[4, 3]));
+    words.extend(instruction(
+        spirv_op::TYPE_POINTER,
+        &[5, SPIRV_STORAGE_CLASS_UNIFORM, 4],
+    ));
+    words.extend(instruction(spirv_op::TYPE_VECTOR, &[6, 1, 2]));
+    words.extend(instruction(
+        spirv_op::TYPE_POINTER,
+        &[7, SPIRV_STORAGE_CLASS_INPUT, 6],
+    ));
+    words.extend(instruction(
+        spirv_op::VARIABLE,
+        &[7, 10, SPIRV_STORAGE_CLASS_INPUT],
+    ));
+    words.extend(instruction(
+        spirv_op::VARIABLE,
+        &[5, 11, SPIRV_STORAGE_CLASS_UNIFORM],
+    ));
+    let code = words
+        .iter()
+        .flat_map(|w| w.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+
+    let reflection = reflect(ShaderFormat::SpirV, &code).unwrap();
+    assert_eq!(
+        reflection.inputs,
+        vec![ShaderInput {
+            semantic_name: "TEXCOORD".to_string(),
+            semantic_index: 0,
+            location: 1,
+            component_type: ComponentType::Float32,
+            component_count: 2,
+        }]
+    );
+    assert!(validate_constant_buffer(&reflection, 0, 64).is_ok());
+    assert_eq!(
+        validate_constant_buffer(&reflection, 0, 128),
+        Err(ReflectionError::ConstantBufferSize {
+            binding: 0,
+            expected: 128,
+            actual: 64,
+        })
+    );
+}
+
+#[test]
+fn test_parse_rdef() {
+    //  Header: constant buffers, bindings, version, flags, creator.
+    let header = [1, 28, 2, 52, 0xFFFE_0400, 0, 128];
+    //  Constant buffer "Transforms": 1 variable at 0, 64 bytes.
+    let constant_buffer = [116, 1, 0, 64, 0, 0];
+    //  Bindings "Sampler" (D3D_SIT_SAMPLER at s0) and "Transforms" (D3D_SIT_CBUFFER at b2).
+    let sampler_binding = [128, 3, 0, 0, 0, 0, 1, 0];
+    let constant_buffer_binding = [116, 0, 0, 0, 0, 2, 1, 0];
+    let mut data = header
+        .iter()
+        .chain(&constant_buffer)
+        .chain(&sampler_binding)
+        .chain(&constant_buffer_binding)
+        .flat_map(|w: &u32| w.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    data.extend_from_slice(b"Transforms\0\0");
+    data.extend_from_slice(b"Sampler\0");
+
+    assert_eq!(
+        parse_rdef(&data).unwrap(),
+        vec![ConstantBuffer {
+            name: "Transforms".to_string(),
+            binding: Some(2),
+            size: 64,
+        }]
+    );
+    assert_eq!(
+        parse_rdef(&data[..90]),
+        Err(ReflectionError::InvalidContainer("truncated chunk"))
+    );
+}
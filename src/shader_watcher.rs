@@ -0,0 +1,158 @@
+use crate::shader_assets::{self, ShaderFormat, ShaderId};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::SystemTime;
+
+pub const WATCH_ENV: &str = "RENDERING_PLUGIN_WATCH_SHADERS";
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+//  `stop` clears RUNNING and notifies WAKE, so the thread leaves its poll wait right away.
+static RUNNING: Mutex<bool> = Mutex::new(false);
+static WAKE: Condvar = Condvar::new();
+static THREAD: Mutex<Option<std::thread::JoinHandle<()>>> = Mutex::new(None);
+static CHANGED: Mutex<Vec<ShaderId>> = Mutex::new(Vec::new());
+
+fn shader_ids_for_file(file_name: &str) -> Vec<ShaderId> {
+    ShaderId::ALL
+        .iter()
+        .copied()
+        .filter(|&id| {
+            ShaderFormat::ALL
+                .iter()
+                .any(|format| format.file_name(id) == file_name)
+        })
+        .collect()
+}
+
+fn scan(directory: &Path) -> HashMap<String, SystemTime> {
+    let mut ret = HashMap::new();
+    if let Ok(entries) = std::fs::read_dir(directory) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if shader_ids_for_file(&file_name).is_empty() {
+                continue;
+            }
+            if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+                ret.insert(file_name, modified);
+            }
+        }
+    }
+    ret
+}
+
+fn mark_changed(ids: Vec<ShaderId>) {
+    let mut changed = CHANGED.lock().unwrap();
+    for id in ids {
+        if !changed.contains(&id) {
+            changed.push(id);
+        }
+    }
+}
+
+//  Files added, modified or deleted between two scans.
+fn changed_files<'a>(
+    previous: &'a HashMap<String, SystemTime>,
+    current: &'a HashMap<String, SystemTime>,
+) -> Vec<&'a str> {
+    let modified = current
+        .iter()
+        .filter(|(file_name, modified)| previous.get(*file_name) != Some(modified))
+        .map(|(file_name, _)| file_name.as_str());
+    let deleted = previous
+        .keys()
+        .filter(|file_name| !current.contains_key(*file_name))
+        .map(String::as_str);
+    modified.chain(deleted).collect()
+}
+
+fn watch() {
+    let mut directory = None;
+    let mut files = HashMap::new();
+    loop {
+        let current_directory = shader_assets::shader_directory();
+        let current = current_directory.as_deref().map(scan).unwrap_or_default();
+        if current_directory != directory {
+            //  Shaders of both directories may now load from somewhere else (or be embedded).
+            directory = current_directory;
+            for file_name in files.keys().chain(current.keys()) {
+                mark_changed(shader_ids_for_file(file_name));
+            }
+        } else {
+            for file_name in changed_files(&files, &current) {
+                mark_changed(shader_ids_for_file(file_name));
+            }
+        }
+        files = current;
+        let running = RUNNING.lock().unwrap();
+        let (running, _) = WAKE
+            .wait_timeout_while(running, POLL_INTERVAL, |running| *running)
+            .unwrap();
+        if !*running {
+            break;
+        }
+    }
+}
+
+pub fn start() {
+    let mut thread = THREAD.lock().unwrap();
+    if thread.is_none() {
+        *RUNNING.lock().unwrap() = true;
+        *thread = std::thread::Builder::new()
+            .name("RenderingPlugin shader watcher".to_string())
+            .spawn(watch)
+            .ok();
+    }
+}
+
+pub fn stop() {
+    let thread = THREAD.lock().unwrap().take();
+    if let Some(thread) = thread {
+        *RUNNING.lock().unwrap() = false;
+        WAKE.notify_all();
+        let _ = thread.join();
+    }
+    CHANGED.lock().unwrap().clear();
+}
+
+pub fn take_changed() -> Vec<ShaderId> {
+    std::mem::take(&mut *CHANGED.lock().unwrap())
+}
+
+#[test]
+fn test_changed_files() {
+    let time = |secs| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    let files = |entries: &[(&str, u64)]| {
+        entries
+            .iter()
+            .map(|&(name, secs)| (name.to_string(), time(secs)))
+            .collect::<HashMap<_, _>>()
+    };
+    let previous = files(&[
+        ("simple_vs.dxbc", 1),
+        ("simple_ps.dxbc", 1),
+        ("textured_vs.spv", 1),
+    ]);
+    let current = files(&[
+        ("simple_vs.dxbc", 1),
+        ("simple_ps.dxbc", 2),
+        ("textured_ps.spv", 1),
+    ]);
+    let mut changed = changed_files(&previous, &current);
+    changed.sort_unstable();
+    assert_eq!(
+        changed,
+        ["simple_ps.dxbc", "textured_ps.spv", "textured_vs.spv"]
+    );
+    assert!(changed_files(&current, &current).is_empty());
+}
+
+#[test]
+fn test_stop_wakes_the_watcher() {
+    start();
+    let started = std::time::Instant::now();
+    stop();
+    assert!(started.elapsed() < POLL_INTERVAL);
+    assert!(THREAD.lock().unwrap().is_none());
+}
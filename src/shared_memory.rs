@@ -0,0 +1,126 @@
+//  A file mapped into memory with MAP_SHARED (or a Windows file mapping), so several processes that
+//  map the same file see each other's writes. On Linux a file under /dev/shm keeps the pages in RAM.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+pub struct SharedMemory {
+    ptr: *mut u8,
+    len: usize,
+    #[cfg(windows)]
+    mapping: winapi::um::winnt::HANDLE,
+    _file: File,
+}
+
+//  The memory is only accessed through raw pointers, with the synchronization left to the user.
+unsafe impl Send for SharedMemory {}
+
+impl SharedMemory {
+    //  Creates the file, or grows an existing one, and maps `len` bytes of it. A file is never
+    //  shrunk, since another process may still map the rest of it: touching mapped pages past the
+    //  end of the file is a SIGBUS on Unix, and Windows cannot resize a file while it is mapped
+    //  (there, creating the mapping grows the file).
+    pub fn create(path: &Path, len: usize) -> io::Result<SharedMemory> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        #[cfg(unix)]
+        if file.metadata()?.len() < len as u64 {
+            file.set_len(len as u64)?;
+        }
+        SharedMemory::map(file, len)
+    }
+
+    //  Maps the whole of an existing file.
+    pub fn open(path: &Path) -> io::Result<SharedMemory> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty file"));
+        }
+        SharedMemory::map(file, len)
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[cfg(unix)]
+    fn map(file: File, len: usize) -> io::Result<SharedMemory> {
+        use std::os::unix::io::AsRawFd;
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SharedMemory {
+            ptr: ptr as *mut u8,
+            len,
+            _file: file,
+        })
+    }
+
+    #[cfg(windows)]
+    fn map(file: File, len: usize) -> io::Result<SharedMemory> {
+        use std::os::windows::io::AsRawHandle;
+        use winapi::um::{handleapi, memoryapi, winnt};
+        unsafe {
+            let mapping = memoryapi::CreateFileMappingW(
+                file.as_raw_handle() as _,
+                std::ptr::null_mut(),
+                winnt::PAGE_READWRITE,
+                ((len as u64) >> 32) as u32,
+                len as u32,
+                std::ptr::null(),
+            );
+            if mapping.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let ptr = memoryapi::MapViewOfFile(mapping, memoryapi::FILE_MAP_ALL_ACCESS, 0, 0, len);
+            if ptr.is_null() {
+                let e = io::Error::last_os_error();
+                handleapi::CloseHandle(mapping);
+                return Err(e);
+            }
+            Ok(SharedMemory {
+                ptr: ptr as *mut u8,
+                len,
+                mapping,
+                _file: file,
+            })
+        }
+    }
+}
+
+impl Drop for SharedMemory {
+    #[cfg(unix)]
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+
+    #[cfg(windows)]
+    fn drop(&mut self) {
+        unsafe {
+            winapi::um::memoryapi::UnmapViewOfFile(self.ptr as _);
+            winapi::um::handleapi::CloseHandle(self.mapping);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//  Texture feed: another local process publishes images through a shared-memory file, and the
+//  plugin uploads the newest one into the texture set with `SetTextureFromUnity` on every render
+//  event, in place of the animated plasma. `TextureFeedProducer` is the writing side for Rust
+//  producers; others can follow the layout below (native endianness, all offsets from the start
+//  of the file).
+//
+//    0  u32  magic "RPTF"          20  u32  stride (bytes per row)
+//    4  u32  version (1)           24  u32  slot count
+//    8  u32  format                28  u32  reserved
+//   12  u32  width                 32  u64  latest frame (atomic, 0 while nothing was published)
+//   16  u32  height
+//
+//  `slot count` slots follow at offset 64, each starting on a 64-byte boundary: a u64 sequence (atomic), padding
+//  to 64 bytes, then `stride * height` bytes of pixels. Frame n (counting from 1) goes into slot
+//  n % slot count. The producer sets the slot's sequence to 2n - 1, writes the pixels, sets it to
+//  2n, and then stores n as the latest frame, all with release ordering. A reader copies the slot
+//  and discards the copy unless the sequence was 2n both before and after.
+//
+//  A producer may restart on the same file with another layout while readers have it mapped. It
+//  clears the magic before rewriting the header and sets it again last, the file only ever grows,
+//  and frame numbers continue from the previous producer's latest frame. A reader maps the file
+//  again when the header describes more than it has mapped.
+
+use crate::logger;
+use crate::render_api::{Handle, RenderAPI};
+use crate::shared_memory::SharedMemory;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+const MAGIC: u32 = u32::from_le_bytes(*b"RPTF");
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 64;
+const SLOT_HEADER_SIZE: usize = 64;
+const LATEST_FRAME_OFFSET: usize = 32;
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FeedFormat {
+    Rgba8 = 0,
+    Bgra8 = 1,
+}
+
+impl FeedFormat {
+    fn from_u32(value: u32) -> Option<FeedFormat> {
+        match value {
+            0 => Some(FeedFormat::Rgba8),
+            1 => Some(FeedFormat::Bgra8),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeedLayout {
+    pub format: FeedFormat,
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+    pub slot_count: u32,
+}
+
+impl FeedLayout {
+    fn frame_size(&self) -> usize {
+        self.stride as usize * self.height as usize
+    }
+
+    fn slot_size(&self) -> usize {
+        (SLOT_HEADER_SIZE + self.frame_size() + 63) & !63
+    }
+
+    fn file_size(&self) -> usize {
+        HEADER_SIZE + self.slot_size() * self.slot_count as usize
+    }
+
+    fn is_valid(&self) -> bool {
+        self.width > 0
+            && self.height > 0
+            && self.slot_count > 0
+            && self.stride as u64 >= self.width as u64 * 4
+    }
+}
+
+fn read_u32(memory: &SharedMemory, offset: usize) -> u32 {
+    unsafe { std::ptr::read_volatile(memory.as_ptr().add(offset) as *const u32) }
+}
+
+fn write_u32(memory: &SharedMemory, offset: usize, value: u32) {
+    unsafe { std::ptr::write_volatile(memory.as_ptr().add(offset) as *mut u32, value) }
+}
+
+fn atomic_u64(memory: &SharedMemory, offset: usize) -> &AtomicU64 {
+    unsafe { &*(memory.as_ptr().add(offset) as *const AtomicU64) }
+}
+
+pub struct TextureFeedProducer {
+    memory: SharedMemory,
+    layout: FeedLayout,
+    frame: u64,
+}
+
+impl TextureFeedProducer {
+    //  Tightly packed rows and a few slots are enough unless the reader falls behind by frames.
+    pub fn create(
+        path: &Path,
+        format: FeedFormat,
+        width: u32,
+        height: u32,
+        slot_count: u32,
+    ) -> io::Result<TextureFeedProducer> {
+        let layout = FeedLayout {
+            format,
+            width,
+            height,
+            stride: width * 4,
+            slot_count,
+        };
+        if !layout.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid texture feed layout",
+            ));
+        }
+        let memory = SharedMemory::create(path, layout.file_size())?;
+        let previous_frame = if read_u32(&memory, 0) == MAGIC {
+            atomic_u64(&memory, LATEST_FRAME_OFFSET).load(Ordering::Acquire)
+        } else {
+            0
+        };
+        write_u32(&memory, 0, 0);
+        fence(Ordering::Release);
+        atomic_u64(&memory, LATEST_FRAME_OFFSET).store(previous_frame, Ordering::Release);
+        for slot in 0..slot_count as usize {
+            atomic_u64(&memory, HEADER_SIZE + slot * layout.slot_size())
+                .store(0, Ordering::Release);
+        }
+        write_u32(&memory, 4, VERSION);
+        write_u32(&memory, 8, format as u32);
+        write_u32(&memory, 12, width);
+        write_u32(&memory, 16, height);
+        write_u32(&memory, 20, layout.stride);
+        write_u32(&memory, 24, slot_count);
+        write_u32(&memory, 28, 0);
+        //  Written last, so readers do not accept a half-initialized header.
+        fence(Ordering::Release);
+        write_u32(&memory, 0, MAGIC);
+        Ok(TextureFeedProducer {
+            memory,
+            layout,
+            frame: previous_frame,
+        })
+    }
+
+    pub fn layout(&self) -> FeedLayout {
+        self.layout
+    }
+
+    //  Publishes `pixels` (`stride * height` bytes) and returns its frame number.
+    pub fn write_frame(&mut self, pixels: &[u8]) -> u64 {
+        assert_eq!(pixels.len(), self.layout.frame_size());
+        self.frame += 1;
+        let frame = self.frame;
+        let slot = HEADER_SIZE
+            + (frame % self.layout.slot_count as u64) as usize * self.layout.slot_size();
+        let sequence = atomic_u64(&self.memory, slot);
+        sequence.store(frame * 2 - 1, Ordering::Release);
+        fence(Ordering::Release);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                pixels.as_ptr(),
+                self.memory.as_ptr().add(slot + SLOT_HEADER_SIZE),
+                pixels.len(),
+            );
+        }
+        sequence.store(frame * 2, Ordering::Release);
+        atomic_u64(&self.memory, LATEST_FRAME_OFFSET).store(frame, Ordering::Release);
+        frame
+    }
+}
+
+pub(crate) struct TextureFeedConsumer {
+    path: PathBuf,
+    memory: SharedMemory,
+    last_frame: u64,
+}
+
+impl TextureFeedConsumer {
+    pub fn open(path: &Path) -> io::Result<TextureFeedConsumer> {
+        let memory = SharedMemory::open(path)?;
+        if memory.len() < HEADER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a texture feed",
+            ));
+        }
+        Ok(TextureFeedConsumer {
+            path: path.to_path_buf(),
+            memory,
+            last_frame: 0,
+        })
+    }
+
+    //  Read on every poll, as the producer may start (or restart) after the feed was opened. The
+    //  layout may need more of the file than is mapped.
+    fn layout(&self) -> Option<FeedLayout> {
+        if read_u32(&self.memory, 0) != MAGIC || read_u32(&self.memory, 4) != VERSION {
+            return None;
+        }
+        fence(Ordering::Acquire);
+        let layout = FeedLayout {
+            format: FeedFormat::from_u32(read_u32(&self.memory, 8))?,
+            width: read_u32(&self.memory, 12),
+            height: read_u32(&self.memory, 16),
+            stride: read_u32(&self.memory, 20),
+            slot_count: read_u32(&self.memory, 24),
+        };
+        if layout.is_valid() {
+            Some(layout)
+        } else {
+            None
+        }
+    }
+
+    //  The current layout, after mapping the file again if a restarted producer grew it.
+    fn mapped_layout(&mut self) -> Option<FeedLayout> {
+        let layout = self.layout()?;
+        if layout.file_size() <= self.memory.len() {
+            return Some(layout);
+        }
+        match SharedMemory::open(&self.path) {
+            Ok(memory) => self.memory = memory,
+            Err(e) => {
+                logger::error(&format!("texture feed: cannot map the file again: {}", e));
+                return None;
+            }
+        }
+        self.layout()
+            .filter(|layout| layout.file_size() <= self.memory.len())
+    }
+
+    //  Copies the newest frame into `pixels` if there is one that was not returned before. A frame
+    //  the producer overwrote during the copy is skipped; the next poll picks up a newer one.
+    pub fn poll(&mut self, pixels: &mut Vec<u8>) -> Option<(u64, FeedLayout)> {
+        let layout = self.mapped_layout()?;
+        let frame = atomic_u64(&self.memory, LATEST_FRAME_OFFSET).load(Ordering::Acquire);
+        if frame == 0 || frame == self.last_frame {
+            return None;
+        }
+        let slot = HEADER_SIZE + (frame % layout.slot_count as u64) as usize * layout.slot_size();
+        let sequence = atomic_u64(&self.memory, slot);
+        if sequence.load(Ordering::Acquire) != frame * 2 {
+            return None;
+        }
+        pixels.resize(layout.frame_size(), 0);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                self.memory.as_ptr().add(slot + SLOT_HEADER_SIZE),
+                pixels.as_mut_ptr(),
+                pixels.len(),
+            );
+        }
+        fence(Ordering::Acquire);
+        if sequence.load(Ordering::Relaxed) != frame * 2 {
+            return None;
+        }
+        self.last_frame = frame;
+        Some((frame, layout))
+    }
+}
+
+struct PluginFeed {
+    consumer: TextureFeedConsumer,
+    pixels: Vec<u8>,
+}
+
+static FEED: Mutex<Option<PluginFeed>> = Mutex::new(None);
+
+pub(crate) fn open(path: &Path) -> io::Result<()> {
+    let consumer = TextureFeedConsumer::open(path)?;
+    *FEED.lock().unwrap() = Some(PluginFeed {
+        consumer,
+        pixels: Vec::new(),
+    });
+    Ok(())
+}
+
+pub(crate) fn close() {
+    *FEED.lock().unwrap() = None;
+}
+
+//  Uploads a new feed frame, if any, into the texture, scaled to it with nearest-neighbor sampling.
+//  Returns whether a feed is open; the texture then keeps showing the last frame in between.
+pub(crate) fn update_texture(api: &dyn RenderAPI, handle: Handle, width: i32, height: i32) -> bool {
+    let mut feed = FEED.lock().unwrap();
+    let feed = match feed.as_mut() {
+        Some(feed) => feed,
+        None => return false,
+    };
+    if handle.is_null() || width <= 0 || height <= 0 {
+        return true;
+    }
+    let layout = match feed.consumer.poll(&mut feed.pixels) {
+        Some((_, layout)) => layout,
+        None => return true,
+    };
+    let mut buffer = match api.begin_modify_texture(handle, width, height) {
+        Some(buffer) => buffer,
+        None => return true,
+    };
+    if unsafe { buffer.ptr() }.is_null() {
+        logger::warning("texture feed: the texture cannot be written");
+        return true;
+    }
+    let swap = layout.format == FeedFormat::Bgra8;
+    let (width, height) = (width as usize, height as usize);
+    for y in 0..height {
+        let src_row = &feed.pixels[y * layout.height as usize / height * layout.stride as usize..];
+        let dst_row = unsafe {
+            std::slice::from_raw_parts_mut(
+                (buffer.mut_ptr() as *mut u8).add(y * buffer.row_pitch() as usize),
+                width * 4,
+            )
+        };
+        for (x, dst) in dst_row.chunks_exact_mut(4).enumerate() {
+            let src_x = x * layout.width as usize / width * 4;
+            let src = &src_row[src_x..src_x + 4];
+            if swap {
+                dst.copy_from_slice(&[src[2], src[1], src[0], src[3]]);
+            } else {
+                dst.copy_from_slice(src);
+            }
+        }
+    }
+    api.end_modify_texture(handle, width as i32, height as i32, buffer);
+    true
+}
+
+#[cfg(test)]
+const PRODUCER_ENV: &str = "RENDERING_PLUGIN_TEST_FEED_PRODUCER";
+
+#[cfg(test)]
+const TEST_FRAMES: u64 = 200;
+
+//  Every pixel of a test frame encodes its frame number, so a torn copy shows up as mixed pixels.
+#[cfg(test)]
+fn test_pixel(frame: u64) -> [u8; 4] {
+    [frame as u8, (frame >> 8) as u8, 0x55, 0xFF]
+}
+
+//  Runs the producer in a second process (this test binary again, limited to this test) and reads
+//  its frames while they are being written.
+#[test]
+fn test_texture_feed_between_processes() {
+    if let Some(path) = std::env::var_os(PRODUCER_ENV) {
+        let mut producer =
+            TextureFeedProducer::create(Path::new(&path), FeedFormat::Rgba8, 32, 16, 3).unwrap();
+        for frame in 1..=TEST_FRAMES {
+            let pixels = test_pixel(frame).repeat(32 * 16);
+            assert_eq!(producer.write_frame(&pixels), frame);
+            std::thread::sleep(std::time::Duration::from_micros(500));
+        }
+        return;
+    }
+
+    let path = std::env::temp_dir().join(format!("rendering_plugin_feed_{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([
+            "texture_feed::test_texture_feed_between_processes",
+            "--exact",
+            "--test-threads=1",
+        ])
+        .env(PRODUCER_ENV, &path)
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let started = std::time::Instant::now();
+    let mut consumer = None;
+    let mut pixels = Vec::new();
+    let mut received = Vec::new();
+    while received.last() != Some(&TEST_FRAMES) {
+        assert!(
+            started.elapsed() < std::time::Duration::from_secs(20),
+            "received {:?}",
+            received
+        );
+        if consumer.is_none() {
+            consumer = TextureFeedConsumer::open(&path).ok();
+        }
+        if let Some((frame, layout)) = consumer.as_mut().and_then(|c| c.poll(&mut pixels)) {
+            assert_eq!((layout.width, layout.height, layout.stride), (32, 16, 128));
+            assert!(pixels.chunks_exact(4).all(|p| p == test_pixel(frame)));
+            received.push(frame);
+        }
+        std::thread::yield_now();
+    }
+    assert!(child.wait().unwrap().success());
+    assert!(received.windows(2).all(|w| w[0] < w[1]));
+    drop(consumer);
+    let _ = std::fs::remove_file(&path);
+}
+
+//  A producer that restarts with a larger and then a smaller layout, while the feed stays open.
+#[test]
+fn test_texture_feed_restart() {
+    let path = std::env::temp_dir().join(format!(
+        "rendering_plugin_feed_restart_{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let mut pixels = Vec::new();
+    let mut consumer = None;
+    for (i, &(width, height)) in [(8, 4), (64, 32), (2, 2)].iter().enumerate() {
+        let mut producer =
+            TextureFeedProducer::create(&path, FeedFormat::Rgba8, width, height, 2).unwrap();
+        let consumer = consumer.get_or_insert_with(|| TextureFeedConsumer::open(&path).unwrap());
+        assert_eq!(consumer.poll(&mut pixels), None);
+        let frame = producer.write_frame(&test_pixel(i as u64).repeat((width * height) as usize));
+        assert_eq!(frame, i as u64 + 1);
+        let (polled, layout) = consumer.poll(&mut pixels).unwrap();
+        assert_eq!(polled, frame);
+        assert_eq!((layout.width, layout.height), (width, height));
+        assert!(pixels.chunks_exact(4).all(|p| p == test_pixel(i as u64)));
+    }
+    assert!(std::fs::metadata(&path).unwrap().len() >= (64 + 2 * (64 + 64 * 32 * 4)) as u64);
+    drop(consumer);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,198 @@
+//  Image files loaded into textures: `load` decodes the file and resizes it to the texture on a
+//  worker thread, and the first render event after it finished uploads the image through
+//  begin/end_modify_texture. Only the texture registered with SetTextureFromUnity has a known size,
+//  so it is the only one that can load an image; a load is dropped when another texture is
+//  registered. The texture keeps the image, instead of the plasma effect, until it is registered
+//  again with a new size, which resizes the decoded image on a worker again and uploads it.
+
+use crate::image::Image;
+use crate::logger;
+use crate::render_api::{Handle, RenderAPI};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+
+//  The decoded image and its copy at the load's size.
+type Decoded = (Arc<Image>, Image);
+
+enum State {
+    //  Decoding, or resizing the decoded image to the load's size.
+    Working(Receiver<Result<Decoded, String>>),
+    Ready {
+        source: Arc<Image>,
+        resized: Image,
+        uploaded: bool,
+    },
+}
+
+struct Load {
+    handle: Handle,
+    size: (i32, i32),
+    state: State,
+}
+
+//  Handles are only passed back to the render API on the render thread.
+unsafe impl Send for Load {}
+
+static LOAD: Mutex<Option<Load>> = Mutex::new(None);
+
+fn spawn_worker(
+    (width, height): (i32, i32),
+    source: impl FnOnce() -> Result<Arc<Image>, String> + Send + 'static,
+) -> Receiver<Result<Decoded, String>> {
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        let decoded = source().map(|source| {
+            let resized = source.resized(width as u32, height as u32);
+            (source, resized)
+        });
+        let _ = sender.send(decoded);
+    });
+    receiver
+}
+
+//  `size` is the size the texture is registered with. Replaces any earlier image, whether or not it
+//  finished decoding.
+pub fn load(handle: Handle, path: PathBuf, size: (i32, i32)) {
+    let receiver = spawn_worker(size, move || Image::load(&path).map(Arc::new));
+    *LOAD.lock().unwrap() = Some(Load {
+        handle,
+        size,
+        state: State::Working(receiver),
+    });
+}
+
+pub fn clear() {
+    *LOAD.lock().unwrap() = None;
+}
+
+//  Takes the worker's result, waiting for it when `wait` is set. A failed load is logged and
+//  dropped.
+fn receive(slot: &mut Option<Load>, wait: bool) {
+    let receiver = match slot {
+        Some(Load {
+            state: State::Working(receiver),
+            ..
+        }) => receiver,
+        _ => return,
+    };
+    let result = if wait {
+        receiver.recv().map_err(|_| TryRecvError::Disconnected)
+    } else {
+        receiver.try_recv()
+    };
+    match result {
+        Ok(Ok((source, resized))) => {
+            if let Some(load) = slot {
+                load.state = State::Ready {
+                    source,
+                    resized,
+                    uploaded: false,
+                };
+            }
+        }
+        Ok(Err(e)) => {
+            logger::error(&format!("cannot load image: {}", e));
+            *slot = None;
+        }
+        Err(TryRecvError::Empty) => {}
+        Err(TryRecvError::Disconnected) => {
+            logger::error("image loading worker panicked");
+            *slot = None;
+        }
+    }
+}
+
+//  Waits for the worker, so the next render event uploads the image. Replays call this before
+//  every render event, which makes the frame an image appears at independent of decoding time.
+pub(crate) fn finish() {
+    receive(&mut LOAD.lock().unwrap(), true);
+}
+
+//  Uploads a decoded image into `texture` (the one registered with SetTextureFromUnity) and returns
+//  whether it shows a loaded image.
+pub fn update_texture(api: &dyn RenderAPI, (handle, width, height): (Handle, i32, i32)) -> bool {
+    let mut slot = LOAD.lock().unwrap();
+    if matches!(&*slot, Some(load) if load.handle != handle || width <= 0 || height <= 0) {
+        logger::warning("dropping the image loaded into a texture that is no longer registered");
+        *slot = None;
+    }
+    receive(&mut slot, false);
+    let load = match slot.as_mut() {
+        Some(load) => load,
+        None => return false,
+    };
+    let (source, resized, uploaded) = match &mut load.state {
+        State::Ready {
+            source,
+            resized,
+            uploaded,
+        } => (source, resized, uploaded),
+        State::Working(_) => return false,
+    };
+    if load.size != (width, height) {
+        let source = source.clone();
+        load.size = (width, height);
+        load.state = State::Working(spawn_worker(load.size, move || Ok(source)));
+        return false;
+    }
+    if *uploaded {
+        return true;
+    }
+    if let Some(mut buffer) = api.begin_modify_texture(handle, width, height) {
+        if unsafe { buffer.ptr() }.is_null() {
+            return false;
+        }
+        for (y, row) in resized.pixels.chunks_exact(resized.row_pitch()).enumerate() {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    row.as_ptr(),
+                    (buffer.mut_ptr() as *mut u8).add(y * buffer.row_pitch() as usize),
+                    row.len(),
+                );
+            }
+        }
+        api.end_modify_texture(handle, width, height, buffer);
+        *uploaded = true;
+    }
+    true
+}
+
+#[test]
+fn test_load_and_upload() {
+    let _replaying = crate::replay::REPLAYING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let path = std::env::temp_dir().join(format!(
+        "rendering_plugin_texture_loader_{}.png",
+        std::process::id()
+    ));
+    let mut source = Image::new(2, 2);
+    source.fill([255, 0, 0, 255]);
+    source.save(&path).unwrap();
+
+    let api = crate::render_api_software::RenderAPISoftware::new(4, 4);
+    let mut texture = Image::new(4, 4);
+    let handle = &mut texture as *mut Image as Handle;
+    load(handle, path.clone(), (4, 4));
+    finish();
+    let _ = std::fs::remove_file(&path);
+    assert!(update_texture(api.as_ref(), (handle, 4, 4)));
+    assert_eq!(texture.pixel(3, 3), [255, 0, 0, 255]);
+
+    //  A new size is resized on the worker, and uploaded at a later render event.
+    texture = Image::new(3, 3);
+    assert!(!update_texture(api.as_ref(), (handle, 3, 3)));
+    finish();
+    assert!(update_texture(api.as_ref(), (handle, 3, 3)));
+    assert_eq!(texture.pixel(2, 2), [255, 0, 0, 255]);
+
+    //  Registering another texture drops the image, and files that do not decode are dropped.
+    let mut other = Image::new(4, 4);
+    let other = &mut other as *mut Image as Handle;
+    assert!(!update_texture(api.as_ref(), (other, 4, 4)));
+    assert!(!update_texture(api.as_ref(), (handle, 3, 3)));
+    load(handle, path, (3, 3));
+    finish();
+    assert!(LOAD.lock().unwrap().is_none());
+}
@@ -0,0 +1,120 @@
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct FrameTime {
+    pub time: f64,
+    pub delta_time: f64,
+    pub frame_index: u64,
+}
+
+impl FrameTime {
+    //  Effects only use the time as a phase, so wrap it in f64 before narrowing to keep
+    //  precision over long sessions.
+    pub fn angle(&self, speed: f64) -> f32 {
+        ((self.time * speed) % std::f64::consts::TAU) as f32
+    }
+}
+
+struct Clock {
+    frame: FrameTime,
+    source_time: Option<f64>,
+    last_source_time: Option<f64>,
+    paused: bool,
+    scale: f64,
+    fixed_step: Option<f64>,
+}
+
+impl Clock {
+    const fn new() -> Clock {
+        Clock {
+            frame: FrameTime {
+                time: 0.0,
+                delta_time: 0.0,
+                frame_index: 0,
+            },
+            source_time: None,
+            last_source_time: None,
+            paused: false,
+            scale: 1.0,
+            fixed_step: None,
+        }
+    }
+
+    fn begin_frame(&mut self) -> FrameTime {
+        let delta_time = match (self.fixed_step, self.source_time, self.last_source_time) {
+            (Some(step), _, _) => step,
+            (None, Some(source), Some(last)) => (source - last).max(0.0),
+            //  The first time from Unity is taken as is, so effects start where they used to.
+            (None, Some(source), None) => {
+                self.frame.time = source;
+                0.0
+            }
+            (None, None, _) => 0.0,
+        };
+        self.last_source_time = self.source_time;
+        self.frame.delta_time = if self.paused {
+            0.0
+        } else {
+            delta_time * self.scale
+        };
+        self.frame.time += self.frame.delta_time;
+        self.frame.frame_index += 1;
+        self.frame
+    }
+}
+
+static CLOCK: Mutex<Clock> = Mutex::new(Clock::new());
+
+pub fn set_source_time(time: f64) {
+    CLOCK.lock().unwrap().source_time = Some(time);
+}
+
+pub fn set_paused(paused: bool) {
+    CLOCK.lock().unwrap().paused = paused;
+}
+
+pub fn set_scale(scale: f64) {
+    CLOCK.lock().unwrap().scale = scale.max(0.0);
+}
+
+pub fn set_fixed_step(step: Option<f64>) {
+    CLOCK.lock().unwrap().fixed_step = step.filter(|step| *step > 0.0);
+}
+
+pub fn reset() {
+    *CLOCK.lock().unwrap() = Clock::new();
+}
+
+pub fn begin_frame() -> FrameTime {
+    CLOCK.lock().unwrap().begin_frame()
+}
+
+pub fn current() -> FrameTime {
+    CLOCK.lock().unwrap().frame
+}
+
+#[test]
+fn test_clock() {
+    let mut clock = Clock::new();
+    clock.source_time = Some(10.0);
+    assert_eq!(clock.begin_frame().time, 10.0);
+    clock.source_time = Some(10.5);
+    clock.scale = 2.0;
+    let frame = clock.begin_frame();
+    assert_eq!(
+        (frame.time, frame.delta_time, frame.frame_index),
+        (11.0, 1.0, 2)
+    );
+
+    clock.paused = true;
+    clock.source_time = Some(11.0);
+    assert_eq!(clock.begin_frame().time, 11.0);
+
+    clock.paused = false;
+    clock.scale = 1.0;
+    clock.fixed_step = Some(0.25);
+    clock.source_time = Some(100.0);
+    let frames = (0..4).map(|_| clock.begin_frame()).collect::<Vec<_>>();
+    assert_eq!(frames[3].time, 12.0);
+    assert_eq!(frames[3].frame_index, 7);
+}
@@ -0,0 +1,92 @@
+//  Messages from the graphics API's validation layers. Every message is counted by severity, and
+//  the ones at or above the log severity are forwarded to the plugin's log. Only the Vulkan
+//  backend reports messages so far, through a VK_EXT_debug_utils messenger (see vulkan_api.rs).
+
+#[cfg(backend_vulkan)]
+use crate::logger;
+use crate::ValidationSeverity;
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+#[cfg(backend_vulkan)]
+pub const VALIDATION_ENV: &str = "RENDERING_PLUGIN_VULKAN_VALIDATION";
+
+const SEVERITIES: [ValidationSeverity; 4] = [
+    ValidationSeverity::Verbose,
+    ValidationSeverity::Info,
+    ValidationSeverity::Warning,
+    ValidationSeverity::Error,
+];
+
+static COUNTS: [AtomicU32; 4] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+
+static LOG_SEVERITY: AtomicI32 = AtomicI32::new(ValidationSeverity::Warning as i32);
+
+pub fn severity_from_i32(severity: i32) -> Option<ValidationSeverity> {
+    SEVERITIES.iter().copied().find(|&s| s as i32 == severity)
+}
+
+#[cfg(backend_vulkan)]
+//  The value of the environment variable: a severity name, or anything else that is not "0" for
+//  the default of logging warnings and errors.
+pub fn parse_env(value: &str) -> Option<ValidationSeverity> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "0" | "false" | "off" => None,
+        "verbose" => Some(ValidationSeverity::Verbose),
+        "info" => Some(ValidationSeverity::Info),
+        "error" => Some(ValidationSeverity::Error),
+        _ => Some(ValidationSeverity::Warning),
+    }
+}
+
+#[cfg(backend_vulkan)]
+pub fn log_severity() -> ValidationSeverity {
+    severity_from_i32(LOG_SEVERITY.load(Ordering::Relaxed)).unwrap_or(ValidationSeverity::Warning)
+}
+
+pub fn set_log_severity(severity: ValidationSeverity) {
+    LOG_SEVERITY.store(severity as i32, Ordering::Relaxed);
+}
+
+pub fn count(severity: ValidationSeverity) -> u32 {
+    COUNTS[severity as usize].load(Ordering::Relaxed)
+}
+
+pub fn reset_counts() {
+    for count in &COUNTS {
+        count.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(backend_vulkan)]
+//  Called from whatever thread the driver reports on.
+pub fn report(severity: ValidationSeverity, message: &str) {
+    COUNTS[severity as usize].fetch_add(1, Ordering::Relaxed);
+    if (severity as i32) < LOG_SEVERITY.load(Ordering::Relaxed) {
+        return;
+    }
+    let message = format!("validation: {}", message);
+    match severity {
+        ValidationSeverity::Error => logger::error(&message),
+        ValidationSeverity::Warning => logger::warning(&message),
+        _ => logger::info(&message),
+    }
+}
+
+#[cfg(backend_vulkan)]
+#[test]
+fn test_validation_messages() {
+    assert_eq!(parse_env("1"), Some(ValidationSeverity::Warning));
+    assert_eq!(parse_env("Verbose"), Some(ValidationSeverity::Verbose));
+    assert_eq!(parse_env("0"), None);
+    assert_eq!(severity_from_i32(3), Some(ValidationSeverity::Error));
+    assert_eq!(severity_from_i32(4), None);
+
+    let errors = count(ValidationSeverity::Error);
+    report(ValidationSeverity::Error, "test error");
+    assert!(count(ValidationSeverity::Error) > errors);
+}
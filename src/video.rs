@@ -0,0 +1,509 @@
+//  Video frames in planar YUV (NV12 or I420, 4:2:0 subsampled). `submit` copies a frame from the
+//  caller's planes, and the next render event either converts it to RGBA into the texture set with
+//  `SetTextureFromUnity`, or, when plane textures are registered, writes luma and chroma into those
+//  for the shader to convert with `matrix`. Frames are scaled to the textures with nearest-neighbor
+//  sampling. The conversion uses SSE2 on x86_64 and plain Rust elsewhere.
+
+use crate::math::Mat4;
+use crate::render_api::{Handle, RenderAPI};
+use crate::{VideoFormat, VideoFrame, YuvColorSpace, YuvRange};
+use std::sync::Mutex;
+
+pub fn format_from_i32(format: i32) -> Option<VideoFormat> {
+    match format {
+        0 => Some(VideoFormat::Nv12),
+        1 => Some(VideoFormat::I420),
+        _ => None,
+    }
+}
+
+pub fn color_space_from_i32(color_space: i32) -> Option<YuvColorSpace> {
+    match color_space {
+        0 => Some(YuvColorSpace::Bt601),
+        1 => Some(YuvColorSpace::Bt709),
+        _ => None,
+    }
+}
+
+pub fn range_from_i32(range: i32) -> Option<YuvRange> {
+    match range {
+        0 => Some(YuvRange::Limited),
+        1 => Some(YuvRange::Full),
+        _ => None,
+    }
+}
+
+//  Applied to 8-bit samples: y = (Y - y_offset) * y_scale, u = U - 128, v = V - 128, and then
+//  r = y + rv * v, g = y + gu * u + gv * v, b = y + bu * u.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Coefficients {
+    y_offset: f32,
+    y_scale: f32,
+    rv: f32,
+    gu: f32,
+    gv: f32,
+    bu: f32,
+}
+
+pub fn coefficients(color_space: YuvColorSpace, range: YuvRange) -> Coefficients {
+    let (kr, kb) = match color_space {
+        YuvColorSpace::Bt601 => (0.299, 0.114),
+        YuvColorSpace::Bt709 => (0.2126, 0.0722),
+    };
+    let kg = 1.0 - kr - kb;
+    let (y_offset, y_scale, c_scale) = match range {
+        YuvRange::Limited => (16.0, 255.0 / 219.0, 255.0 / 224.0),
+        YuvRange::Full => (0.0, 1.0, 1.0),
+    };
+    Coefficients {
+        y_offset,
+        y_scale,
+        rv: 2.0 * (1.0 - kr) * c_scale,
+        gu: -2.0 * kb * (1.0 - kb) / kg * c_scale,
+        gv: -2.0 * kr * (1.0 - kr) / kg * c_scale,
+        bu: 2.0 * (1.0 - kb) * c_scale,
+    }
+}
+
+//  The same conversion for a shader: rgb = (matrix * float4(Y, U, V, 1)).rgb, with the samples read
+//  from the plane textures as 0..1 values.
+pub fn matrix(color_space: YuvColorSpace, range: YuvRange) -> Mat4 {
+    let c = coefficients(color_space, range);
+    let offset = |u: f32, v: f32| -(c.y_scale * c.y_offset + 128.0 * (u + v)) / 255.0;
+    #[rustfmt::skip]
+    let matrix = Mat4::from_cols_array([
+        c.y_scale, c.y_scale, c.y_scale, 0.0,
+        0.0, c.gu, c.bu, 0.0,
+        c.rv, c.gv, 0.0, 0.0,
+        offset(0.0, c.rv), offset(c.gu, c.gv), offset(c.bu, 0.0), 1.0,
+    ]);
+    matrix
+}
+
+fn to_u8(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+//  Converts one row: `luma` has a sample per pixel and `chroma` an interleaved U, V pair per two.
+fn convert_row_scalar(c: &Coefficients, luma: &[u8], chroma: &[u8], rgba: &mut [u8]) {
+    for (x, dst) in rgba.chunks_exact_mut(4).enumerate() {
+        let y = (luma[x] as f32 - c.y_offset) * c.y_scale;
+        let u = chroma[x / 2 * 2] as f32 - 128.0;
+        let v = chroma[x / 2 * 2 + 1] as f32 - 128.0;
+        dst[0] = to_u8(y + c.rv * v);
+        dst[1] = to_u8(y + c.gu * u + c.gv * v);
+        dst[2] = to_u8(y + c.bu * u);
+        dst[3] = 255;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn convert_row(c: &Coefficients, luma: &[u8], chroma: &[u8], rgba: &mut [u8]) {
+    use std::arch::x86_64::*;
+    let width = rgba.len() / 4;
+    let simd_width = width & !3;
+    //  SSE2 is part of x86_64, so no runtime detection is needed. Four pixels per iteration.
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let y_offset = _mm_set1_ps(c.y_offset);
+        let y_scale = _mm_set1_ps(c.y_scale);
+        let chroma_offset = _mm_set1_ps(128.0);
+        let (rv, gu, gv, bu) = (
+            _mm_set1_ps(c.rv),
+            _mm_set1_ps(c.gu),
+            _mm_set1_ps(c.gv),
+            _mm_set1_ps(c.bu),
+        );
+        let alpha = _mm_set1_epi32(255);
+        let widen = |bytes: &[u8]| {
+            let packed =
+                _mm_cvtsi32_si128(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+            _mm_unpacklo_epi16(_mm_unpacklo_epi8(packed, zero), zero)
+        };
+        for x in (0..simd_width).step_by(4) {
+            let y = _mm_mul_ps(
+                _mm_sub_ps(_mm_cvtepi32_ps(widen(&luma[x..])), y_offset),
+                y_scale,
+            );
+            //  U0 V0 U1 V1, each shared by two horizontally adjacent pixels.
+            let uv = widen(&chroma[x..]);
+            let u = _mm_sub_ps(
+                _mm_cvtepi32_ps(_mm_shuffle_epi32(uv, 0b10_10_00_00)),
+                chroma_offset,
+            );
+            let v = _mm_sub_ps(
+                _mm_cvtepi32_ps(_mm_shuffle_epi32(uv, 0b11_11_01_01)),
+                chroma_offset,
+            );
+            let r = _mm_cvtps_epi32(_mm_add_ps(y, _mm_mul_ps(rv, v)));
+            let g = _mm_cvtps_epi32(_mm_add_ps(
+                y,
+                _mm_add_ps(_mm_mul_ps(gu, u), _mm_mul_ps(gv, v)),
+            ));
+            let b = _mm_cvtps_epi32(_mm_add_ps(y, _mm_mul_ps(bu, u)));
+            //  Interleave into r0 g0 b0 a0 r1 ..., saturating to 0..255 on the final pack.
+            let rb = _mm_packs_epi32(r, b);
+            let ga = _mm_packs_epi32(g, alpha);
+            let rg = _mm_unpacklo_epi16(rb, ga);
+            let ba = _mm_unpackhi_epi16(rb, ga);
+            let pixels = _mm_packus_epi16(_mm_unpacklo_epi32(rg, ba), _mm_unpackhi_epi32(rg, ba));
+            _mm_storeu_si128(rgba[x * 4..x * 4 + 16].as_mut_ptr() as *mut __m128i, pixels);
+        }
+    }
+    convert_row_scalar(
+        c,
+        &luma[simd_width..],
+        &chroma[simd_width..],
+        &mut rgba[simd_width * 4..],
+    );
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn convert_row(c: &Coefficients, luma: &[u8], chroma: &[u8], rgba: &mut [u8]) {
+    convert_row_scalar(c, luma, chroma, rgba);
+}
+
+//  A submitted frame, with the chroma planes interleaved as in NV12.
+struct Frame {
+    width: usize,
+    height: usize,
+    luma: Vec<u8>,
+    chroma: Vec<u8>,
+    coefficients: Coefficients,
+}
+
+impl Frame {
+    fn chroma_width(&self) -> usize {
+        self.width.div_ceil(2)
+    }
+
+    fn luma_row(&self, y: usize) -> &[u8] {
+        &self.luma[y * self.width..(y + 1) * self.width]
+    }
+
+    fn chroma_row(&self, y: usize) -> &[u8] {
+        let pitch = self.chroma_width() * 2;
+        &self.chroma[y * pitch..(y + 1) * pitch]
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PlaneTextures {
+    luma: (Handle, i32, i32),
+    chroma: (Handle, i32, i32),
+}
+
+struct VideoState {
+    active: bool,
+    frame: Option<Frame>,
+    planes: Option<PlaneTextures>,
+}
+
+//  Handles are only passed back to the render API on the render thread.
+unsafe impl Send for VideoState {}
+
+static VIDEO: Mutex<VideoState> = Mutex::new(VideoState {
+    active: false,
+    frame: None,
+    planes: None,
+});
+
+//  One plane of a frame in the caller's memory.
+struct Plane {
+    data: *const u8,
+    stride: usize,
+    row_bytes: usize,
+    rows: usize,
+}
+
+impl Plane {
+    unsafe fn row(&self, y: usize) -> &[u8] {
+        std::slice::from_raw_parts(self.data.add(y * self.stride), self.row_bytes)
+    }
+}
+
+//  The frame's planes, checked against its format and size: Y, then UV for NV12 (where `u_plane`
+//  holds the interleaved chroma and `v_plane` is ignored), or U and V for I420.
+fn frame_planes(frame: &VideoFrame) -> Result<(VideoFormat, Vec<Plane>), String> {
+    let format = format_from_i32(frame.format)
+        .ok_or_else(|| format!("unknown video format {}", frame.format))?;
+    if frame.width <= 0 || frame.height <= 0 {
+        return Err(format!(
+            "invalid video frame size {}x{}",
+            frame.width, frame.height
+        ));
+    }
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let planes: &[(Handle, i32, usize, usize)] = match format {
+        VideoFormat::Nv12 => &[
+            (frame.y_plane, frame.y_stride, width, height),
+            (
+                frame.u_plane,
+                frame.u_stride,
+                chroma_width * 2,
+                chroma_height,
+            ),
+        ],
+        VideoFormat::I420 => &[
+            (frame.y_plane, frame.y_stride, width, height),
+            (frame.u_plane, frame.u_stride, chroma_width, chroma_height),
+            (frame.v_plane, frame.v_stride, chroma_width, chroma_height),
+        ],
+    };
+    if planes.iter().any(|&(data, stride, row_bytes, _)| {
+        data.is_null() || stride < 0 || (stride as usize) < row_bytes
+    }) {
+        return Err("missing video plane or stride too small".to_string());
+    }
+    let planes = planes
+        .iter()
+        .map(|&(data, stride, row_bytes, rows)| Plane {
+            data: data as *const u8,
+            stride: stride as usize,
+            row_bytes,
+            rows,
+        })
+        .collect();
+    Ok((format, planes))
+}
+
+fn read_frame(frame: &VideoFrame) -> Result<Frame, String> {
+    let color_space = color_space_from_i32(frame.color_space)
+        .ok_or_else(|| format!("unknown YUV color space {}", frame.color_space))?;
+    let range =
+        range_from_i32(frame.range).ok_or_else(|| format!("unknown YUV range {}", frame.range))?;
+    let (format, planes) = frame_planes(frame)?;
+
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let mut luma = Vec::with_capacity(width * height);
+    let mut chroma = Vec::with_capacity(width.div_ceil(2) * height.div_ceil(2) * 2);
+    unsafe {
+        for y in 0..planes[0].rows {
+            luma.extend_from_slice(planes[0].row(y));
+        }
+        for y in 0..planes[1].rows {
+            match format {
+                VideoFormat::Nv12 => chroma.extend_from_slice(planes[1].row(y)),
+                VideoFormat::I420 => chroma.extend(
+                    planes[1]
+                        .row(y)
+                        .iter()
+                        .zip(planes[2].row(y))
+                        .flat_map(|(&u, &v)| [u, v]),
+                ),
+            }
+        }
+    }
+
+    Ok(Frame {
+        width,
+        height,
+        luma,
+        chroma,
+        coefficients: coefficients(color_space, range),
+    })
+}
+
+//  The frame's planes without row padding, as (stride, data), for the recorder. Empty when the
+//  frame would be rejected.
+pub fn packed_planes(frame: &VideoFrame) -> Vec<(i32, Vec<u8>)> {
+    let planes = match frame_planes(frame) {
+        Ok((_, planes)) => planes,
+        Err(_) => return Vec::new(),
+    };
+    planes
+        .iter()
+        .map(|plane| {
+            let mut data = Vec::with_capacity(plane.row_bytes * plane.rows);
+            for y in 0..plane.rows {
+                data.extend_from_slice(unsafe { plane.row(y) });
+            }
+            (plane.row_bytes as i32, data)
+        })
+        .collect()
+}
+
+//  Copies the planes, so the caller may reuse them as soon as this returns.
+pub fn submit(frame: &VideoFrame) -> Result<(), String> {
+    let frame = read_frame(frame)?;
+    let mut video = VIDEO.lock().unwrap();
+    video.active = true;
+    video.frame = Some(frame);
+    Ok(())
+}
+
+pub fn set_plane_textures(luma: (Handle, i32, i32), chroma: (Handle, i32, i32)) {
+    VIDEO.lock().unwrap().planes = if luma.0.is_null() || chroma.0.is_null() {
+        None
+    } else {
+        Some(PlaneTextures { luma, chroma })
+    };
+}
+
+pub fn clear() {
+    let mut video = VIDEO.lock().unwrap();
+    video.active = false;
+    video.frame = None;
+    video.planes = None;
+}
+
+//  Writes a `width` x `height` image into the texture, scaled with nearest-neighbor sampling.
+//  `row` produces source row `y` as RGBA.
+fn write_texture(
+    api: &dyn RenderAPI,
+    (handle, texture_width, texture_height): (Handle, i32, i32),
+    width: usize,
+    height: usize,
+    mut row: impl FnMut(usize, &mut [u8]),
+) {
+    if handle.is_null() || texture_width <= 0 || texture_height <= 0 {
+        return;
+    }
+    let mut buffer = match api.begin_modify_texture(handle, texture_width, texture_height) {
+        Some(buffer) => buffer,
+        None => return,
+    };
+    if unsafe { buffer.ptr() }.is_null() {
+        return;
+    }
+    let (dst_width, dst_height) = (texture_width as usize, texture_height as usize);
+    let mut source_row = vec![0; width * 4];
+    let mut converted = None;
+    for y in 0..dst_height {
+        let dst = unsafe {
+            std::slice::from_raw_parts_mut(
+                (buffer.mut_ptr() as *mut u8).add(y * buffer.row_pitch() as usize),
+                dst_width * 4,
+            )
+        };
+        let src_y = y * height / dst_height;
+        if dst_width == width {
+            row(src_y, dst);
+            continue;
+        }
+        if converted != Some(src_y) {
+            row(src_y, &mut source_row);
+            converted = Some(src_y);
+        }
+        for (x, pixel) in dst.chunks_exact_mut(4).enumerate() {
+            let src_x = x * width / dst_width * 4;
+            pixel.copy_from_slice(&source_row[src_x..src_x + 4]);
+        }
+    }
+    api.end_modify_texture(handle, texture_width, texture_height, buffer);
+}
+
+fn upload(api: &dyn RenderAPI, frame: &Frame, target: Result<PlaneTextures, (Handle, i32, i32)>) {
+    match target {
+        Ok(planes) => {
+            write_texture(api, planes.luma, frame.width, frame.height, |y, dst| {
+                for (pixel, &luma) in dst.chunks_exact_mut(4).zip(frame.luma_row(y)) {
+                    pixel.copy_from_slice(&[luma, luma, luma, 255]);
+                }
+            });
+            write_texture(
+                api,
+                planes.chroma,
+                frame.chroma_width(),
+                frame.height.div_ceil(2),
+                |y, dst| {
+                    for (pixel, uv) in dst.chunks_exact_mut(4).zip(frame.chroma_row(y).chunks(2)) {
+                        pixel.copy_from_slice(&[uv[0], uv[1], 0, 255]);
+                    }
+                },
+            );
+        }
+        Err(texture) => write_texture(api, texture, frame.width, frame.height, |y, dst| {
+            convert_row(
+                &frame.coefficients,
+                frame.luma_row(y),
+                frame.chroma_row(y / 2),
+                dst,
+            )
+        }),
+    }
+}
+
+//  Uploads the frame submitted since the last call, if any. Returns whether video owns the texture
+//  set with `SetTextureFromUnity`, which is the case once a frame was submitted and no plane
+//  textures are registered.
+pub fn update_texture(api: &dyn RenderAPI, handle: Handle, width: i32, height: i32) -> bool {
+    let mut video = VIDEO.lock().unwrap();
+    if !video.active {
+        return false;
+    }
+    let planes = video.planes;
+    if let Some(frame) = video.frame.take() {
+        upload(api, &frame, planes.ok_or((handle, width, height)));
+    }
+    planes.is_none()
+}
+
+#[test]
+fn test_yuv_conversion() {
+    let c = coefficients(YuvColorSpace::Bt601, YuvRange::Limited);
+    //  Black, white and red in BT.601 limited range.
+    let mut rgba = [0; 8];
+    convert_row(&c, &[16, 235], &[128, 128], &mut rgba);
+    assert_eq!(rgba, [0, 0, 0, 255, 255, 255, 255, 255]);
+    convert_row(&c, &[81], &[90, 240], &mut rgba[..4]);
+    assert!(rgba[..4]
+        .iter()
+        .zip(&[255, 0, 0, 255])
+        .all(|(&a, &b)| (a as i32 - b).abs() <= 1));
+
+    //  The SIMD path against the scalar one, over a width with a scalar tail.
+    for &(color_space, range) in &[
+        (YuvColorSpace::Bt601, YuvRange::Full),
+        (YuvColorSpace::Bt709, YuvRange::Limited),
+    ] {
+        let c = coefficients(color_space, range);
+        let luma = (0..257).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        let chroma = (0..258).map(|i| (i * 13 + 5) as u8).collect::<Vec<_>>();
+        let mut simd = vec![0; luma.len() * 4];
+        let mut scalar = vec![0; luma.len() * 4];
+        convert_row(&c, &luma, &chroma, &mut simd);
+        convert_row_scalar(&c, &luma, &chroma, &mut scalar);
+        assert!(simd
+            .iter()
+            .zip(&scalar)
+            .all(|(&a, &b)| (a as i32 - b as i32).abs() <= 1));
+
+        //  The shader matrix matches the CPU conversion.
+        let m = matrix(color_space, range).to_cols_array();
+        let (y, u, v) = (
+            luma[3] as f32 / 255.0,
+            chroma[2] as f32 / 255.0,
+            chroma[3] as f32 / 255.0,
+        );
+        for channel in 0..3 {
+            let value = m[channel] * y + m[4 + channel] * u + m[8 + channel] * v + m[12 + channel];
+            assert!((to_u8(value * 255.0) as i32 - scalar[12 + channel] as i32).abs() <= 1);
+        }
+    }
+
+    //  A 2x2 I420 frame, red on the left and a darker red on the right, uploaded at twice its size.
+    let (y, u, v) = ([81u8, 41, 81, 41], [90u8], [240u8]);
+    let frame = read_frame(&VideoFrame {
+        format: VideoFormat::I420 as i32,
+        width: 2,
+        height: 2,
+        color_space: YuvColorSpace::Bt601 as i32,
+        range: YuvRange::Limited as i32,
+        y_plane: y.as_ptr() as Handle,
+        y_stride: 2,
+        u_plane: u.as_ptr() as Handle,
+        u_stride: 1,
+        v_plane: v.as_ptr() as Handle,
+        v_stride: 1,
+    })
+    .unwrap();
+    let api = crate::render_api_software::RenderAPISoftware::new(4, 4);
+    let mut texture = crate::image::Image::new(4, 4);
+    let handle = &mut texture as *mut crate::image::Image as Handle;
+    upload(api.as_ref(), &frame, Err((handle, 4, 4)));
+    assert!(texture.pixel(1, 3)[0] >= 254);
+    assert_eq!(texture.pixel(1, 3), texture.pixel(0, 0));
+    assert!(texture.pixel(2, 0)[0] > 0 && texture.pixel(2, 0)[0] < 255);
+}
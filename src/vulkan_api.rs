@@ -1,24 +1,194 @@
-use unity_native_plugin::graphics::GfxRenderer::Vulkan;
+use crate::logger;
+use crate::validation;
+use crate::vulkan_trace;
+use crate::ValidationSeverity;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
+//  Named after the Vulkan C type it mirrors; the 1.1 and 1.2 entry functions are kept loaded for
+//  hooks that need them.
+#[allow(non_camel_case_types, dead_code)]
 pub struct vulkan_functions {
     static_fn: ash::vk::StaticFn,
     entry_fn_1_0: ash::vk::EntryFnV1_0,
     entry_fn_1_1: ash::vk::EntryFnV1_1,
     entry_fn_1_2: ash::vk::EntryFnV1_2,
+    instance_fn_1_0: ash::vk::InstanceFnV1_0,
 }
 
 static mut VULKAN_FUNCTIONS: Option<vulkan_functions> = None;
 
-impl vulkan_functions {
-    pub unsafe fn default() -> &'static vulkan_functions {
-        vulkan_functions::default_mut()
+//  Names the plugin wants enabled on top of what Unity asks for. They have to be requested before
+//  Unity creates the instance and device, i.e. while the plugin is being loaded.
+struct CreateInfoRequests {
+    instance_extensions: Vec<CString>,
+    instance_layers: Vec<CString>,
+    device_extensions: Vec<CString>,
+}
+
+static REQUESTS: Mutex<CreateInfoRequests> = Mutex::new(CreateInfoRequests {
+    instance_extensions: Vec::new(),
+    instance_layers: Vec::new(),
+    device_extensions: Vec::new(),
+});
+
+fn add_request(names: &mut Vec<CString>, name: &CStr) {
+    if !names.iter().any(|n| n.as_c_str() == name) {
+        names.push(name.to_owned());
     }
+}
 
-    unsafe fn default_mut() -> &'static mut vulkan_functions {
-        if VULKAN_FUNCTIONS.is_none() {
-            VULKAN_FUNCTIONS = Some(std::mem::zeroed());
+pub fn request_instance_extension(name: &CStr) {
+    add_request(&mut REQUESTS.lock().unwrap().instance_extensions, name);
+}
+
+pub fn request_instance_layer(name: &CStr) {
+    add_request(&mut REQUESTS.lock().unwrap().instance_layers, name);
+}
+
+pub fn request_device_extension(name: &CStr) {
+    add_request(&mut REQUESTS.lock().unwrap().device_extensions, name);
+}
+
+const VALIDATION_LAYER: &[u8] = b"VK_LAYER_KHRONOS_validation\0";
+
+static VALIDATION: AtomicBool = AtomicBool::new(false);
+static mut DEBUG_MESSENGER: Option<(ash::vk::ExtDebugUtilsFn, ash::vk::DebugUtilsMessengerEXT)> =
+    None;
+
+//  Adds the validation layer and VK_EXT_debug_utils to Unity's instance and routes the messages
+//  to `validation`. Like the other requests this only works before the instance is created.
+pub fn enable_validation() {
+    VALIDATION.store(true, Ordering::Relaxed);
+    request_instance_layer(CStr::from_bytes_with_nul(VALIDATION_LAYER).unwrap());
+    request_instance_extension(ash::vk::ExtDebugUtilsFn::name());
+}
+
+//  Warnings and errors are always requested so they are counted; info and verbose messages only
+//  when they would be logged, as the layer produces a lot of them.
+fn debug_messenger_create_info() -> ash::vk::DebugUtilsMessengerCreateInfoEXT {
+    let mut message_severity = ash::vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+        | ash::vk::DebugUtilsMessageSeverityFlagsEXT::ERROR;
+    match validation::log_severity() {
+        ValidationSeverity::Verbose => {
+            message_severity |= ash::vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+                | ash::vk::DebugUtilsMessageSeverityFlagsEXT::INFO
         }
-        VULKAN_FUNCTIONS.as_mut().unwrap()
+        ValidationSeverity::Info => {
+            message_severity |= ash::vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+        }
+        _ => {}
+    }
+    ash::vk::DebugUtilsMessengerCreateInfoEXT {
+        message_severity,
+        message_type: ash::vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+            | ash::vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+            | ash::vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+        pfn_user_callback: Some(debug_utils_messenger_callback),
+        ..Default::default()
+    }
+}
+
+unsafe extern "system" fn debug_utils_messenger_callback(
+    message_severity: ash::vk::DebugUtilsMessageSeverityFlagsEXT,
+    _: ash::vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const ash::vk::DebugUtilsMessengerCallbackDataEXT,
+    _: *mut std::os::raw::c_void,
+) -> ash::vk::Bool32 {
+    let severity = if message_severity.contains(ash::vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        ValidationSeverity::Error
+    } else if message_severity.contains(ash::vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        ValidationSeverity::Warning
+    } else if message_severity.contains(ash::vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        ValidationSeverity::Info
+    } else {
+        ValidationSeverity::Verbose
+    };
+    let to_string = |s: *const c_char| {
+        if s.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(s).to_string_lossy().into_owned()
+        }
+    };
+    let (id_name, message) = if p_callback_data.is_null() {
+        (String::new(), String::new())
+    } else {
+        (
+            to_string((*p_callback_data).p_message_id_name),
+            to_string((*p_callback_data).p_message),
+        )
+    };
+    if id_name.is_empty() {
+        validation::report(severity, &message);
+    } else {
+        validation::report(severity, &format!("[{}] {}", id_name, message));
+    }
+    //  The call that triggered the message must not be aborted.
+    ash::vk::FALSE
+}
+
+unsafe fn enumerate<T>(f: impl Fn(*mut u32, *mut T) -> ash::vk::Result) -> Vec<T> {
+    let mut count = 0;
+    if f(&mut count, std::ptr::null_mut()) != ash::vk::Result::SUCCESS {
+        return Vec::new();
+    }
+    let mut ret = Vec::with_capacity(count as usize);
+    match f(&mut count, ret.as_mut_ptr()) {
+        ash::vk::Result::SUCCESS | ash::vk::Result::INCOMPLETE => ret.set_len(count as usize),
+        _ => {}
+    }
+    ret
+}
+
+//  Unity's names followed by the requested ones it does not already enable. Names the driver does
+//  not offer are left out with a warning, since enabling them would fail the whole creation.
+unsafe fn append_names(
+    kind: &str,
+    count: u32,
+    names: *const *const c_char,
+    requested: &[CString],
+    available: &[&CStr],
+) -> Vec<*const c_char> {
+    let mut ret = if names.is_null() {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(names, count as usize).to_vec()
+    };
+    for name in requested {
+        if ret.iter().any(|&n| CStr::from_ptr(n) == name.as_c_str()) {
+            continue;
+        }
+        if available.contains(&name.as_c_str()) {
+            ret.push(name.as_ptr());
+        } else {
+            logger::warning(&format!(
+                "Vulkan {} {} is not available",
+                kind,
+                name.to_string_lossy()
+            ));
+        }
+    }
+    ret
+}
+
+//  `function` is one of the hooks below, cast to a fn pointer.
+pub(crate) unsafe fn hook<F: Copy>(function: F) -> ash::vk::PFN_vkVoidFunction {
+    assert_eq!(
+        std::mem::size_of::<F>(),
+        std::mem::size_of::<ash::vk::PFN_vkVoidFunction>()
+    );
+    std::mem::transmute_copy(&function)
+}
+
+impl vulkan_functions {
+    //  The hooks are only handed out by `intercept_vulkan_initialization`, which sets the functions.
+    pub unsafe fn default() -> &'static vulkan_functions {
+        (*std::ptr::addr_of!(VULKAN_FUNCTIONS))
+            .as_ref()
+            .expect("Vulkan functions used before the initialization was intercepted")
     }
 
     fn new(get_instance_proc_addr: ash::vk::PFN_vkGetInstanceProcAddr) -> vulkan_functions {
@@ -44,15 +214,30 @@ impl vulkan_functions {
             )
         });
 
+        //  Loaded for real once Unity has created its instance.
+        let instance_fn_1_0 = ash::vk::InstanceFnV1_0::load(|_| std::ptr::null());
+
         vulkan_functions {
             static_fn,
             entry_fn_1_0,
             entry_fn_1_1,
             entry_fn_1_2,
+            instance_fn_1_0,
         }
     }
 
-    pub extern "system" fn intercept_vulkan_initialization(
+    //  No reference returned by `default` may be in use while this replaces the functions.
+    unsafe fn load_instance_functions(instance: ash::vk::Instance) {
+        if let Some(functions) = (*std::ptr::addr_of_mut!(VULKAN_FUNCTIONS)).as_mut() {
+            let get_instance_proc_addr = functions.static_fn.get_instance_proc_addr;
+            let instance_fn_1_0 = ash::vk::InstanceFnV1_0::load(|name| {
+                std::mem::transmute(get_instance_proc_addr(instance, name.as_ptr()))
+            });
+            functions.instance_fn_1_0 = instance_fn_1_0;
+        }
+    }
+
+    pub unsafe extern "system" fn intercept_vulkan_initialization(
         get_instance_proc_addr: ash::vk::PFN_vkGetInstanceProcAddr,
         _: *mut ::std::os::raw::c_void,
     ) -> ash::vk::PFN_vkGetInstanceProcAddr {
@@ -62,29 +247,252 @@ impl vulkan_functions {
         }
     }
 
+    //  Everything that is not hooked is forwarded to the loader, including lookups Unity makes
+    //  through the returned vkGetInstanceProcAddr itself. Without an instance only the global
+    //  functions can be looked up, so the hooked instance and device functions are not handed out.
     extern "system" fn hook_vk_get_instance_proc_addr(
-        _: ash::vk::Instance,
+        instance: ash::vk::Instance,
         func_name: *const std::os::raw::c_char,
     ) -> ash::vk::PFN_vkVoidFunction {
         if func_name.is_null() {
             None
         } else {
             unsafe {
-                let func_name = std::ffi::CStr::from_ptr(func_name);
-                if func_name.to_bytes() == "vkCreateInstance".as_bytes() {
-                    Some(std::mem::transmute(vulkan_functions::hook_vk_create_instance as usize))
-                } else {
-                    None
+                let name = std::ffi::CStr::from_ptr(func_name).to_bytes();
+                match name {
+                    b"vkGetInstanceProcAddr" => hook(
+                        vulkan_functions::hook_vk_get_instance_proc_addr
+                            as unsafe extern "system" fn(_, _) -> _,
+                    ),
+                    b"vkCreateInstance" => hook(
+                        vulkan_functions::hook_vk_create_instance
+                            as unsafe extern "system" fn(_, _, _) -> _,
+                    ),
+                    b"vkDestroyInstance" | b"vkCreateDevice" | b"vkGetDeviceProcAddr"
+                        if instance == ash::vk::Instance::null() =>
+                    {
+                        None
+                    }
+                    b"vkDestroyInstance" => hook(
+                        vulkan_functions::hook_vk_destroy_instance
+                            as unsafe extern "system" fn(_, _),
+                    ),
+                    b"vkCreateDevice" => hook(
+                        vulkan_functions::hook_vk_create_device
+                            as unsafe extern "system" fn(_, _, _, _) -> _,
+                    ),
+                    b"vkGetDeviceProcAddr" if vulkan_trace::is_enabled() => hook(
+                        vulkan_functions::hook_vk_get_device_proc_addr
+                            as unsafe extern "system" fn(_, _) -> _,
+                    ),
+                    _ => vulkan_trace::wrap(
+                        name,
+                        Self::default()
+                            .static_fn
+                            .get_instance_proc_addr(instance, func_name),
+                    ),
                 }
             }
         }
     }
 
+    //  Only handed out while tracing, so device functions can be wrapped too.
+    unsafe extern "system" fn hook_vk_get_device_proc_addr(
+        device: ash::vk::Device,
+        func_name: *const std::os::raw::c_char,
+    ) -> ash::vk::PFN_vkVoidFunction {
+        if func_name.is_null() {
+            return None;
+        }
+        let name = std::ffi::CStr::from_ptr(func_name).to_bytes();
+        if name == b"vkGetDeviceProcAddr" {
+            return hook(
+                vulkan_functions::hook_vk_get_device_proc_addr
+                    as unsafe extern "system" fn(_, _) -> _,
+            );
+        }
+        vulkan_trace::wrap(
+            name,
+            Self::default()
+                .instance_fn_1_0
+                .get_device_proc_addr(device, func_name),
+        )
+    }
+
     unsafe extern "system" fn hook_vk_create_instance(
         p_create_info: *const ash::vk::InstanceCreateInfo,
         p_allocator: *const ash::vk::AllocationCallbacks,
         p_instance: *mut ash::vk::Instance,
     ) -> ash::vk::Result {
-        Self::default().entry_fn_1_0.create_instance(p_create_info, p_allocator, p_instance)
+        let functions = Self::default();
+        if p_create_info.is_null() {
+            return functions
+                .entry_fn_1_0
+                .create_instance(p_create_info, p_allocator, p_instance);
+        }
+
+        let requests = REQUESTS.lock().unwrap();
+        let available_layers = enumerate(|count, properties| {
+            functions
+                .entry_fn_1_0
+                .enumerate_instance_layer_properties(count, properties)
+        });
+        let available_layer_names = available_layers
+            .iter()
+            .map(|p: &ash::vk::LayerProperties| CStr::from_ptr(p.layer_name.as_ptr()))
+            .collect::<Vec<_>>();
+        let layers = append_names(
+            "layer",
+            (*p_create_info).enabled_layer_count,
+            (*p_create_info).pp_enabled_layer_names,
+            &requests.instance_layers,
+            &available_layer_names,
+        );
+
+        //  Extensions provided by the enabled layers count as available too.
+        let mut available_extensions = enumerate(|count, properties| {
+            functions
+                .entry_fn_1_0
+                .enumerate_instance_extension_properties(std::ptr::null(), count, properties)
+        });
+        for &layer in &layers {
+            available_extensions.extend(enumerate(|count, properties| {
+                functions
+                    .entry_fn_1_0
+                    .enumerate_instance_extension_properties(layer, count, properties)
+            }));
+        }
+        let available_extension_names = available_extensions
+            .iter()
+            .map(|p: &ash::vk::ExtensionProperties| CStr::from_ptr(p.extension_name.as_ptr()))
+            .collect::<Vec<_>>();
+        let extensions = append_names(
+            "instance extension",
+            (*p_create_info).enabled_extension_count,
+            (*p_create_info).pp_enabled_extension_names,
+            &requests.instance_extensions,
+            &available_extension_names,
+        );
+
+        let mut create_info = *p_create_info;
+        create_info.enabled_layer_count = layers.len() as u32;
+        create_info.pp_enabled_layer_names = layers.as_ptr();
+        create_info.enabled_extension_count = extensions.len() as u32;
+        create_info.pp_enabled_extension_names = extensions.as_ptr();
+
+        //  Chained into the create info too, so messages about creating and destroying the
+        //  instance itself are reported.
+        let debug_utils = VALIDATION.load(Ordering::Relaxed)
+            && extensions
+                .iter()
+                .any(|&n| CStr::from_ptr(n) == ash::vk::ExtDebugUtilsFn::name());
+        let mut messenger_create_info = debug_messenger_create_info();
+        if debug_utils {
+            messenger_create_info.p_next = create_info.p_next;
+            create_info.p_next = &messenger_create_info as *const _ as *const std::os::raw::c_void;
+        }
+
+        let result = functions
+            .entry_fn_1_0
+            .create_instance(&create_info, p_allocator, p_instance);
+        if result == ash::vk::Result::SUCCESS {
+            if debug_utils {
+                functions.create_debug_messenger(*p_instance, &messenger_create_info);
+            }
+            vulkan_functions::load_instance_functions(*p_instance);
+        }
+        result
+    }
+
+    unsafe fn create_debug_messenger(
+        &self,
+        instance: ash::vk::Instance,
+        create_info: &ash::vk::DebugUtilsMessengerCreateInfoEXT,
+    ) {
+        let debug_utils_fn = ash::vk::ExtDebugUtilsFn::load(|name| {
+            std::mem::transmute(
+                self.static_fn
+                    .get_instance_proc_addr(instance, name.as_ptr()),
+            )
+        });
+        let mut create_info = *create_info;
+        create_info.p_next = std::ptr::null();
+        let mut messenger = ash::vk::DebugUtilsMessengerEXT::null();
+        let result = debug_utils_fn.create_debug_utils_messenger_ext(
+            instance,
+            &create_info,
+            std::ptr::null(),
+            &mut messenger,
+        );
+        if result == ash::vk::Result::SUCCESS {
+            DEBUG_MESSENGER = Some((debug_utils_fn, messenger));
+        } else {
+            logger::warning(&format!(
+                "failed to create the Vulkan debug messenger: {}",
+                result
+            ));
+        }
+    }
+
+    unsafe extern "system" fn hook_vk_destroy_instance(
+        instance: ash::vk::Instance,
+        p_allocator: *const ash::vk::AllocationCallbacks,
+    ) {
+        if let Some((debug_utils_fn, messenger)) = DEBUG_MESSENGER.take() {
+            debug_utils_fn.destroy_debug_utils_messenger_ext(instance, messenger, std::ptr::null());
+        }
+        Self::default()
+            .instance_fn_1_0
+            .destroy_instance(instance, p_allocator);
+    }
+
+    unsafe extern "system" fn hook_vk_create_device(
+        physical_device: ash::vk::PhysicalDevice,
+        p_create_info: *const ash::vk::DeviceCreateInfo,
+        p_allocator: *const ash::vk::AllocationCallbacks,
+        p_device: *mut ash::vk::Device,
+    ) -> ash::vk::Result {
+        let functions = Self::default();
+        if p_create_info.is_null() {
+            return functions.instance_fn_1_0.create_device(
+                physical_device,
+                p_create_info,
+                p_allocator,
+                p_device,
+            );
+        }
+
+        let requests = REQUESTS.lock().unwrap();
+        let available_extensions = enumerate(|count, properties| {
+            functions
+                .instance_fn_1_0
+                .enumerate_device_extension_properties(
+                    physical_device,
+                    std::ptr::null(),
+                    count,
+                    properties,
+                )
+        });
+        let available_extension_names = available_extensions
+            .iter()
+            .map(|p: &ash::vk::ExtensionProperties| CStr::from_ptr(p.extension_name.as_ptr()))
+            .collect::<Vec<_>>();
+        let extensions = append_names(
+            "device extension",
+            (*p_create_info).enabled_extension_count,
+            (*p_create_info).pp_enabled_extension_names,
+            &requests.device_extensions,
+            &available_extension_names,
+        );
+
+        let mut create_info = *p_create_info;
+        create_info.enabled_extension_count = extensions.len() as u32;
+        create_info.pp_enabled_extension_names = extensions.as_ptr();
+        functions.instance_fn_1_0.create_device(
+            physical_device,
+            &create_info,
+            p_allocator,
+            p_device,
+        )
     }
 }
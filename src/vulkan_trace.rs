@@ -0,0 +1,466 @@
+//  Opt-in tracer for Unity's Vulkan calls. When enabled, the lookups in vulkan_api.rs hand out
+//  wrappers for the functions listed in `traced_functions!`, which record the call name, its
+//  arguments and how long the driver took into a ring buffer. A frame ends at vkQueuePresentKHR;
+//  finished frames can be written as Chrome trace event JSON (chrome://tracing, Perfetto).
+//  Arguments are copied as they are and only formatted when a frame is written, which happens on
+//  a writer thread, so a traced call costs two clock reads and a push under a short lock.
+
+use crate::logger;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+//  "1" traces into the ring buffer only; a path also writes the first frame to it, or every frame
+//  when the path contains `{frame}`.
+pub const TRACE_ENV: &str = "RENDERING_PLUGIN_VULKAN_TRACE";
+pub const TRACE_CAPACITY_ENV: &str = "RENDERING_PLUGIN_VULKAN_TRACE_CAPACITY";
+
+const DEFAULT_CAPACITY: usize = 1 << 16;
+const FRAME_PLACEHOLDER: &str = "{frame}";
+//  Frames handed to the writer thread but not written yet. Presenting only waits for the writer
+//  when it falls this far behind.
+const MAX_QUEUED_FRAMES: usize = 4;
+
+//  Room for the arguments of every traced function, as a tuple.
+const ARG_WORDS: usize = 12;
+type RawArgs = [MaybeUninit<u64>; ARG_WORDS];
+
+#[derive(Clone, Copy)]
+struct TraceEntry {
+    frame: u64,
+    thread: u32,
+    name: &'static str,
+    args: RawArgs,
+    format_args: fn(&RawArgs) -> String,
+    start: Duration,
+    duration: Duration,
+}
+
+struct TraceState {
+    epoch: Instant,
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+    dump_path: Option<PathBuf>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static FRAME: AtomicU64 = AtomicU64::new(0);
+static NEXT_THREAD: AtomicU32 = AtomicU32::new(0);
+static STATE: Mutex<Option<TraceState>> = Mutex::new(None);
+//  A finished frame's entries and the file they go to.
+type FrameDump = (PathBuf, Vec<TraceEntry>);
+
+static WRITER: Mutex<Option<SyncSender<FrameDump>>> = Mutex::new(None);
+
+thread_local! {
+    static THREAD: u32 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+//  Has to happen before Unity creates its device, since function pointers are only wrapped when
+//  they are looked up.
+pub fn enable(capacity: usize, dump_path: Option<PathBuf>) {
+    *STATE.lock().unwrap() = Some(TraceState {
+        epoch: Instant::now(),
+        capacity: capacity.max(1),
+        entries: VecDeque::new(),
+        dump_path,
+    });
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enable_from_env() {
+    let value = match std::env::var_os(TRACE_ENV) {
+        Some(value) => value,
+        None => return,
+    };
+    let capacity = std::env::var(TRACE_CAPACITY_ENV)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(DEFAULT_CAPACITY);
+    let dump_path = match value.to_str() {
+        Some("0") => return,
+        Some("1") | Some("") => None,
+        _ => Some(PathBuf::from(value)),
+    };
+    enable(capacity, dump_path);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+//  Writes the next finished frame to `path`. Fails when tracing was not enabled at load.
+pub fn request_dump(path: &Path) -> bool {
+    match STATE.lock().unwrap().as_mut() {
+        Some(state) => {
+            state.dump_path = Some(path.to_path_buf());
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+pub fn recorded_calls() -> Vec<(&'static str, String)> {
+    match STATE.lock().unwrap().as_ref() {
+        Some(state) => state
+            .entries
+            .iter()
+            .map(|e| (e.name, (e.format_args)(&e.args)))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn record(call: &TracedCall, end: Instant) {
+    let thread = THREAD.with(|thread| *thread);
+    let frame = FRAME.load(Ordering::Relaxed);
+    if let Some(state) = STATE.lock().unwrap().as_mut() {
+        if state.entries.len() == state.capacity {
+            state.entries.pop_front();
+        }
+        state.entries.push_back(TraceEntry {
+            frame,
+            thread,
+            name: call.name,
+            args: call.args,
+            format_args: call.format_args,
+            start: call.start.saturating_duration_since(state.epoch),
+            duration: end - call.start,
+        });
+    }
+}
+
+fn end_frame() {
+    let frame = FRAME.fetch_add(1, Ordering::Relaxed);
+    let (path, entries) = {
+        let mut guard = STATE.lock().unwrap();
+        let state = match guard.as_mut() {
+            Some(state) => state,
+            None => return,
+        };
+        let path = match state.dump_path.as_ref() {
+            Some(path) => match frame_path(path, frame) {
+                Some(frame_path) => frame_path,
+                None => state.dump_path.take().unwrap(),
+            },
+            None => return,
+        };
+        let entries = state
+            .entries
+            .iter()
+            .filter(|e| e.frame == frame)
+            .copied()
+            .collect::<Vec<_>>();
+        (path, entries)
+    };
+    let mut writer = WRITER.lock().unwrap();
+    let sender = writer.get_or_insert_with(|| {
+        let (sender, receiver) = sync_channel::<FrameDump>(MAX_QUEUED_FRAMES);
+        std::thread::spawn(move || {
+            for (path, entries) in receiver {
+                write_frame(&path, &entries);
+            }
+        });
+        sender
+    });
+    if sender.send((path, entries)).is_err() {
+        logger::error("Vulkan trace writer thread stopped");
+        *writer = None;
+    }
+}
+
+fn write_frame(path: &Path, entries: &[TraceEntry]) {
+    let json = frame_json(entries.iter());
+    let result = std::fs::File::create(path).and_then(|mut file| file.write_all(json.as_bytes()));
+    if let Err(e) = result {
+        logger::error(&format!(
+            "failed to write the Vulkan trace to {}: {}",
+            path.display(),
+            e
+        ));
+    }
+}
+
+fn frame_path(path: &Path, frame: u64) -> Option<PathBuf> {
+    let path = path.to_str()?;
+    if path.contains(FRAME_PLACEHOLDER) {
+        Some(PathBuf::from(
+            path.replace(FRAME_PLACEHOLDER, &format!("{:06}", frame)),
+        ))
+    } else {
+        None
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+//  Complete ("X") events in microseconds. Entries that fell out of the ring buffer are missing.
+fn frame_json<'a>(entries: impl Iterator<Item = &'a TraceEntry>) -> String {
+    let events = entries
+        .map(|e| {
+            format!(
+                "{{\"name\":{},\"ph\":\"X\",\"pid\":0,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3},\"args\":{{\"frame\":{},\"call\":{}}}}}",
+                json_string(e.name),
+                e.thread,
+                e.start.as_secs_f64() * 1e6,
+                e.duration.as_secs_f64() * 1e6,
+                e.frame,
+                json_string(&(e.format_args)(&e.args))
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+}
+
+//  Records the call when dropped, i.e. right after the wrapped function returned.
+struct TracedCall {
+    name: &'static str,
+    args: RawArgs,
+    format_args: fn(&RawArgs) -> String,
+    start: Instant,
+}
+
+impl TracedCall {
+    //  `args` is the tuple of arguments, which `format_args` reads back.
+    fn new<T: Copy>(
+        name: &'static str,
+        args: T,
+        format_args: fn(&RawArgs) -> String,
+    ) -> TracedCall {
+        assert!(
+            std::mem::size_of::<T>() <= std::mem::size_of::<RawArgs>()
+                && std::mem::align_of::<T>() <= std::mem::align_of::<RawArgs>()
+        );
+        let mut raw = [MaybeUninit::uninit(); ARG_WORDS];
+        unsafe { std::ptr::write(raw.as_mut_ptr() as *mut T, args) };
+        TracedCall {
+            name,
+            args: raw,
+            format_args,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for TracedCall {
+    fn drop(&mut self) {
+        record(self, Instant::now());
+        if self.name == "vkQueuePresentKHR" {
+            end_frame();
+        }
+    }
+}
+
+//  Generates a wrapper per function that calls the real function (whose address `wrap` stores)
+//  and records the call. Arguments are written with their Debug representation, so pointers show
+//  as addresses and are not followed.
+macro_rules! traced_functions {
+    ($($name:ident = $symbol:literal ($($arg:ident: $ty:ty),*) $(-> $ret:ty)?;)*) => {
+        #[allow(non_camel_case_types)]
+        enum Traced {
+            $($name,)*
+            Count,
+        }
+
+        #[allow(clippy::declare_interior_mutable_const)]
+        const NO_FUNCTION: AtomicUsize = AtomicUsize::new(0);
+        static ORIGINALS: [AtomicUsize; Traced::Count as usize] = [NO_FUNCTION; Traced::Count as usize];
+
+        $(
+            #[allow(clippy::too_many_arguments)]
+            unsafe extern "system" fn $name($($arg: $ty),*) $(-> $ret)? {
+                let original: unsafe extern "system" fn($($ty),*) $(-> $ret)? =
+                    std::mem::transmute(ORIGINALS[Traced::$name as usize].load(Ordering::Relaxed));
+                fn format_args(args: &RawArgs) -> String {
+                    let ($($arg,)*) = unsafe { std::ptr::read(args.as_ptr() as *const ($($ty,)*)) };
+                    [$(format!(concat!(stringify!($arg), "={:?}"), $arg)),*].join(", ")
+                }
+                let _call = TracedCall::new($symbol, ($($arg,)*), format_args);
+                original($($arg),*)
+            }
+        )*
+
+        //  Returns the wrapper for a traced function and remembers `original` for it. Unity only
+        //  uses one device, so the last function looked up for a name is the one called.
+        pub fn wrap(name: &[u8], original: ash::vk::PFN_vkVoidFunction) -> ash::vk::PFN_vkVoidFunction {
+            let original = original?;
+            if !is_enabled() {
+                return Some(original);
+            }
+            let (index, wrapper): (usize, ash::vk::PFN_vkVoidFunction) = match name {
+                $(x if x == $symbol.as_bytes() => {
+                    let wrapper: unsafe extern "system" fn($($ty),*) $(-> $ret)? = $name;
+                    (Traced::$name as usize, unsafe { crate::vulkan_api::hook(wrapper) })
+                })*
+                _ => return Some(original),
+            };
+            ORIGINALS[index].store(original as usize, Ordering::Relaxed);
+            wrapper
+        }
+    };
+}
+
+traced_functions! {
+    queue_submit = "vkQueueSubmit"(
+        queue: ash::vk::Queue,
+        submit_count: u32,
+        p_submits: *const ash::vk::SubmitInfo,
+        fence: ash::vk::Fence
+    ) -> ash::vk::Result;
+    queue_present_khr = "vkQueuePresentKHR"(
+        queue: ash::vk::Queue,
+        p_present_info: *const ash::vk::PresentInfoKHR
+    ) -> ash::vk::Result;
+    acquire_next_image_khr = "vkAcquireNextImageKHR"(
+        device: ash::vk::Device,
+        swapchain: ash::vk::SwapchainKHR,
+        timeout: u64,
+        semaphore: ash::vk::Semaphore,
+        fence: ash::vk::Fence,
+        p_image_index: *mut u32
+    ) -> ash::vk::Result;
+    queue_wait_idle = "vkQueueWaitIdle"(queue: ash::vk::Queue) -> ash::vk::Result;
+    device_wait_idle = "vkDeviceWaitIdle"(device: ash::vk::Device) -> ash::vk::Result;
+    wait_for_fences = "vkWaitForFences"(
+        device: ash::vk::Device,
+        fence_count: u32,
+        p_fences: *const ash::vk::Fence,
+        wait_all: ash::vk::Bool32,
+        timeout: u64
+    ) -> ash::vk::Result;
+    allocate_memory = "vkAllocateMemory"(
+        device: ash::vk::Device,
+        p_allocate_info: *const ash::vk::MemoryAllocateInfo,
+        p_allocator: *const ash::vk::AllocationCallbacks,
+        p_memory: *mut ash::vk::DeviceMemory
+    ) -> ash::vk::Result;
+    free_memory = "vkFreeMemory"(
+        device: ash::vk::Device,
+        memory: ash::vk::DeviceMemory,
+        p_allocator: *const ash::vk::AllocationCallbacks
+    );
+    create_buffer = "vkCreateBuffer"(
+        device: ash::vk::Device,
+        p_create_info: *const ash::vk::BufferCreateInfo,
+        p_allocator: *const ash::vk::AllocationCallbacks,
+        p_buffer: *mut ash::vk::Buffer
+    ) -> ash::vk::Result;
+    destroy_buffer = "vkDestroyBuffer"(
+        device: ash::vk::Device,
+        buffer: ash::vk::Buffer,
+        p_allocator: *const ash::vk::AllocationCallbacks
+    );
+    create_image = "vkCreateImage"(
+        device: ash::vk::Device,
+        p_create_info: *const ash::vk::ImageCreateInfo,
+        p_allocator: *const ash::vk::AllocationCallbacks,
+        p_image: *mut ash::vk::Image
+    ) -> ash::vk::Result;
+    destroy_image = "vkDestroyImage"(
+        device: ash::vk::Device,
+        image: ash::vk::Image,
+        p_allocator: *const ash::vk::AllocationCallbacks
+    );
+    begin_command_buffer = "vkBeginCommandBuffer"(
+        command_buffer: ash::vk::CommandBuffer,
+        p_begin_info: *const ash::vk::CommandBufferBeginInfo
+    ) -> ash::vk::Result;
+    end_command_buffer = "vkEndCommandBuffer"(
+        command_buffer: ash::vk::CommandBuffer
+    ) -> ash::vk::Result;
+    cmd_begin_render_pass = "vkCmdBeginRenderPass"(
+        command_buffer: ash::vk::CommandBuffer,
+        p_render_pass_begin: *const ash::vk::RenderPassBeginInfo,
+        contents: ash::vk::SubpassContents
+    );
+    cmd_end_render_pass = "vkCmdEndRenderPass"(command_buffer: ash::vk::CommandBuffer);
+    cmd_draw = "vkCmdDraw"(
+        command_buffer: ash::vk::CommandBuffer,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32
+    );
+    cmd_draw_indexed = "vkCmdDrawIndexed"(
+        command_buffer: ash::vk::CommandBuffer,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32
+    );
+    cmd_dispatch = "vkCmdDispatch"(
+        command_buffer: ash::vk::CommandBuffer,
+        group_count_x: u32,
+        group_count_y: u32,
+        group_count_z: u32
+    );
+    cmd_copy_buffer = "vkCmdCopyBuffer"(
+        command_buffer: ash::vk::CommandBuffer,
+        src_buffer: ash::vk::Buffer,
+        dst_buffer: ash::vk::Buffer,
+        region_count: u32,
+        p_regions: *const ash::vk::BufferCopy
+    );
+    cmd_copy_buffer_to_image = "vkCmdCopyBufferToImage"(
+        command_buffer: ash::vk::CommandBuffer,
+        src_buffer: ash::vk::Buffer,
+        dst_image: ash::vk::Image,
+        dst_image_layout: ash::vk::ImageLayout,
+        region_count: u32,
+        p_regions: *const ash::vk::BufferImageCopy
+    );
+    cmd_pipeline_barrier = "vkCmdPipelineBarrier"(
+        command_buffer: ash::vk::CommandBuffer,
+        src_stage_mask: ash::vk::PipelineStageFlags,
+        dst_stage_mask: ash::vk::PipelineStageFlags,
+        dependency_flags: ash::vk::DependencyFlags,
+        memory_barrier_count: u32,
+        p_memory_barriers: *const ash::vk::MemoryBarrier,
+        buffer_memory_barrier_count: u32,
+        p_buffer_memory_barriers: *const ash::vk::BufferMemoryBarrier,
+        image_memory_barrier_count: u32,
+        p_image_memory_barriers: *const ash::vk::ImageMemoryBarrier
+    );
+}
+
+#[test]
+fn test_trace_json() {
+    assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    let entries = [TraceEntry {
+        frame: 3,
+        thread: 1,
+        name: "vkCmdDraw",
+        args: [MaybeUninit::uninit(); ARG_WORDS],
+        format_args: |_| "vertex_count=3".to_string(),
+        start: Duration::from_micros(1500),
+        duration: Duration::from_nanos(250),
+    }];
+    assert_eq!(
+        frame_json(entries.iter()),
+        "{\"traceEvents\":[\n{\"name\":\"vkCmdDraw\",\"ph\":\"X\",\"pid\":0,\"tid\":1,\"ts\":1500.000,\"dur\":0.250,\"args\":{\"frame\":3,\"call\":\"vertex_count=3\"}}\n]}\n"
+    );
+    assert_eq!(
+        frame_path(Path::new("trace_{frame}.json"), 7),
+        Some(PathBuf::from("trace_000007.json"))
+    );
+}